@@ -1,6 +1,12 @@
 use crate::{
-  Action, ActionDriver, ExecutionContext, ExecutionContextBuilder, GithubAuthorization, JobId,
-  Plugin, PluginDriver, Result, Runner, SharedActionDriver, SharedPluginDriver, SignalManager,
+  Action, ActionDriver, ChangedFilesProvider, ChangedFilesProviderDriver, CronScheduler,
+  CronSchedulerBuilder, EnvironmentVariables, Error, EventMatcher, EventMatcherDriver, EventSender,
+  EventStream, ExecutionContext, ExecutionContextBuilder, GiteaChangedFilesProvider,
+  GithubAuthorization, GithubChangedFilesProvider, GitlabChangedFilesProvider, JobId, Plugin,
+  PluginDriver, PluginFailurePolicy, Result, RunPage, RunQuery, Runner, SharedActionDriver,
+  SharedChangedFilesProvider, SharedEventMatcherDriver, SharedPluginDriver, SharedStateStore,
+  SignalManager, StateRecorder, StateStore, TriggerEvent, Workflow, WorkflowId, WorkflowRunRecord,
+  WorkflowRunResult, DEFAULT_EVENT_CAPACITY,
 };
 use std::{collections::HashMap, sync::Arc};
 
@@ -8,9 +14,13 @@ use std::{collections::HashMap, sync::Arc};
 pub struct AstroRun {
   runner: Arc<Box<dyn Runner>>,
   github_auth: Option<GithubAuthorization>,
+  changed_files_provider: Option<SharedChangedFilesProvider>,
   plugin_driver: SharedPluginDriver,
   action_driver: SharedActionDriver,
+  event_matcher_driver: SharedEventMatcherDriver,
   signal_manager: SignalManager,
+  state_store: Option<SharedStateStore>,
+  event_sender: EventSender,
 }
 
 impl AstroRun {
@@ -26,15 +36,103 @@ impl AstroRun {
     let mut builder = ExecutionContext::builder()
       .runner(self.runner.clone())
       .signal_manager(self.signal_manager.clone())
-      .plugin_driver(self.plugin_driver());
+      .plugin_driver(self.plugin_driver())
+      .event_sender(self.event_sender.clone())
+      .event_matcher_driver(Arc::clone(&self.event_matcher_driver));
 
     if let Some(github_auth) = &self.github_auth {
       builder = builder.github_auth(github_auth.clone());
     }
 
+    if let Some(changed_files_provider) = &self.changed_files_provider {
+      builder = builder.changed_files_provider(Arc::clone(changed_files_provider));
+    }
+
     builder
   }
 
+  /// Streams the events of all the workflow runs started after subscribing.
+  ///
+  /// Each subscriber buffers up to `AstroRunBuilder::event_capacity` events, and receives
+  /// `AstroRunEvent::Lagged` when it falls further behind.
+  pub fn subscribe(&self) -> EventStream {
+    EventStream::new(self.event_sender.subscribe())
+  }
+
+  pub fn cron_scheduler(&self) -> CronSchedulerBuilder {
+    CronScheduler::builder(self.clone())
+  }
+
+  /// Manually triggers a workflow with the `workflow_dispatch` event.
+  ///
+  /// The inputs are validated against `on.workflow_dispatch.inputs` before the run.
+  pub async fn dispatch(
+    &self,
+    workflow: &Workflow,
+    event: TriggerEvent,
+    inputs: EnvironmentVariables,
+  ) -> Result<WorkflowRunResult> {
+    let condition = workflow.workflow_dispatch().ok_or_else(|| {
+      Error::workflow_config_error("Workflow does not support the `workflow_dispatch` event")
+    })?;
+
+    let inputs = condition.validate_inputs(inputs)?;
+    let workflow = workflow.with_inputs(&inputs);
+
+    let mut event = event;
+    event.event = "workflow_dispatch".to_string();
+    event.payload = Some(serde_json::json!({ "inputs": inputs }));
+
+    let ctx = self.execution_context().event(event).build();
+
+    Ok(workflow.run(ctx).await)
+  }
+
+  pub fn state_store(&self) -> Option<SharedStateStore> {
+    self.state_store.clone()
+  }
+
+  /// Lists the recorded runs matching the query. Requires a state store
+  pub async fn query_runs(&self, query: &RunQuery) -> Result<RunPage> {
+    self.require_state_store()?.query(query).await
+  }
+
+  /// Returns a recorded run with its job and step results. Requires a state store
+  pub async fn get_run(&self, id: &WorkflowId) -> Result<Option<WorkflowRunRecord>> {
+    self.require_state_store()?.get(id).await
+  }
+
+  fn require_state_store(&self) -> Result<&SharedStateStore> {
+    self
+      .state_store
+      .as_ref()
+      .ok_or_else(|| Error::init_error("State store is not configured"))
+  }
+
+  /// Marks the runs left unfinished by a previous process as failed and `interrupted`.
+  ///
+  /// Call it on startup, before any workflow runs. Returns the interrupted runs.
+  pub async fn recover_interrupted_runs(&self) -> Result<Vec<WorkflowRunRecord>> {
+    let Some(state_store) = &self.state_store else {
+      return Ok(vec![]);
+    };
+
+    let mut interrupted = vec![];
+
+    for mut record in state_store.list().await? {
+      if record.state.is_terminal() {
+        continue;
+      }
+
+      log::warn!("Workflow {} was interrupted", record.id);
+      record.mark_interrupted();
+      state_store.save(record.clone()).await?;
+      interrupted.push(record);
+    }
+
+    Ok(interrupted)
+  }
+
   pub(crate) fn plugin_driver(&self) -> SharedPluginDriver {
     Arc::clone(&self.plugin_driver)
   }
@@ -49,7 +147,13 @@ pub struct AstroRunBuilder {
   runner: Option<Box<dyn Runner>>,
   plugins: Vec<Box<dyn Plugin>>,
   actions: HashMap<String, Box<dyn Action>>,
+  event_matchers: HashMap<String, Box<dyn EventMatcher>>,
   github_auth: Option<GithubAuthorization>,
+  changed_files_provider: Option<Box<dyn ChangedFilesProvider>>,
+  changed_files_providers: HashMap<String, Box<dyn ChangedFilesProvider>>,
+  state_store: Option<Box<dyn StateStore>>,
+  event_capacity: Option<usize>,
+  plugin_failure_policy: PluginFailurePolicy,
 }
 
 impl AstroRunBuilder {
@@ -77,6 +181,17 @@ impl AstroRunBuilder {
     self
   }
 
+  /// Registers a matcher for the conditions of `event`, such as `release` or a custom event
+  pub fn event_matcher(
+    mut self,
+    event: impl Into<String>,
+    matcher: impl EventMatcher + 'static,
+  ) -> Self {
+    self.event_matchers.insert(event.into(), Box::new(matcher));
+
+    self
+  }
+
   pub fn github_personal_token(mut self, token: impl Into<String>) -> Self {
     self.github_auth = Some(GithubAuthorization::PersonalAccessToken(token.into()));
     self
@@ -91,15 +206,92 @@ impl AstroRunBuilder {
     self
   }
 
-  pub fn build(self) -> AstroRun {
+  /// Uses the GitLab API for events whose `provider` is `gitlab`
+  pub fn gitlab(mut self, base_url: impl Into<String>, token: impl Into<String>) -> Self {
+    self.changed_files_providers.insert(
+      "gitlab".to_string(),
+      Box::new(GitlabChangedFilesProvider::new(base_url, token)),
+    );
+
+    self
+  }
+
+  /// Uses the Gitea API for events whose `provider` is `gitea`
+  pub fn gitea(mut self, base_url: impl Into<String>, token: impl Into<String>) -> Self {
+    self.changed_files_providers.insert(
+      "gitea".to_string(),
+      Box::new(GiteaChangedFilesProvider::new(base_url, token)),
+    );
+
+    self
+  }
+
+  /// Resolves changed files for `paths` filters of all events, for example
+  /// `LocalGitChangedFilesProvider`. Takes precedence over the GitHub, GitLab and Gitea APIs.
+  pub fn changed_files_provider(mut self, provider: impl ChangedFilesProvider + 'static) -> Self {
+    self.changed_files_provider = Some(Box::new(provider));
+
+    self
+  }
+
+  /// Persists the state of every run, see `FileStateStore`
+  pub fn state_store(mut self, state_store: impl StateStore + 'static) -> Self {
+    self.state_store = Some(Box::new(state_store));
+
+    self
+  }
+
+  /// What happens when a plugin hook fails, defaults to logging the error
+  pub fn plugin_failure_policy(mut self, policy: PluginFailurePolicy) -> Self {
+    self.plugin_failure_policy = policy;
+
+    self
+  }
+
+  /// Number of events buffered for each subscriber, defaults to 1024
+  pub fn event_capacity(mut self, capacity: usize) -> Self {
+    self.event_capacity = Some(capacity);
+
+    self
+  }
+
+  pub fn build(mut self) -> AstroRun {
     let runner = self.runner.unwrap();
 
+    if let Some(github_auth) = &self.github_auth {
+      self.changed_files_providers.insert(
+        "github".to_string(),
+        Box::new(GithubChangedFilesProvider::new(github_auth.clone())),
+      );
+    }
+
+    let driver = ChangedFilesProviderDriver::new(self.changed_files_providers);
+    let changed_files_provider = self
+      .changed_files_provider
+      .or_else(|| (!driver.is_empty()).then(|| Box::new(driver) as Box<dyn ChangedFilesProvider>));
+
+    let state_store: Option<SharedStateStore> = self.state_store.map(Arc::new);
+    if let Some(state_store) = &state_store {
+      self
+        .plugins
+        .push(Box::new(StateRecorder::new(Arc::clone(state_store))));
+    }
+
     AstroRun {
       runner: Arc::new(runner),
-      plugin_driver: Arc::new(PluginDriver::new(self.plugins)),
+      plugin_driver: Arc::new(
+        PluginDriver::new(self.plugins).failure_policy(self.plugin_failure_policy),
+      ),
       action_driver: Arc::new(ActionDriver::new(self.actions)),
+      event_matcher_driver: Arc::new(EventMatcherDriver::new(self.event_matchers)),
       signal_manager: SignalManager::new(),
       github_auth: self.github_auth,
+      changed_files_provider: changed_files_provider.map(Arc::new),
+      state_store,
+      event_sender: tokio::sync::broadcast::channel(
+        self.event_capacity.unwrap_or(DEFAULT_EVENT_CAPACITY),
+      )
+      .0,
     }
   }
 }
@@ -0,0 +1,245 @@
+use super::{check_sha, ChangedFilesProvider, PATH_SEGMENT};
+use crate::{Error, Result, TriggerEvent};
+use percent_encoding::utf8_percent_encode;
+use serde::{de::DeserializeOwned, Deserialize};
+
+const LIMIT: usize = 50;
+
+#[derive(Deserialize)]
+struct ChangedFile {
+  filename: String,
+}
+
+#[derive(Deserialize)]
+struct Commit {
+  #[serde(default)]
+  files: Vec<ChangedFile>,
+}
+
+#[derive(Deserialize)]
+struct Compare {
+  #[serde(default)]
+  commits: Vec<Commit>,
+}
+
+/// Resolves changed files with the Gitea (and Forgejo) REST API.
+///
+/// Pull requests are looked up by `pr_number` (the pull request index).
+pub struct GiteaChangedFilesProvider {
+  base_url: String,
+  token: String,
+  client: reqwest::Client,
+}
+
+impl GiteaChangedFilesProvider {
+  /// `base_url` is the Gitea instance, for example `https://gitea.com`
+  pub fn new(base_url: impl Into<String>, token: impl Into<String>) -> Self {
+    Self {
+      base_url: base_url.into().trim_end_matches('/').to_string(),
+      token: token.into(),
+      client: reqwest::Client::new(),
+    }
+  }
+
+  async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T> {
+    let url = format!("{}/api/v1{}", self.base_url, path);
+
+    let response = self
+      .client
+      .get(&url)
+      .header("Authorization", format!("token {}", self.token))
+      .query(query)
+      .send()
+      .await
+      .and_then(|response| response.error_for_status())
+      .map_err(|err| Error::internal_runtime_error(format!("Failed to request Gitea: {}", err)))?;
+
+    response
+      .json()
+      .await
+      .map_err(|err| Error::internal_runtime_error(format!("Invalid Gitea response: {}", err)))
+  }
+
+  async fn get_pull_request_files(&self, repo: &str, index: i64) -> Result<Vec<ChangedFile>> {
+    let mut files = vec![];
+
+    for page in 1.. {
+      let items: Vec<ChangedFile> = self
+        .get(
+          &format!("{}/pulls/{}/files", repo, index),
+          &[("page", page.to_string()), ("limit", LIMIT.to_string())],
+        )
+        .await?;
+      let len = items.len();
+
+      files.extend(items);
+
+      if len < LIMIT {
+        break;
+      }
+    }
+
+    Ok(files)
+  }
+}
+
+#[async_trait::async_trait]
+impl ChangedFilesProvider for GiteaChangedFilesProvider {
+  async fn changed_files(&self, event: &TriggerEvent) -> Result<Vec<String>> {
+    let repo = format!(
+      "/repos/{}/{}",
+      utf8_percent_encode(&event.repo_owner, PATH_SEGMENT),
+      utf8_percent_encode(&event.repo_name, PATH_SEGMENT)
+    );
+
+    let files = match event.event.as_str() {
+      "push" => match event.before_sha() {
+        Some(before) => {
+          let compare: Compare = self
+            .get(
+              &format!(
+                "{}/compare/{}...{}",
+                repo,
+                check_sha(before)?,
+                check_sha(&event.sha)?
+              ),
+              &[],
+            )
+            .await?;
+
+          compare
+            .commits
+            .into_iter()
+            .flat_map(|commit| commit.files)
+            .collect()
+        }
+        None => {
+          let commit: Commit = self
+            .get(
+              &format!("{}/git/commits/{}", repo, check_sha(&event.sha)?),
+              &[],
+            )
+            .await?;
+
+          commit.files
+        }
+      },
+      "pull_request" => {
+        let index = event
+          .pr_number
+          .ok_or(Error::workflow_config_error("pr_number is not provided"))?;
+
+        self.get_pull_request_files(&repo, index).await?
+      }
+      _ => {
+        return Err(Error::unsupported_feature(format!(
+          "Event {} is not supported",
+          event.event
+        )))
+      }
+    };
+
+    let mut filenames: Vec<String> = vec![];
+    for file in files {
+      if !filenames.contains(&file.filename) {
+        filenames.push(file.filename);
+      }
+    }
+
+    Ok(filenames)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::changed_files::mock_server::MockServer;
+  use std::collections::HashMap;
+
+  #[astro_run_test::test]
+  async fn test_gitea_changed_files() {
+    let server = MockServer::start(HashMap::from([
+      (
+        "/api/v1/repos/panghu-huang/astro-run/compare/1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b...8d3c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d",
+        serde_json::json!({
+          "total_commits": 2,
+          "commits": [
+            { "files": [{ "filename": "src/lib.rs", "status": "modified" }] },
+            { "files": [
+              { "filename": "src/lib.rs", "status": "modified" },
+              { "filename": "README.md", "status": "added" },
+            ] },
+          ]
+        }),
+      ),
+      (
+        "/api/v1/repos/panghu-huang/astro-run/git/commits/8d3c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d",
+        serde_json::json!({ "sha": "8d3c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d", "files": [{ "filename": "Cargo.toml" }] }),
+      ),
+      (
+        "/api/v1/repos/panghu-huang/astro-run/pulls/3/files",
+        serde_json::json!([{ "filename": "docs/a.md" }]),
+      ),
+    ]))
+    .await;
+
+    let provider = GiteaChangedFilesProvider::new(&server.base_url, "gitea-token");
+    let event = TriggerEvent {
+      provider: Some("gitea".to_string()),
+      sha: "8d3c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d".to_string(),
+      ..Default::default()
+    };
+
+    let files = provider
+      .changed_files(&TriggerEvent {
+        before: Some("1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b".to_string()),
+        ..event.clone()
+      })
+      .await
+      .unwrap();
+    assert_eq!(files, vec!["src/lib.rs", "README.md"]);
+
+    let files = provider.changed_files(&event).await.unwrap();
+    assert_eq!(files, vec!["Cargo.toml"]);
+
+    let files = provider
+      .changed_files(&TriggerEvent {
+        event: "pull_request".to_string(),
+        pr_number: Some(3),
+        ..event.clone()
+      })
+      .await
+      .unwrap();
+    assert_eq!(files, vec!["docs/a.md"]);
+
+    let error = provider
+      .changed_files(&TriggerEvent {
+        before: Some("../../../user".to_string()),
+        ..event.clone()
+      })
+      .await
+      .unwrap_err();
+    assert_eq!(
+      error,
+      Error::workflow_config_error("Invalid commit sha `../../../user`")
+    );
+
+    // Path segments of the repository are encoded
+    provider
+      .changed_files(&TriggerEvent {
+        repo_owner: "../admin".to_string(),
+        ..event.clone()
+      })
+      .await
+      .unwrap_err();
+
+    let requests = server.requests.lock();
+    assert_eq!(requests.len(), 4);
+    assert!(requests[3].contains(
+      "/api/v1/repos/..%2Fadmin/astro-run/git/commits/8d3c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d"
+    ));
+    assert!(requests.iter().all(|request| request
+      .to_lowercase()
+      .contains("authorization: token gitea-token")));
+  }
+}
@@ -0,0 +1,174 @@
+use super::ChangedFilesProvider;
+use crate::{Error, GithubAuthorization, Result, TriggerEvent};
+use octocrate::{APIConfig, AppAuthorization, GitHubAPI, PersonalAccessToken};
+
+/// Resolves changed files with the GitHub API
+pub struct GithubChangedFilesProvider {
+  github_auth: GithubAuthorization,
+}
+
+impl GithubChangedFilesProvider {
+  pub fn new(github_auth: GithubAuthorization) -> Self {
+    Self { github_auth }
+  }
+
+  async fn get_push_changed_files(&self, event: &TriggerEvent) -> Result<Vec<String>> {
+    let TriggerEvent {
+      repo_owner,
+      repo_name,
+      sha,
+      ..
+    } = event;
+    let github_api = self.get_github_api_by_repo(repo_owner, repo_name).await?;
+
+    let commit = github_api
+      .repos
+      .get_commit(repo_owner, repo_name, sha)
+      .send()
+      .await
+      .map_err(|e| Error::internal_runtime_error(format!("Failed to get commit: {}", e)))?;
+
+    let files: Vec<String> = commit
+      .files
+      .map(|files| files.iter().map(|f| f.filename.clone()).collect())
+      .unwrap_or(vec![]);
+
+    Ok(files)
+  }
+
+  async fn get_pull_request_changed_files(&self, event: &TriggerEvent) -> Result<Vec<String>> {
+    let TriggerEvent {
+      repo_owner,
+      repo_name,
+      pr_number,
+      ..
+    } = event;
+    let github_api = self.get_github_api_by_repo(repo_owner, repo_name).await?;
+
+    let pull_request_files = github_api
+      .pulls
+      .list_files(
+        repo_owner,
+        repo_name,
+        pr_number.ok_or(Error::workflow_config_error("pr_number is not provided"))?,
+      )
+      .send()
+      .await
+      .map_err(|e| {
+        Error::internal_runtime_error(format!("Failed to get pull request files: {}", e))
+      })?;
+
+    Ok(pull_request_files.into_iter().map(|f| f.filename).collect())
+  }
+
+  async fn get_github_api_by_repo(
+    &self,
+    repo_owner: &String,
+    repo_name: &String,
+  ) -> crate::Result<GitHubAPI> {
+    if self.github_auth.is_personal_access_token() {
+      return Ok(self.create_github_api());
+    }
+
+    self
+      .create_github_api_by_app_authorization(repo_owner, repo_name)
+      .await
+  }
+
+  async fn create_github_api_by_app_authorization(
+    &self,
+    repo_owner: &String,
+    repo_name: &String,
+  ) -> crate::Result<GitHubAPI> {
+    let github_api = self.create_github_api();
+
+    let installation = github_api
+      .apps
+      .get_repo_installation(repo_owner, repo_name)
+      .send()
+      .await
+      .map_err(|err| {
+        Error::internal_runtime_error(format!(
+          "Failed to get installation for repository: {}",
+          err
+        ))
+      })?;
+
+    let installation_token = github_api
+      .apps
+      .create_installation_access_token(installation.id)
+      .send()
+      .await
+      .map_err(|err| {
+        Error::internal_runtime_error(format!(
+          "Failed to create installation access token: {}",
+          err
+        ))
+      })?;
+
+    let config = APIConfig::with_token(installation_token).shared();
+
+    Ok(GitHubAPI::new(&config))
+  }
+
+  fn create_github_api(&self) -> GitHubAPI {
+    match &self.github_auth {
+      GithubAuthorization::PersonalAccessToken(token) => {
+        let access_token = PersonalAccessToken::new(token);
+
+        let config = APIConfig::with_token(access_token).shared();
+
+        GitHubAPI::new(&config)
+      }
+      GithubAuthorization::GithubApp {
+        app_id,
+        private_key,
+      } => {
+        let authorization = AppAuthorization::new(app_id.to_string(), private_key);
+
+        let config = APIConfig::with_token(authorization).shared();
+
+        GitHubAPI::new(&config)
+      }
+    }
+  }
+}
+
+#[async_trait::async_trait]
+impl ChangedFilesProvider for GithubChangedFilesProvider {
+  async fn changed_files(&self, event: &TriggerEvent) -> Result<Vec<String>> {
+    match event.event.as_str() {
+      "push" => self.get_push_changed_files(event).await,
+      "pull_request" => self.get_pull_request_changed_files(event).await,
+      _ => Err(Error::unsupported_feature(format!(
+        "Event {} is not supported",
+        event.event
+      ))),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[astro_run_test::test]
+  async fn invalid_github_app_id() {
+    dotenv::dotenv().ok();
+
+    let private_key = std::env::var("GH_APP_PRIVATE_KEY")
+      .map_err(|err| crate::Error::internal_runtime_error(format!("GH_APP_PRIVATE_KEY: {}", err)))
+      .unwrap();
+
+    let provider = GithubChangedFilesProvider::new(GithubAuthorization::GithubApp {
+      app_id: 0,
+      private_key,
+    });
+
+    let res = provider
+      .get_github_api_by_repo(&"panghu-huang".to_string(), &"astro-run".to_string())
+      .await;
+
+    assert!(res.is_err());
+  }
+}
@@ -0,0 +1,255 @@
+use super::{check_sha, ChangedFilesProvider, PATH_SEGMENT};
+use crate::{Error, Result, TriggerEvent};
+use percent_encoding::utf8_percent_encode;
+use serde::{de::DeserializeOwned, Deserialize};
+
+const PER_PAGE: usize = 100;
+
+#[derive(Deserialize)]
+struct Diff {
+  old_path: String,
+  new_path: String,
+}
+
+#[derive(Deserialize)]
+struct Compare {
+  diffs: Vec<Diff>,
+}
+
+/// Resolves changed files with the GitLab REST API (v4).
+///
+/// The project is `repo_owner/repo_name`, where `repo_owner` may contain subgroups.
+/// Merge requests are looked up by `pr_number` (the merge request IID).
+pub struct GitlabChangedFilesProvider {
+  base_url: String,
+  token: String,
+  client: reqwest::Client,
+}
+
+impl GitlabChangedFilesProvider {
+  /// `base_url` is the GitLab instance, for example `https://gitlab.com`
+  pub fn new(base_url: impl Into<String>, token: impl Into<String>) -> Self {
+    Self {
+      base_url: base_url.into().trim_end_matches('/').to_string(),
+      token: token.into(),
+      client: reqwest::Client::new(),
+    }
+  }
+
+  async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T> {
+    let url = format!("{}/api/v4{}", self.base_url, path);
+
+    let response = self
+      .client
+      .get(&url)
+      .header("PRIVATE-TOKEN", &self.token)
+      .query(query)
+      .send()
+      .await
+      .and_then(|response| response.error_for_status())
+      .map_err(|err| Error::internal_runtime_error(format!("Failed to request GitLab: {}", err)))?;
+
+    response
+      .json()
+      .await
+      .map_err(|err| Error::internal_runtime_error(format!("Invalid GitLab response: {}", err)))
+  }
+
+  async fn get_paginated_diffs(&self, path: &str) -> Result<Vec<Diff>> {
+    let mut diffs = vec![];
+
+    for page in 1.. {
+      let items: Vec<Diff> = self
+        .get(
+          path,
+          &[
+            ("page", page.to_string()),
+            ("per_page", PER_PAGE.to_string()),
+          ],
+        )
+        .await?;
+      let len = items.len();
+
+      diffs.extend(items);
+
+      if len < PER_PAGE {
+        break;
+      }
+    }
+
+    Ok(diffs)
+  }
+
+  fn project_path(event: &TriggerEvent) -> String {
+    let project = format!("{}/{}", event.repo_owner, event.repo_name);
+
+    // `group/project` becomes `group%2Fproject`
+    format!("/projects/{}", utf8_percent_encode(&project, PATH_SEGMENT))
+  }
+}
+
+#[async_trait::async_trait]
+impl ChangedFilesProvider for GitlabChangedFilesProvider {
+  async fn changed_files(&self, event: &TriggerEvent) -> Result<Vec<String>> {
+    let project = Self::project_path(event);
+
+    let diffs = match event.event.as_str() {
+      "push" => match event.before_sha() {
+        Some(before) => {
+          let compare: Compare = self
+            .get(
+              &format!("{}/repository/compare", project),
+              &[
+                ("from", check_sha(before)?.to_string()),
+                ("to", check_sha(&event.sha)?.to_string()),
+              ],
+            )
+            .await?;
+
+          compare.diffs
+        }
+        None => {
+          self
+            .get_paginated_diffs(&format!(
+              "{}/repository/commits/{}/diff",
+              project,
+              check_sha(&event.sha)?
+            ))
+            .await?
+        }
+      },
+      "pull_request" => {
+        let iid = event
+          .pr_number
+          .ok_or(Error::workflow_config_error("pr_number is not provided"))?;
+
+        self
+          .get_paginated_diffs(&format!("{}/merge_requests/{}/diffs", project, iid))
+          .await?
+      }
+      _ => {
+        return Err(Error::unsupported_feature(format!(
+          "Event {} is not supported",
+          event.event
+        )))
+      }
+    };
+
+    let mut files = vec![];
+    for diff in diffs {
+      if diff.old_path != diff.new_path {
+        files.push(diff.old_path);
+      }
+      files.push(diff.new_path);
+    }
+
+    Ok(files)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::changed_files::mock_server::MockServer;
+  use std::collections::HashMap;
+
+  #[astro_run_test::test]
+  async fn test_gitlab_changed_files() {
+    let server = MockServer::start(HashMap::from([
+      (
+        "/api/v4/projects/group%2Fsub%2Fastro-run/repository/compare",
+        serde_json::json!({
+          "diffs": [
+            { "old_path": "src/old.rs", "new_path": "src/new.rs" },
+            { "old_path": "README.md", "new_path": "README.md" },
+          ]
+        }),
+      ),
+      (
+        "/api/v4/projects/group%2Fsub%2Fastro-run/repository/commits/8d3c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d/diff",
+        serde_json::json!([{ "old_path": "Cargo.toml", "new_path": "Cargo.toml" }]),
+      ),
+      (
+        "/api/v4/projects/group%2Fsub%2Fastro-run/merge_requests/7/diffs",
+        serde_json::json!([{ "old_path": "docs/a.md", "new_path": "docs/a.md" }]),
+      ),
+    ]))
+    .await;
+
+    let provider = GitlabChangedFilesProvider::new(&server.base_url, "gitlab-token");
+    let event = TriggerEvent {
+      provider: Some("gitlab".to_string()),
+      repo_owner: "group/sub".to_string(),
+      sha: "8d3c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d".to_string(),
+      ..Default::default()
+    };
+
+    let files = provider
+      .changed_files(&TriggerEvent {
+        before: Some("1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b".to_string()),
+        ..event.clone()
+      })
+      .await
+      .unwrap();
+    assert_eq!(files, vec!["src/old.rs", "src/new.rs", "README.md"]);
+
+    // A new branch has no previous head
+    let files = provider
+      .changed_files(&TriggerEvent {
+        before: Some("0000000000000000000000000000000000000000".to_string()),
+        ..event.clone()
+      })
+      .await
+      .unwrap();
+    assert_eq!(files, vec!["Cargo.toml"]);
+
+    let files = provider
+      .changed_files(&TriggerEvent {
+        event: "pull_request".to_string(),
+        pr_number: Some(7),
+        ..event.clone()
+      })
+      .await
+      .unwrap();
+    assert_eq!(files, vec!["docs/a.md"]);
+
+    let error = provider
+      .changed_files(&TriggerEvent {
+        sha: "../../../user".to_string(),
+        ..event.clone()
+      })
+      .await
+      .unwrap_err();
+    assert_eq!(
+      error,
+      Error::workflow_config_error("Invalid commit sha `../../../user`")
+    );
+
+    let requests = server.requests.lock();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[0].contains(
+      "from=1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b&to=8d3c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d"
+    ));
+    assert!(requests.iter().all(|request| request
+      .to_lowercase()
+      .contains("private-token: gitlab-token")));
+  }
+
+  #[astro_run_test::test]
+  async fn test_gitlab_request_error() {
+    let server = MockServer::start(HashMap::new()).await;
+    let provider = GitlabChangedFilesProvider::new(&server.base_url, "gitlab-token");
+
+    let error = provider
+      .changed_files(&TriggerEvent {
+        sha: "8d3c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d".to_string(),
+        ..Default::default()
+      })
+      .await
+      .unwrap_err();
+
+    assert!(error
+      .to_string()
+      .contains("Failed to request GitLab: HTTP status client error (404 Not Found)"));
+  }
+}
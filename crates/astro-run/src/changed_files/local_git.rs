@@ -0,0 +1,342 @@
+use super::{check_sha, is_sha, ChangedFilesProvider};
+use crate::{Error, Result, TriggerEvent};
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// Resolves changed files from a git checkout on disk.
+///
+/// For `push` events, `sha` is diffed against `before`, or its first parent (the empty tree for
+/// a root commit) if `before` is not provided.
+/// For `pull_request` events, `sha` is diffed against its merge base with the PR base, which is
+/// `TriggerEvent::base_ref`, `pull_request.base.sha` / `pull_request.base.ref` in the event
+/// payload, or the configured `base_ref`.
+pub struct LocalGitChangedFilesProvider {
+  repo_dir: PathBuf,
+  base_ref: Option<String>,
+}
+
+impl LocalGitChangedFilesProvider {
+  pub fn new(repo_dir: impl Into<PathBuf>) -> Self {
+    Self {
+      repo_dir: repo_dir.into(),
+      base_ref: None,
+    }
+  }
+
+  /// Fallback base of pull requests, for example `origin/main`
+  pub fn base_ref(mut self, base_ref: impl Into<String>) -> Self {
+    self.base_ref = Some(base_ref.into());
+    self
+  }
+
+  async fn git(&self, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+      .args(args)
+      .current_dir(&self.repo_dir)
+      .output()
+      .await?;
+
+    if !output.status.success() {
+      return Err(Error::internal_runtime_error(format!(
+        "Failed to run `git {}`: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr).trim()
+      )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+  }
+
+  /// Rejects a base that is neither a commit sha nor a valid ref name, since it comes from the
+  /// event payload and is passed to git
+  async fn check_ref(&self, reference: &str) -> Result<()> {
+    if is_sha(reference) {
+      return Ok(());
+    }
+
+    let is_valid = !reference.starts_with('-')
+      && self
+        .git(&["check-ref-format", "--allow-onelevel", reference])
+        .await
+        .is_ok();
+
+    if !is_valid {
+      return Err(Error::workflow_config_error(format!(
+        "Invalid git ref `{}`",
+        reference
+      )));
+    }
+
+    Ok(())
+  }
+
+  async fn get_push_changed_files(&self, event: &TriggerEvent) -> Result<String> {
+    let sha = check_sha(&event.sha)?;
+
+    if let Some(before) = event.before_sha() {
+      let before = check_sha(before)?;
+
+      return self
+        .git(&["diff", "--name-only", "--end-of-options", before, sha])
+        .await;
+    }
+
+    let parent = format!("{}^1", sha);
+
+    if self
+      .git(&[
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        "--end-of-options",
+        &parent,
+      ])
+      .await
+      .is_ok()
+    {
+      self
+        .git(&["diff", "--name-only", "--end-of-options", &parent, sha])
+        .await
+    } else {
+      self
+        .git(&[
+          "diff-tree",
+          "--no-commit-id",
+          "--name-only",
+          "-r",
+          "--root",
+          "--end-of-options",
+          sha,
+        ])
+        .await
+    }
+  }
+
+  async fn get_pull_request_changed_files(&self, event: &TriggerEvent) -> Result<String> {
+    let base = event
+      .base_ref
+      .clone()
+      .or_else(|| {
+        let base = event.payload.as_ref()?.get("pull_request")?.get("base")?;
+
+        base
+          .get("sha")
+          .or(base.get("ref"))?
+          .as_str()
+          .map(|base| base.to_string())
+      })
+      .or(self.base_ref.clone())
+      .ok_or(Error::workflow_config_error(
+        "Base of the pull request is not provided",
+      ))?;
+
+    self.check_ref(&base).await?;
+    let range = format!("{}...{}", base, check_sha(&event.sha)?);
+
+    self
+      .git(&["diff", "--name-only", "--end-of-options", &range])
+      .await
+  }
+}
+
+#[async_trait::async_trait]
+impl ChangedFilesProvider for LocalGitChangedFilesProvider {
+  async fn changed_files(&self, event: &TriggerEvent) -> Result<Vec<String>> {
+    let output = match event.event.as_str() {
+      "push" => self.get_push_changed_files(event).await?,
+      "pull_request" => self.get_pull_request_changed_files(event).await?,
+      _ => {
+        return Err(Error::unsupported_feature(format!(
+          "Event {} is not supported",
+          event.event
+        )))
+      }
+    };
+
+    Ok(
+      output
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct TestRepo {
+    provider: LocalGitChangedFilesProvider,
+    dir: PathBuf,
+  }
+
+  impl TestRepo {
+    async fn new() -> Self {
+      let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+      std::fs::create_dir_all(&dir).unwrap();
+
+      let repo = Self {
+        provider: LocalGitChangedFilesProvider::new(&dir),
+        dir,
+      };
+
+      repo.git(&["init", "-q", "-b", "main"]).await;
+      repo.git(&["config", "user.email", "test@astro-run"]).await;
+      repo.git(&["config", "user.name", "test"]).await;
+
+      repo
+    }
+
+    async fn git(&self, args: &[&str]) -> String {
+      self.provider.git(args).await.unwrap().trim().to_string()
+    }
+
+    async fn commit(&self, files: &[&str]) -> String {
+      for file in files {
+        let path = self.dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, uuid::Uuid::new_v4().to_string()).unwrap();
+      }
+
+      self.git(&["add", "-A"]).await;
+      self.git(&["commit", "-q", "-m", "commit"]).await;
+      self.git(&["rev-parse", "HEAD"]).await
+    }
+  }
+
+  impl Drop for TestRepo {
+    fn drop(&mut self) {
+      std::fs::remove_dir_all(&self.dir).ok();
+    }
+  }
+
+  #[astro_run_test::test]
+  async fn test_push_changed_files() {
+    let repo = TestRepo::new().await;
+
+    let root = repo.commit(&["README.md", "src/main.rs"]).await;
+    let sha = repo.commit(&["src/lib.rs"]).await;
+
+    let files = repo
+      .provider
+      .changed_files(&TriggerEvent {
+        sha: root.clone(),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    assert_eq!(files, vec!["README.md", "src/main.rs"]);
+
+    let files = repo
+      .provider
+      .changed_files(&TriggerEvent {
+        sha: sha.clone(),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    assert_eq!(files, vec!["src/lib.rs"]);
+
+    let sha = repo.commit(&["src/main.rs"]).await;
+    let files = repo
+      .provider
+      .changed_files(&TriggerEvent {
+        sha,
+        before: Some(root),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    assert_eq!(files, vec!["src/lib.rs", "src/main.rs"]);
+  }
+
+  #[astro_run_test::test]
+  async fn test_pull_request_changed_files() {
+    let repo = TestRepo::new().await;
+
+    repo.commit(&["README.md"]).await;
+    repo.git(&["checkout", "-q", "-b", "feature"]).await;
+    repo.commit(&["src/feature.rs"]).await;
+    let sha = repo.commit(&["docs/feature.md"]).await;
+    repo.git(&["checkout", "-q", "main"]).await;
+    // Changes on the base branch are not part of the pull request
+    repo.commit(&["src/main.rs"]).await;
+
+    let event = TriggerEvent {
+      event: "pull_request".to_string(),
+      sha,
+      payload: Some(serde_json::json!({
+        "pull_request": { "base": { "ref": "main" } }
+      })),
+      ..Default::default()
+    };
+
+    let files = repo.provider.changed_files(&event).await.unwrap();
+    assert_eq!(files, vec!["docs/feature.md", "src/feature.rs"]);
+
+    let error = repo
+      .provider
+      .changed_files(&TriggerEvent {
+        payload: None,
+        ..event
+      })
+      .await
+      .unwrap_err();
+    assert_eq!(
+      error,
+      Error::workflow_config_error("Base of the pull request is not provided")
+    );
+  }
+
+  #[astro_run_test::test]
+  async fn test_invalid_revisions() {
+    let repo = TestRepo::new().await;
+    let sha = repo.commit(&["README.md"]).await;
+
+    let error = repo
+      .provider
+      .changed_files(&TriggerEvent {
+        sha: "--output=/tmp/changed".to_string(),
+        ..Default::default()
+      })
+      .await
+      .unwrap_err();
+    assert_eq!(
+      error,
+      Error::workflow_config_error("Invalid commit sha `--output=/tmp/changed`")
+    );
+
+    let error = repo
+      .provider
+      .changed_files(&TriggerEvent {
+        sha: sha.clone(),
+        before: Some("--output=/tmp/changed".to_string()),
+        ..Default::default()
+      })
+      .await
+      .unwrap_err();
+    assert_eq!(
+      error,
+      Error::workflow_config_error("Invalid commit sha `--output=/tmp/changed`")
+    );
+
+    for base in ["--output=/tmp/changed", "main~1"] {
+      let error = repo
+        .provider
+        .changed_files(&TriggerEvent {
+          event: "pull_request".to_string(),
+          sha: sha.clone(),
+          base_ref: Some(base.to_string()),
+          ..Default::default()
+        })
+        .await
+        .unwrap_err();
+      assert_eq!(
+        error,
+        Error::workflow_config_error(format!("Invalid git ref `{}`", base))
+      );
+    }
+  }
+}
@@ -0,0 +1,63 @@
+use parking_lot::Mutex;
+use std::{collections::HashMap, sync::Arc};
+use tokio::{
+  io::{AsyncReadExt, AsyncWriteExt},
+  net::TcpListener,
+};
+
+/// Minimal HTTP server responding with fixed JSON bodies by path, for provider tests
+pub struct MockServer {
+  pub base_url: String,
+  /// Request line and headers of every received request
+  pub requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+  pub async fn start(routes: HashMap<&'static str, serde_json::Value>) -> Self {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(vec![]));
+    let received = Arc::clone(&requests);
+
+    tokio::spawn(async move {
+      while let Ok((mut stream, _)) = listener.accept().await {
+        let mut buffer = vec![0; 8192];
+        let mut len = 0;
+
+        while !String::from_utf8_lossy(&buffer[..len]).contains("\r\n\r\n") {
+          match stream.read(&mut buffer[len..]).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => len += n,
+          }
+        }
+
+        let request = String::from_utf8_lossy(&buffer[..len]).to_string();
+        let path = request
+          .split_whitespace()
+          .nth(1)
+          .unwrap_or_default()
+          .split('?')
+          .next()
+          .unwrap_or_default()
+          .to_string();
+        received.lock().push(request);
+
+        let (status, body) = match routes.get(path.as_str()) {
+          Some(body) => ("200 OK", body.to_string()),
+          None => ("404 Not Found", "{}".to_string()),
+        };
+
+        let response = format!(
+          "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+          status,
+          body.len(),
+          body
+        );
+
+        stream.write_all(response.as_bytes()).await.ok();
+      }
+    });
+
+    Self { base_url, requests }
+  }
+}
@@ -0,0 +1,124 @@
+mod gitea;
+mod github;
+mod gitlab;
+mod local_git;
+#[cfg(test)]
+mod mock_server;
+
+pub use gitea::*;
+pub use github::*;
+pub use gitlab::*;
+pub use local_git::*;
+
+use crate::{Error, Result, TriggerEvent};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC};
+use std::{collections::HashMap, sync::Arc};
+
+/// Encodes everything but unreserved characters, so a value stays a single URL path segment
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+  .remove(b'-')
+  .remove(b'.')
+  .remove(b'_')
+  .remove(b'~');
+
+/// # ChangedFilesProvider
+/// Resolves the files changed by a `push` or `pull_request` event, which are matched against
+/// the `paths` filters of conditions.
+///
+/// `GithubChangedFilesProvider`, `GitlabChangedFilesProvider` and `GiteaChangedFilesProvider`
+/// are selected by `TriggerEvent::provider` when configured on `AstroRunBuilder`.
+/// Use `LocalGitChangedFilesProvider` for self-hosted git or local runs.
+#[async_trait::async_trait]
+pub trait ChangedFilesProvider: Send + Sync {
+  async fn changed_files(&self, event: &TriggerEvent) -> Result<Vec<String>>;
+}
+
+pub type SharedChangedFilesProvider = Arc<Box<dyn ChangedFilesProvider>>;
+
+/// Routes an event to the provider registered for `TriggerEvent::provider`, defaulting to `github`
+#[derive(Default)]
+pub struct ChangedFilesProviderDriver {
+  providers: HashMap<String, Box<dyn ChangedFilesProvider>>,
+}
+
+impl ChangedFilesProviderDriver {
+  pub fn new(providers: HashMap<String, Box<dyn ChangedFilesProvider>>) -> Self {
+    Self { providers }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.providers.is_empty()
+  }
+}
+
+#[async_trait::async_trait]
+impl ChangedFilesProvider for ChangedFilesProviderDriver {
+  async fn changed_files(&self, event: &TriggerEvent) -> Result<Vec<String>> {
+    let provider = event.provider.as_deref().unwrap_or("github");
+
+    match self.providers.get(provider) {
+      Some(changed_files_provider) => changed_files_provider.changed_files(event).await,
+      None => Err(Error::init_error(format!(
+        "Changed files provider `{}` is not configured",
+        provider
+      ))),
+    }
+  }
+}
+
+/// A full SHA-1 or SHA-256 commit hash
+fn is_sha(value: &str) -> bool {
+  matches!(value.len(), 40 | 64) && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Rejects a sha from the event payload before it is passed to git or put into an API URL
+fn check_sha(sha: &str) -> Result<&str> {
+  if !is_sha(sha) {
+    return Err(Error::workflow_config_error(format!(
+      "Invalid commit sha `{}`",
+      sha
+    )));
+  }
+
+  Ok(sha)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use mock_server::MockServer;
+
+  #[astro_run_test::test]
+  async fn test_provider_routing() {
+    let server = MockServer::start(HashMap::from([(
+      "/api/v4/projects/panghu-huang%2Fastro-run/repository/commits/8d3c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d/diff",
+      serde_json::json!([{ "old_path": "src/lib.rs", "new_path": "src/lib.rs" }]),
+    )]))
+    .await;
+
+    let driver = ChangedFilesProviderDriver::new(HashMap::from([(
+      "gitlab".to_string(),
+      Box::new(GitlabChangedFilesProvider::new(&server.base_url, "token"))
+        as Box<dyn ChangedFilesProvider>,
+    )]));
+
+    let event = TriggerEvent {
+      provider: Some("gitlab".to_string()),
+      sha: "8d3c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d".to_string(),
+      ..Default::default()
+    };
+    assert_eq!(
+      driver.changed_files(&event).await.unwrap(),
+      vec!["src/lib.rs"]
+    );
+
+    let error = driver
+      .changed_files(&TriggerEvent::default())
+      .await
+      .unwrap_err();
+    assert_eq!(
+      error,
+      Error::init_error("Changed files provider `github` is not configured")
+    );
+  }
+}
@@ -0,0 +1,614 @@
+use crate::{
+  AstroRun, Condition, Error, Result, ScheduleCondition, TriggerEvent, UserWorkflow, Workflow,
+  WorkflowRunResult,
+};
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
+use parking_lot::Mutex;
+use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+
+/// Upper bound of a single sleep in `CronScheduler::start`, so that workflows added
+/// while the scheduler is sleeping are picked up in time
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// Source of the current time for the `CronScheduler`.
+pub trait Clock: Send + Sync {
+  fn now(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> DateTime<Utc> {
+    Utc::now()
+  }
+}
+
+struct ScheduleEntry {
+  name: String,
+  config: String,
+  event: TriggerEvent,
+  cron: String,
+  schedule: cron::Schedule,
+  timezone: Tz,
+  last_fired_at: DateTime<Utc>,
+}
+
+impl ScheduleEntry {
+  fn key(&self) -> String {
+    format!("{}:{}", self.name, self.cron)
+  }
+
+  fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    self
+      .schedule
+      .after(&time.with_timezone(&self.timezone))
+      .next()
+      .map(|time| time.with_timezone(&Utc))
+  }
+
+  /// Returns the latest slot which is due at `now`. Missed slots are coalesced into one run.
+  fn due_slot(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    // Slots are whole seconds, the latest one before the next second is at or before `now`
+    let before = now.with_nanosecond(0)? + chrono::Duration::seconds(1);
+    let latest = self
+      .schedule
+      .after(&before.with_timezone(&self.timezone))
+      .next_back()?
+      .with_timezone(&Utc);
+
+    (latest > self.last_fired_at).then_some(latest)
+  }
+}
+
+/// # CronScheduler
+/// Fires workflows declaring `on.schedule` at the times given by their cron expressions.
+///
+/// Each run gets a fresh workflow id and a `TriggerEvent` with event `schedule`. The payload
+/// contains the cron expression which fired (`schedule`) and the slot time (`scheduled_at`).
+///
+/// When a state file is configured, the last fired slot of every schedule is persisted,
+/// so a slot never fires twice across restarts.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let scheduler = astro_run
+///   .cron_scheduler()
+///   .state_file("/var/lib/astro-run/schedules.json")
+///   .build();
+///
+/// scheduler.add_workflow("nightly", workflow_yaml, TriggerEvent::default())?;
+/// scheduler.start().await;
+/// ```
+#[derive(Clone)]
+pub struct CronScheduler {
+  astro_run: AstroRun,
+  clock: Arc<dyn Clock>,
+  state_file: Option<PathBuf>,
+  entries: Arc<Mutex<Vec<ScheduleEntry>>>,
+}
+
+impl CronScheduler {
+  pub fn builder(astro_run: AstroRun) -> CronSchedulerBuilder {
+    CronSchedulerBuilder::new(astro_run)
+  }
+
+  /// Registers a workflow with its `on.schedule` cron expressions.
+  /// `event` is the base trigger event (repository, branch, sha) used for every scheduled run.
+  pub fn add_workflow(
+    &self,
+    name: impl Into<String>,
+    config: impl Into<String>,
+    event: TriggerEvent,
+  ) -> Result<()> {
+    let name = name.into();
+    let config = config.into();
+    let user_workflow = UserWorkflow::try_from(config.as_str())?;
+
+    let schedules = match user_workflow.on {
+      Some(Condition::Config(config)) => config.schedule.unwrap_or_default(),
+      _ => vec![],
+    };
+
+    if schedules.is_empty() {
+      return Err(Error::workflow_config_error(format!(
+        "Workflow `{}` has no schedule",
+        name
+      )));
+    }
+
+    let state = self.load_state();
+    let now = self.clock.now();
+    let mut entries = self.entries.lock();
+
+    entries.retain(|entry| entry.name != name);
+
+    for ScheduleCondition { cron, timezone } in schedules {
+      let schedule = parse_cron(&cron)?;
+      let timezone = match timezone {
+        Some(timezone) => Tz::from_str(&timezone).map_err(|_| {
+          Error::workflow_config_error(format!("Invalid schedule timezone `{}`", timezone))
+        })?,
+        None => Tz::UTC,
+      };
+
+      let mut entry = ScheduleEntry {
+        name: name.clone(),
+        config: config.clone(),
+        event: event.clone(),
+        cron,
+        schedule,
+        timezone,
+        last_fired_at: now,
+      };
+
+      if let Some(last_fired_at) = state.get(&entry.key()) {
+        entry.last_fired_at = *last_fired_at;
+      }
+
+      entries.push(entry);
+    }
+
+    Ok(())
+  }
+
+  pub fn remove_workflow(&self, name: &str) {
+    self.entries.lock().retain(|entry| entry.name != name);
+  }
+
+  /// The earliest time at which a schedule will be due
+  pub fn next_run_at(&self) -> Option<DateTime<Utc>> {
+    self
+      .entries
+      .lock()
+      .iter()
+      .filter_map(|entry| entry.next_after(entry.last_fired_at))
+      .min()
+  }
+
+  /// Fires every schedule which is due at the current time of the clock.
+  pub fn tick(&self) -> Vec<JoinHandle<Result<WorkflowRunResult>>> {
+    let now = self.clock.now();
+    let mut due = vec![];
+
+    {
+      let mut entries = self.entries.lock();
+
+      for entry in entries.iter_mut() {
+        if let Some(slot) = entry.due_slot(now) {
+          entry.last_fired_at = slot;
+
+          let mut event = entry.event.clone();
+          event.event = "schedule".to_string();
+          event.payload = Some(serde_json::json!({
+            "schedule": entry.cron,
+            "scheduled_at": slot.to_rfc3339(),
+          }));
+
+          log::trace!(
+            "Schedule `{}` of `{}` is due at {}",
+            entry.cron,
+            entry.name,
+            slot
+          );
+          due.push((entry.name.clone(), entry.config.clone(), event));
+        }
+      }
+    }
+
+    if due.is_empty() {
+      return vec![];
+    }
+
+    self.save_state();
+
+    due
+      .into_iter()
+      .map(|(name, config, event)| {
+        let astro_run = self.astro_run.clone();
+
+        tokio::spawn(async move {
+          let workflow = Workflow::builder()
+            .config(config)
+            .build(&astro_run)
+            .await
+            .map_err(|err| {
+              log::error!("Failed to build scheduled workflow `{}`: {}", name, err);
+              err
+            })?;
+
+          let ctx = astro_run.execution_context().event(event).build();
+
+          Ok(workflow.run(ctx).await)
+        })
+      })
+      .collect()
+  }
+
+  /// Runs the scheduler until the task is dropped.
+  pub async fn start(&self) {
+    loop {
+      self.tick();
+
+      // Nothing to wait for until a workflow is added
+      let sleep = match self.next_run_at() {
+        Some(next) => (next - self.clock.now())
+          .to_std()
+          .unwrap_or(Duration::ZERO)
+          .clamp(Duration::from_millis(100), MAX_SLEEP),
+        None => MAX_SLEEP,
+      };
+
+      tokio::time::sleep(sleep).await;
+    }
+  }
+
+  fn load_state(&self) -> HashMap<String, DateTime<Utc>> {
+    let Some(state_file) = &self.state_file else {
+      return HashMap::new();
+    };
+
+    match std::fs::read_to_string(state_file) {
+      Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+        log::error!("Failed to parse schedule state file: {}", err);
+        HashMap::new()
+      }),
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+      Err(err) => {
+        log::error!("Failed to read schedule state file: {}", err);
+        HashMap::new()
+      }
+    }
+  }
+
+  fn save_state(&self) {
+    let Some(state_file) = &self.state_file else {
+      return;
+    };
+
+    let mut state = self.load_state();
+
+    for entry in self.entries.lock().iter() {
+      state.insert(entry.key(), entry.last_fired_at);
+    }
+
+    let result = serde_json::to_string_pretty(&state)
+      .map_err(std::io::Error::other)
+      .and_then(|content| std::fs::write(state_file, content));
+
+    if let Err(err) = result {
+      log::error!("Failed to write schedule state file: {}", err);
+    }
+  }
+}
+
+pub struct CronSchedulerBuilder {
+  astro_run: AstroRun,
+  clock: Option<Arc<dyn Clock>>,
+  state_file: Option<PathBuf>,
+}
+
+impl CronSchedulerBuilder {
+  pub fn new(astro_run: AstroRun) -> Self {
+    Self {
+      astro_run,
+      clock: None,
+      state_file: None,
+    }
+  }
+
+  pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+    self.clock = Some(Arc::new(clock));
+    self
+  }
+
+  /// JSON file which records the last fired slot of every schedule
+  pub fn state_file(mut self, state_file: impl Into<PathBuf>) -> Self {
+    self.state_file = Some(state_file.into());
+    self
+  }
+
+  pub fn build(self) -> CronScheduler {
+    CronScheduler {
+      astro_run: self.astro_run,
+      clock: self.clock.unwrap_or_else(|| Arc::new(SystemClock)),
+      state_file: self.state_file,
+      entries: Arc::new(Mutex::new(vec![])),
+    }
+  }
+}
+
+/// Parses 5-field (`min hour day month weekday`) or 6/7-field cron expressions
+fn parse_cron(expression: &str) -> Result<cron::Schedule> {
+  let invalid = |err: String| {
+    Error::workflow_config_error(format!("Invalid cron expression `{}`: {}", expression, err))
+  };
+
+  let fields: Vec<&str> = expression.split_whitespace().collect();
+  let expression = if fields.len() == 5 {
+    let weekdays = crontab_weekdays(fields[4]).map_err(invalid)?;
+    format!("0 {} {}", fields[..4].join(" "), weekdays)
+  } else {
+    expression.to_string()
+  };
+
+  cron::Schedule::from_str(&expression).map_err(|err| invalid(err.to_string()))
+}
+
+/// Translates the weekday field of a crontab expression, where Sunday is 0 or 7, to the
+/// numbering of the `cron` crate, where Sunday is 1. Names such as `MON-FRI` are kept
+fn crontab_weekdays(field: &str) -> std::result::Result<String, String> {
+  let mut items = vec![];
+
+  for item in field.split(',') {
+    if item == "*"
+      || !item
+        .chars()
+        .all(|c| c.is_ascii_digit() || "*-/".contains(c))
+    {
+      items.push(item.to_string());
+      continue;
+    }
+
+    let invalid = || format!("invalid weekday `{}`", item);
+    let parse = |value: &str| value.parse::<usize>().map_err(|_| invalid());
+
+    let (range, step) = match item.split_once('/') {
+      Some((range, step)) => (range, Some(parse(step)?)),
+      None => (item, None),
+    };
+    let (start, end) = match range.split_once('-') {
+      Some((start, end)) => (parse(start)?, parse(end)?),
+      None if range == "*" => (0, 6),
+      // `1/2` is every other day from Monday
+      None if step.is_some() => (parse(range)?, 6),
+      None => (parse(range)?, parse(range)?),
+    };
+
+    if end > 7 || start > end || step == Some(0) {
+      return Err(invalid());
+    }
+
+    let mut days: Vec<usize> = (start..=end)
+      .step_by(step.unwrap_or(1))
+      .map(|day| day % 7 + 1)
+      .collect();
+    days.sort_unstable();
+    days.dedup();
+
+    items.extend(days.iter().map(|day| day.to_string()));
+  }
+
+  Ok(items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{async_trait, stream, Context, RunResponse, RunResult, Runner, WorkflowState};
+  use chrono::TimeZone;
+
+  struct TestRunner;
+
+  #[async_trait]
+  impl Runner for TestRunner {
+    async fn run(&self, ctx: Context) -> RunResponse {
+      let (tx, rx) = stream();
+      tx.log(ctx.command.run);
+      tx.end(RunResult::Succeeded);
+
+      Ok(rx)
+    }
+  }
+
+  #[derive(Clone)]
+  struct ManualClock(Arc<Mutex<DateTime<Utc>>>);
+
+  impl ManualClock {
+    fn new(time: DateTime<Utc>) -> Self {
+      Self(Arc::new(Mutex::new(time)))
+    }
+
+    fn set(&self, time: DateTime<Utc>) {
+      *self.0.lock() = time;
+    }
+  }
+
+  impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+      *self.0.lock()
+    }
+  }
+
+  const WORKFLOW: &str = r#"
+on:
+  schedule:
+    - cron: "0 2 * * *"
+      timezone: Asia/Shanghai
+jobs:
+  test:
+    steps:
+      - run: echo "nightly"
+"#;
+
+  fn utc(hour: u32, min: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 1, hour, min, 0).unwrap()
+  }
+
+  #[astro_run_test::test]
+  async fn test_fire_schedule() {
+    let clock = ManualClock::new(utc(17, 0));
+    let astro_run = AstroRun::builder().runner(TestRunner).build();
+    let scheduler = astro_run.cron_scheduler().clock(clock.clone()).build();
+
+    scheduler
+      .add_workflow("nightly", WORKFLOW, TriggerEvent::default())
+      .unwrap();
+
+    // 02:00 Asia/Shanghai is 18:00 UTC
+    assert_eq!(scheduler.next_run_at(), Some(utc(18, 0)));
+    assert!(scheduler.tick().is_empty());
+
+    clock.set(utc(18, 0));
+    let handles = scheduler.tick();
+    assert_eq!(handles.len(), 1);
+
+    for handle in handles {
+      let result = handle.await.unwrap().unwrap();
+      assert_eq!(result.state, WorkflowState::Succeeded);
+    }
+
+    // Fired only once per slot
+    assert!(scheduler.tick().is_empty());
+  }
+
+  #[astro_run_test::test]
+  async fn test_missed_slots() {
+    let clock = ManualClock::new(utc(17, 0));
+    let astro_run = AstroRun::builder().runner(TestRunner).build();
+    let scheduler = astro_run.cron_scheduler().clock(clock.clone()).build();
+
+    scheduler
+      .add_workflow("nightly", WORKFLOW, TriggerEvent::default())
+      .unwrap();
+
+    // Down for a month, the missed slots are coalesced into the latest one
+    clock.set(Utc.with_ymd_and_hms(2024, 2, 1, 20, 0, 0).unwrap());
+    let handles = scheduler.tick();
+    assert_eq!(handles.len(), 1);
+    for handle in handles {
+      handle.await.unwrap().unwrap();
+    }
+
+    assert_eq!(
+      scheduler.next_run_at(),
+      Some(Utc.with_ymd_and_hms(2024, 2, 2, 18, 0, 0).unwrap())
+    );
+    assert!(scheduler.tick().is_empty());
+  }
+
+  #[astro_run_test::test]
+  async fn test_state_file() {
+    let state_file = std::env::temp_dir().join(format!("{}.json", uuid::Uuid::new_v4()));
+    let clock = ManualClock::new(utc(17, 0));
+    let astro_run = AstroRun::builder().runner(TestRunner).build();
+
+    let scheduler = astro_run
+      .cron_scheduler()
+      .clock(clock.clone())
+      .state_file(&state_file)
+      .build();
+    scheduler
+      .add_workflow("nightly", WORKFLOW, TriggerEvent::default())
+      .unwrap();
+
+    clock.set(utc(18, 30));
+    for handle in scheduler.tick() {
+      handle.await.unwrap().unwrap();
+    }
+
+    // Restarted after the slot has fired
+    let scheduler = astro_run
+      .cron_scheduler()
+      .clock(clock.clone())
+      .state_file(&state_file)
+      .build();
+    scheduler
+      .add_workflow("nightly", WORKFLOW, TriggerEvent::default())
+      .unwrap();
+
+    assert!(scheduler.tick().is_empty());
+
+    std::fs::remove_file(state_file).unwrap();
+  }
+
+  #[astro_run_test::test]
+  async fn test_invalid_schedule() {
+    let astro_run = AstroRun::builder().runner(TestRunner).build();
+    let scheduler = astro_run.cron_scheduler().build();
+
+    let error = scheduler
+      .add_workflow(
+        "invalid",
+        r#"
+on:
+  schedule:
+    - cron: "0 2 * * *"
+      timezone: Mars/Olympus
+jobs:
+  test:
+    steps:
+      - run: echo "nightly"
+"#,
+        TriggerEvent::default(),
+      )
+      .unwrap_err();
+
+    assert_eq!(
+      error,
+      Error::workflow_config_error("Invalid schedule timezone `Mars/Olympus`")
+    );
+
+    let error = scheduler
+      .add_workflow(
+        "no-schedule",
+        r#"
+jobs:
+  test:
+    steps:
+      - run: echo "nightly"
+"#,
+        TriggerEvent::default(),
+      )
+      .unwrap_err();
+
+    assert_eq!(
+      error,
+      Error::workflow_config_error("Workflow `no-schedule` has no schedule")
+    );
+  }
+
+  #[test]
+  fn test_crontab_weekdays() {
+    use chrono::{Datelike, Weekday};
+
+    let weekdays = |expression: &str| {
+      let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+      parse_cron(expression)
+        .unwrap()
+        .after(&start)
+        .take(7)
+        .map(|time| time.weekday())
+        .collect::<Vec<_>>()
+    };
+
+    // 2024-01-01 is a Monday
+    assert_eq!(weekdays("0 2 * * 1"), vec![Weekday::Mon; 7]);
+    assert_eq!(weekdays("0 2 * * 0")[0], Weekday::Sun);
+    assert_eq!(weekdays("0 2 * * 7")[0], Weekday::Sun);
+    assert_eq!(
+      weekdays("0 2 * * 5-7")[..3],
+      [Weekday::Fri, Weekday::Sat, Weekday::Sun]
+    );
+    assert_eq!(
+      weekdays("0 2 * * 1-5/2")[..3],
+      [Weekday::Mon, Weekday::Wed, Weekday::Fri]
+    );
+    assert_eq!(weekdays("0 2 * * MON,0")[..2], [Weekday::Mon, Weekday::Sun]);
+
+    let upcoming = parse_cron("30 8 * * 1")
+      .unwrap()
+      .upcoming(Utc)
+      .take(3)
+      .collect::<Vec<_>>();
+    assert!(upcoming.iter().all(|time| time.weekday() == Weekday::Mon));
+
+    assert_eq!(
+      parse_cron("0 2 * * 8").unwrap_err(),
+      Error::workflow_config_error("Invalid cron expression `0 2 * * 8`: invalid weekday `8`")
+    );
+  }
+}
@@ -0,0 +1,159 @@
+use crate::{ConditionPayload, EventCondition};
+use std::{collections::HashMap, sync::Arc};
+
+/// # EventMatcher
+/// Decides whether an `EventCondition` matches a trigger event.
+///
+/// Events other than `push` and `pull_request` are matched by `EventCondition::is_match`,
+/// which checks branches, paths and field filters. Register an `EventMatcher` for an event name
+/// to customize how its condition is evaluated.
+///
+/// ## Example
+///
+/// ```rust
+/// struct ReleaseMatcher;
+///
+/// impl astro_run::EventMatcher for ReleaseMatcher {
+///   fn is_match(
+///     &self,
+///     condition: &astro_run::EventCondition,
+///     payload: &astro_run::ConditionPayload,
+///   ) -> bool {
+///     // Only published releases
+///     payload.field("action").as_deref() == Some("published") && condition.is_match(payload)
+///   }
+/// }
+/// ```
+pub trait EventMatcher: Send + Sync {
+  fn is_match(&self, condition: &EventCondition, payload: &ConditionPayload) -> bool;
+}
+
+pub type SharedEventMatcherDriver = Arc<EventMatcherDriver>;
+
+#[derive(Clone, Default)]
+pub struct EventMatcherDriver {
+  matchers: Arc<HashMap<String, Box<dyn EventMatcher>>>,
+}
+
+impl EventMatcherDriver {
+  pub fn new(matchers: HashMap<String, Box<dyn EventMatcher>>) -> Self {
+    Self {
+      matchers: Arc::new(matchers),
+    }
+  }
+
+  pub fn is_match(
+    &self,
+    event: &str,
+    condition: &EventCondition,
+    payload: &ConditionPayload,
+  ) -> bool {
+    if let Some(matcher) = self.matchers.get(event) {
+      log::trace!("Matching event `{}` with registered matcher", event);
+      return matcher.is_match(condition, payload);
+    }
+
+    condition.is_match(payload)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Condition, TriggerEvent};
+
+  fn release_payload(action: &str) -> ConditionPayload {
+    ConditionPayload {
+      event: "release".to_string(),
+      branch: "main".to_string(),
+      paths: vec![],
+      trigger_event: Some(TriggerEvent {
+        event: "release".to_string(),
+        ref_name: "refs/tags/v1.0.0".to_string(),
+        payload: Some(serde_json::json!({
+          "action": action,
+          "release": {
+            "tag_name": "v1.0.0",
+            "draft": false,
+          }
+        })),
+        ..Default::default()
+      }),
+    }
+  }
+
+  #[test]
+  fn test_event_condition_filters() {
+    let condition = EventCondition {
+      branches: Some(vec!["main".to_string()]),
+      paths: None,
+      filters: HashMap::from([
+        ("ref_name".to_string(), vec!["refs/tags/v*".to_string()]),
+        ("release.tag_name".to_string(), vec!["v1.*".to_string()]),
+        ("release.draft".to_string(), vec!["false".to_string()]),
+      ]),
+    };
+
+    assert!(condition.is_match(&release_payload("published")));
+
+    let condition = EventCondition {
+      filters: HashMap::from([("release.name".to_string(), vec!["*".to_string()])]),
+      ..Default::default()
+    };
+
+    // Missing field
+    assert!(!condition.is_match(&release_payload("published")));
+  }
+
+  #[test]
+  fn test_registered_matcher() {
+    struct PublishedOnly;
+
+    impl EventMatcher for PublishedOnly {
+      fn is_match(&self, condition: &EventCondition, payload: &ConditionPayload) -> bool {
+        payload.field("action").as_deref() == Some("published") && condition.is_match(payload)
+      }
+    }
+
+    let mut matchers = HashMap::new();
+    matchers.insert(
+      "release".to_string(),
+      Box::new(PublishedOnly) as Box<dyn EventMatcher>,
+    );
+
+    let driver = EventMatcherDriver::new(matchers);
+
+    let condition: Condition = serde_yaml::from_str(
+      r#"
+release:
+deploy_request:
+  environment: [production]
+"#,
+    )
+    .unwrap();
+
+    assert!(condition.is_match_with(&release_payload("published"), &driver));
+    assert!(!condition.is_match_with(&release_payload("created"), &driver));
+    // Falls back to the default matcher without the registered one
+    assert!(condition.is_match(&release_payload("created")));
+
+    let payload = ConditionPayload {
+      event: "deploy_request".to_string(),
+      trigger_event: Some(TriggerEvent {
+        event: "deploy_request".to_string(),
+        payload: Some(serde_json::json!({ "environment": "production" })),
+        ..Default::default()
+      }),
+      ..Default::default()
+    };
+
+    assert!(condition.is_match_with(&payload, &driver));
+
+    let payload = ConditionPayload {
+      event: "schedule".to_string(),
+      ..Default::default()
+    };
+
+    assert!(!condition.is_match_with(&payload, &driver));
+  }
+}
@@ -0,0 +1,283 @@
+use crate::{
+  JobRunResult, RunJobEvent, RunStepEvent, RunWorkflowEvent, StepRunResult, WorkflowId,
+  WorkflowLog, WorkflowRunResult, WorkflowState, WorkflowStateEvent,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{ready, Context, Poll},
+};
+use tokio::{sync::broadcast, task::JoinHandle};
+use tokio_stream::{
+  wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+  Stream,
+};
+
+/// Number of events buffered for each subscriber before the oldest ones are dropped
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+pub type EventSender = broadcast::Sender<AstroRunEvent>;
+
+/// Everything that happens during a run, in the order the plugin hooks see it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+pub enum AstroRunEvent {
+  WorkflowStarted(RunWorkflowEvent),
+  JobStarted(RunJobEvent),
+  StepStarted(RunStepEvent),
+  StateChanged(WorkflowStateEvent),
+  Log(WorkflowLog),
+  StepCompleted(StepRunResult),
+  JobCompleted(JobRunResult),
+  WorkflowCompleted(WorkflowRunResult),
+  /// The subscriber fell behind and the oldest `skipped` events were dropped
+  Lagged {
+    skipped: u64,
+  },
+}
+
+impl AstroRunEvent {
+  /// The workflow the event belongs to, `None` for `Lagged`
+  pub fn workflow_id(&self) -> Option<WorkflowId> {
+    let id = match self {
+      AstroRunEvent::WorkflowStarted(event) => event.source.id.clone(),
+      AstroRunEvent::JobStarted(event) => event.source.id.workflow_id(),
+      AstroRunEvent::StepStarted(event) => event.source.id.workflow_id(),
+      AstroRunEvent::StateChanged(event) => match event {
+        WorkflowStateEvent::WorkflowStateUpdated { id, .. } => id.clone(),
+        WorkflowStateEvent::JobStateUpdated { id, .. } => id.workflow_id(),
+        WorkflowStateEvent::StepStateUpdated { id, .. } => id.workflow_id(),
+      },
+      AstroRunEvent::Log(log) => log.step_id.workflow_id(),
+      AstroRunEvent::StepCompleted(result) => result.id.workflow_id(),
+      AstroRunEvent::JobCompleted(result) => result.id.workflow_id(),
+      AstroRunEvent::WorkflowCompleted(result) => result.id.clone(),
+      AstroRunEvent::Lagged { .. } => return None,
+    };
+
+    Some(id)
+  }
+
+  /// Whether it is the last event of a workflow run. Skipped workflows end with their state change
+  pub fn is_workflow_end(&self) -> bool {
+    matches!(
+      self,
+      AstroRunEvent::WorkflowCompleted(_)
+        | AstroRunEvent::StateChanged(WorkflowStateEvent::WorkflowStateUpdated {
+          state: WorkflowState::Skipped,
+          ..
+        })
+    )
+  }
+}
+
+/// A stream of `AstroRunEvent`, see `AstroRun::subscribe` and `Workflow::run_with_events`.
+///
+/// Events are buffered per subscriber. A subscriber which falls behind receives
+/// `AstroRunEvent::Lagged` in place of the events it missed. The stream of
+/// `Workflow::run_with_events` still ends with the result of the run if the last event was missed.
+pub struct EventStream {
+  inner: BroadcastStream<AstroRunEvent>,
+  workflow_id: Option<WorkflowId>,
+  // Task running the workflow, awaited once no event is left
+  run: Option<JoinHandle<WorkflowRunResult>>,
+  // Result of the finished run, yielded if its last event was missed
+  result: Option<WorkflowRunResult>,
+  completed: bool,
+}
+
+impl EventStream {
+  pub(crate) fn new(receiver: broadcast::Receiver<AstroRunEvent>) -> Self {
+    EventStream {
+      inner: BroadcastStream::new(receiver),
+      workflow_id: None,
+      run: None,
+      result: None,
+      completed: false,
+    }
+  }
+
+  /// Only yields the events of a workflow, and ends once the workflow ends
+  pub(crate) fn workflow(mut self, workflow_id: WorkflowId) -> Self {
+    self.workflow_id = Some(workflow_id);
+    self
+  }
+
+  /// Task running the workflow, so that the stream ends even if its last event is dropped
+  pub(crate) fn run(mut self, run: JoinHandle<WorkflowRunResult>) -> Self {
+    self.run = Some(run);
+    self
+  }
+
+  /// Called once no event is buffered. Waits for the run to finish, then drains the events
+  /// it sent in the meantime before yielding its result
+  fn poll_run(&mut self, cx: &mut Context<'_>) -> Poll<Option<AstroRunEvent>> {
+    if let Some(result) = self.result.take() {
+      self.completed = true;
+
+      let event = if result.state == WorkflowState::Skipped {
+        AstroRunEvent::StateChanged(WorkflowStateEvent::WorkflowStateUpdated {
+          id: result.id,
+          state: WorkflowState::Skipped,
+        })
+      } else {
+        AstroRunEvent::WorkflowCompleted(result)
+      };
+
+      return Poll::Ready(Some(event));
+    }
+
+    let Some(run) = self.run.as_mut() else {
+      return Poll::Pending;
+    };
+
+    let result = ready!(Pin::new(run).poll(cx));
+    self.run = None;
+
+    match result {
+      Ok(result) => {
+        self.result = Some(result);
+        // Polled again to drain the events
+        cx.waker().wake_by_ref();
+
+        Poll::Pending
+      }
+      Err(err) => {
+        log::error!("Workflow run failed: {}", err);
+        self.completed = true;
+
+        Poll::Ready(None)
+      }
+    }
+  }
+}
+
+impl Stream for EventStream {
+  type Item = AstroRunEvent;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    if self.completed {
+      return Poll::Ready(None);
+    }
+
+    loop {
+      let event = match Pin::new(&mut self.inner).poll_next(cx) {
+        Poll::Ready(Some(Ok(event))) => event,
+        Poll::Ready(Some(Err(BroadcastStreamRecvError::Lagged(skipped)))) => {
+          log::warn!("Event subscriber lagged behind, {} events skipped", skipped);
+          return Poll::Ready(Some(AstroRunEvent::Lagged { skipped }));
+        }
+        Poll::Ready(None) => return Poll::Ready(None),
+        Poll::Pending => return self.poll_run(cx),
+      };
+
+      if let Some(workflow_id) = &self.workflow_id {
+        if event.workflow_id().as_ref() != Some(workflow_id) {
+          continue;
+        }
+
+        self.completed = event.is_workflow_end();
+      }
+
+      return Poll::Ready(Some(event));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::StepId;
+  use tokio_stream::StreamExt;
+
+  fn log(workflow_id: &str, message: &str) -> AstroRunEvent {
+    AstroRunEvent::Log(WorkflowLog {
+      step_id: StepId::new(workflow_id, "test", 0),
+      message: message.to_string(),
+      ..Default::default()
+    })
+  }
+
+  #[astro_run_test::test]
+  async fn test_lagged() {
+    let (sender, receiver) = broadcast::channel(2);
+    let stream = EventStream::new(receiver);
+
+    for i in 0..5 {
+      sender.send(log("a", &i.to_string())).unwrap();
+    }
+    drop(sender);
+
+    let events: Vec<_> = stream.collect().await;
+    assert!(matches!(events[0], AstroRunEvent::Lagged { skipped: 3 }));
+    assert_eq!(events.len(), 3);
+    assert!(matches!(&events[2], AstroRunEvent::Log(log) if log.message == "4"));
+  }
+
+  #[astro_run_test::test]
+  async fn test_workflow_filter() {
+    let (sender, receiver) = broadcast::channel(10);
+    let stream = EventStream::new(receiver).workflow(WorkflowId::new("a"));
+
+    sender.send(log("b", "other")).unwrap();
+    sender.send(log("a", "first")).unwrap();
+    sender
+      .send(AstroRunEvent::StateChanged(
+        WorkflowStateEvent::WorkflowStateUpdated {
+          id: WorkflowId::new("a"),
+          state: WorkflowState::Skipped,
+        },
+      ))
+      .unwrap();
+    sender.send(log("a", "after end")).unwrap();
+
+    // Ends without the sender being dropped
+    let events: Vec<_> = stream.collect().await;
+    assert_eq!(events.len(), 2);
+    assert!(matches!(&events[0], AstroRunEvent::Log(log) if log.message == "first"));
+    assert!(events[1].is_workflow_end());
+  }
+
+  #[astro_run_test::test]
+  async fn test_workflow_end_lagged() {
+    let (sender, receiver) = broadcast::channel(2);
+    let workflow_id = WorkflowId::new("a");
+
+    let run = tokio::spawn({
+      let sender = sender.clone();
+      let result = WorkflowRunResult {
+        id: workflow_id.clone(),
+        state: WorkflowState::Succeeded,
+        started_at: None,
+        completed_at: None,
+        jobs: Default::default(),
+        summary: None,
+      };
+
+      async move {
+        sender.send(log("a", "first")).unwrap();
+        sender
+          .send(AstroRunEvent::WorkflowCompleted(result.clone()))
+          .unwrap();
+        // Pushes the last event of `a` out of the buffer
+        sender.send(log("b", "other")).unwrap();
+        sender.send(log("b", "other")).unwrap();
+
+        result
+      }
+    });
+    let stream = EventStream::new(receiver).workflow(workflow_id).run(run);
+    // The run finishes before the stream is polled
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+    // Ends while the sender is alive
+    let events: Vec<_> = stream.collect().await;
+    assert_eq!(events.len(), 2);
+    assert!(matches!(events[0], AstroRunEvent::Lagged { skipped: 2 }));
+    assert!(
+      matches!(&events[1], AstroRunEvent::WorkflowCompleted(result) if result.state == WorkflowState::Succeeded)
+    );
+    drop(sender);
+  }
+}
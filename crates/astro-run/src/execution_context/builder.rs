@@ -1,7 +1,8 @@
 use super::condition_matcher::ConditionMatcher;
 use crate::{
-  ContextPayload, ContextPayloadExt, Error, ExecutionContext, GithubAuthorization, Runner,
-  SharedPluginDriver, SignalManager, TriggerEvent,
+  ContextPayload, ContextPayloadExt, Error, EventSender, ExecutionContext, GithubAuthorization,
+  Runner, SharedChangedFilesProvider, SharedEventMatcherDriver, SharedPluginDriver, SignalManager,
+  TriggerEvent, DEFAULT_EVENT_CAPACITY,
 };
 use std::sync::Arc;
 
@@ -9,10 +10,13 @@ use std::sync::Arc;
 pub struct ExecutionContextBuilder {
   runner: Option<Arc<Box<dyn Runner>>>,
   plugin_driver: Option<SharedPluginDriver>,
+  event_matcher_driver: Option<SharedEventMatcherDriver>,
   signal_manager: Option<SignalManager>,
   event: Option<TriggerEvent>,
   github_auth: Option<GithubAuthorization>,
+  changed_files_provider: Option<SharedChangedFilesProvider>,
   payload: Option<ContextPayload>,
+  event_sender: Option<EventSender>,
 }
 
 impl ExecutionContextBuilder {
@@ -20,10 +24,13 @@ impl ExecutionContextBuilder {
     ExecutionContextBuilder {
       runner: None,
       plugin_driver: None,
+      event_matcher_driver: None,
       signal_manager: None,
       event: None,
       github_auth: None,
+      changed_files_provider: None,
       payload: None,
+      event_sender: None,
     }
   }
 
@@ -38,11 +45,23 @@ impl ExecutionContextBuilder {
     self
   }
 
+  pub fn event_matcher_driver(mut self, event_matcher_driver: SharedEventMatcherDriver) -> Self {
+    self.event_matcher_driver = Some(event_matcher_driver);
+
+    self
+  }
+
   pub fn signal_manager(mut self, signal_manager: SignalManager) -> Self {
     self.signal_manager = Some(signal_manager);
     self
   }
 
+  /// Publishes the events of the runs to the subscribers of `sender`
+  pub fn event_sender(mut self, sender: EventSender) -> Self {
+    self.event_sender = Some(sender);
+    self
+  }
+
   pub fn event(mut self, event: TriggerEvent) -> Self {
     self.event = Some(event);
     self
@@ -53,6 +72,15 @@ impl ExecutionContextBuilder {
     self
   }
 
+  /// Overrides the changed files provider created from `github_auth`
+  pub fn changed_files_provider(
+    mut self,
+    changed_files_provider: SharedChangedFilesProvider,
+  ) -> Self {
+    self.changed_files_provider = Some(changed_files_provider);
+    self
+  }
+
   pub fn payload<P>(mut self, payload: P) -> Self
   where
     P: ContextPayloadExt + 'static,
@@ -86,13 +114,25 @@ impl ExecutionContextBuilder {
       .unwrap();
 
     let payload = self.payload;
+    let event_sender = self
+      .event_sender
+      .unwrap_or_else(|| tokio::sync::broadcast::channel(DEFAULT_EVENT_CAPACITY).0);
+
+    let mut condition_matcher = ConditionMatcher::new(self.event, self.github_auth);
+    if let Some(event_matcher_driver) = self.event_matcher_driver {
+      condition_matcher = condition_matcher.event_matcher_driver(event_matcher_driver);
+    }
+    if let Some(changed_files_provider) = self.changed_files_provider {
+      condition_matcher = condition_matcher.changed_files_provider(changed_files_provider);
+    }
 
     ExecutionContext {
       runner,
       signal_manager,
       plugin_driver,
-      condition_matcher: ConditionMatcher::new(self.event, self.github_auth),
+      condition_matcher,
       payload,
+      event_sender,
     }
   }
 }
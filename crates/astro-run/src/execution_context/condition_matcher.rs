@@ -1,38 +1,61 @@
-use crate::{Condition, ConditionPayload, Error, GithubAuthorization, Result, TriggerEvent};
-use octocrate::{APIConfig, AppAuthorization, GitHubAPI, PersonalAccessToken};
+use crate::{
+  Condition, ConditionPayload, Error, GithubAuthorization, GithubChangedFilesProvider, Result,
+  SharedChangedFilesProvider, SharedEventMatcherDriver, TriggerEvent,
+};
 use parking_lot::Mutex;
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct ConditionMatcher {
-  pub github_auth: Option<GithubAuthorization>,
+  pub changed_files_provider: Option<SharedChangedFilesProvider>,
   pub event: Option<TriggerEvent>,
   pub payload: Arc<Mutex<Option<ConditionPayload>>>,
+  pub event_matcher_driver: SharedEventMatcherDriver,
 }
 
 impl ConditionMatcher {
   pub fn new(event: Option<TriggerEvent>, github_auth: Option<GithubAuthorization>) -> Self {
+    let changed_files_provider = github_auth.map(|github_auth| {
+      Arc::new(Box::new(GithubChangedFilesProvider::new(github_auth)) as Box<_>)
+    });
+
     Self {
-      github_auth,
+      changed_files_provider,
       event,
       payload: Arc::new(Mutex::new(None)),
+      event_matcher_driver: Default::default(),
     }
   }
 
+  pub fn changed_files_provider(
+    mut self,
+    changed_files_provider: SharedChangedFilesProvider,
+  ) -> Self {
+    self.changed_files_provider = Some(changed_files_provider);
+
+    self
+  }
+
+  pub fn event_matcher_driver(mut self, event_matcher_driver: SharedEventMatcherDriver) -> Self {
+    self.event_matcher_driver = event_matcher_driver;
+
+    self
+  }
+
   pub async fn is_match(&self, condition: &Condition) -> bool {
-    if self.github_auth.is_none() {
-      log::trace!("Github authorization is not provided");
+    let Some(event) = &self.event else {
+      log::trace!("Event is not provided");
       return true;
-    }
+    };
 
-    if self.event.is_none() {
-      log::trace!("Event is not provided");
+    if self.changed_files_provider.is_none() && Self::is_changed_files_required(&event.event) {
+      log::trace!("Changed files provider is not provided");
       return true;
     }
     log::trace!("Matching condition {:#?}", condition);
 
     if let Some(payload) = self.payload.lock().as_ref() {
-      return condition.is_match(payload);
+      return condition.is_match_with(payload, &self.event_matcher_driver);
     }
 
     match self.condition_payload().await {
@@ -40,7 +63,7 @@ impl ConditionMatcher {
         let mut payload_lock = self.payload.lock();
         *payload_lock = Some(payload.clone());
 
-        condition.is_match(&payload)
+        condition.is_match_with(&payload, &self.event_matcher_driver)
       }
       Err(err) => {
         log::trace!("Failed to get condition payload: {}", err);
@@ -50,14 +73,26 @@ impl ConditionMatcher {
     }
   }
 
+  /// Only `push` and `pull_request` events provide changed files
+  fn is_changed_files_required(event: &str) -> bool {
+    matches!(event, "push" | "pull_request")
+  }
+
   async fn condition_payload(&self) -> Result<ConditionPayload> {
-    let TriggerEvent { event, branch, .. } = self.event.as_ref().unwrap();
+    let trigger_event = self.event.as_ref().unwrap();
+    let TriggerEvent { event, branch, .. } = trigger_event;
+
+    let files = if Self::is_changed_files_required(event) {
+      self.get_changed_files().await?
+    } else {
+      vec![]
+    };
 
-    let files = self.get_changed_files().await?;
     let payload = ConditionPayload {
       event: event.clone(),
       branch: branch.clone(),
       paths: files,
+      trigger_event: Some(trigger_event.clone()),
     };
 
     log::trace!("Condition payload: {:#?}", payload);
@@ -66,141 +101,21 @@ impl ConditionMatcher {
   }
 
   async fn get_changed_files(&self) -> Result<Vec<String>> {
-    let TriggerEvent { event, .. } = self.event.as_ref().unwrap();
+    let event = self.event.as_ref().unwrap();
 
-    match event.as_str() {
-      "push" => self.get_push_changed_files().await,
-      "pull_request" => self.get_pull_request_changed_files().await,
-      _ => Err(Error::unsupported_feature(format!(
+    if !Self::is_changed_files_required(&event.event) {
+      return Err(Error::unsupported_feature(format!(
         "Event {} is not supported",
-        event
-      ))),
+        event.event
+      )));
     }
-  }
-
-  async fn get_push_changed_files(&self) -> Result<Vec<String>> {
-    let TriggerEvent {
-      repo_owner,
-      repo_name,
-      sha,
-      ..
-    } = self.event.as_ref().unwrap();
-    let github_api = self.get_github_api_by_repo(repo_owner, repo_name).await?;
-
-    let commit = github_api
-      .repos
-      .get_commit(repo_owner, repo_name, sha)
-      .send()
-      .await
-      .map_err(|e| Error::internal_runtime_error(format!("Failed to get commit: {}", e)))?;
-
-    let files: Vec<String> = commit
-      .files
-      .map(|files| files.iter().map(|f| f.filename.clone()).collect())
-      .unwrap_or(vec![]);
-
-    Ok(files)
-  }
-
-  async fn get_pull_request_changed_files(&self) -> Result<Vec<String>> {
-    let TriggerEvent {
-      repo_owner,
-      repo_name,
-      pr_number,
-      ..
-    } = self.event.as_ref().unwrap();
-    let github_api = self.get_github_api_by_repo(repo_owner, repo_name).await?;
-
-    let pull_request_files = github_api
-      .pulls
-      .list_files(
-        repo_owner,
-        repo_name,
-        pr_number.ok_or(Error::workflow_config_error("pr_number is not provided"))?,
-      )
-      .send()
-      .await
-      .map_err(|e| {
-        Error::internal_runtime_error(format!("Failed to get pull request files: {}", e))
-      })?;
-
-    Ok(pull_request_files.into_iter().map(|f| f.filename).collect())
-  }
 
-  async fn get_github_api_by_repo(
-    &self,
-    repo_owner: &String,
-    repo_name: &String,
-  ) -> crate::Result<GitHubAPI> {
-    let github_auth = self.github_auth.as_ref().unwrap();
+    let provider = self
+      .changed_files_provider
+      .as_ref()
+      .ok_or(Error::init_error("Changed files provider is not provided"))?;
 
-    if github_auth.is_personal_access_token() {
-      return Ok(self.create_github_api());
-    }
-
-    self
-      .create_github_api_by_app_authorization(repo_owner, repo_name)
-      .await
-  }
-
-  async fn create_github_api_by_app_authorization(
-    &self,
-    repo_owner: &String,
-    repo_name: &String,
-  ) -> crate::Result<GitHubAPI> {
-    let github_api = self.create_github_api();
-
-    let installation = github_api
-      .apps
-      .get_repo_installation(repo_owner, repo_name)
-      .send()
-      .await
-      .map_err(|err| {
-        Error::internal_runtime_error(format!(
-          "Failed to get installation for repository: {}",
-          err
-        ))
-      })?;
-
-    let installation_token = github_api
-      .apps
-      .create_installation_access_token(installation.id)
-      .send()
-      .await
-      .map_err(|err| {
-        Error::internal_runtime_error(format!(
-          "Failed to create installation access token: {}",
-          err
-        ))
-      })?;
-
-    let config = APIConfig::with_token(installation_token).shared();
-
-    Ok(GitHubAPI::new(&config))
-  }
-
-  fn create_github_api(&self) -> GitHubAPI {
-    let github_auth = self.github_auth.as_ref().unwrap();
-
-    match &github_auth {
-      GithubAuthorization::PersonalAccessToken(token) => {
-        let access_token = PersonalAccessToken::new(token);
-
-        let config = APIConfig::with_token(access_token).shared();
-
-        GitHubAPI::new(&config)
-      }
-      GithubAuthorization::GithubApp {
-        app_id,
-        private_key,
-      } => {
-        let authorization = AppAuthorization::new(app_id.to_string(), private_key);
-
-        let config = APIConfig::with_token(authorization).shared();
-
-        GitHubAPI::new(&config)
-      }
-    }
+    provider.changed_files(event).await
   }
 }
 
@@ -227,29 +142,36 @@ mod tests {
   }
 
   #[astro_run_test::test]
-  async fn invalid_github_app_id() {
-    dotenv::dotenv().ok();
-
-    let private_key = std::env::var("GH_APP_PRIVATE_KEY")
-      .map_err(|err| crate::Error::internal_runtime_error(format!("GH_APP_PRIVATE_KEY: {}", err)))
-      .unwrap();
-
-    let matcher = ConditionMatcher::new(
-      Some(TriggerEvent {
-        event: "pull_request".to_string(),
-        ..Default::default()
-      }),
-      Some(GithubAuthorization::GithubApp {
-        app_id: 0,
-        private_key,
-      }),
-    );
+  async fn test_changed_files_provider() {
+    struct StaticProvider;
 
-    let res = matcher
-      .get_github_api_by_repo(&"panghu-huang".to_string(), &"astro-run".to_string())
-      .await;
+    #[async_trait::async_trait]
+    impl crate::ChangedFilesProvider for StaticProvider {
+      async fn changed_files(&self, _event: &TriggerEvent) -> Result<Vec<String>> {
+        Ok(vec!["docs/README.md".to_string()])
+      }
+    }
 
-    assert!(res.is_err());
+    let matcher = ConditionMatcher::new(Some(TriggerEvent::default()), None)
+      .changed_files_provider(Arc::new(Box::new(StaticProvider)));
+
+    let condition: Condition = serde_yaml::from_str(
+      r#"
+push:
+  paths: [src/**]
+"#,
+    )
+    .unwrap();
+    assert!(!matcher.is_match(&condition).await);
+
+    let condition: Condition = serde_yaml::from_str(
+      r#"
+push:
+  paths: [docs/**]
+"#,
+    )
+    .unwrap();
+    assert!(matcher.is_match(&condition).await);
   }
 
   #[astro_run_test::test]
@@ -4,9 +4,11 @@ mod context_payload;
 
 pub use self::builder::ExecutionContextBuilder;
 use crate::{
-  AstroRunSignal, Condition, Context, Error, Job, JobId, JobRunResult, Result, RunResult,
-  RunStepEvent, Runner, SharedPluginDriver, Signal, SignalManager, Step, StepRunResult, StreamExt,
-  Workflow, WorkflowLog, WorkflowRunResult, WorkflowState, WorkflowStateEvent,
+  parse_workflow_command, AstroRunEvent, AstroRunSignal, BeforeRun, Condition, Context, Error,
+  EventSender, EventStream, Job, JobId, JobRunResult, Result, RunResult, RunStepEvent, Runner,
+  SharedPluginDriver, Signal, SignalManager, Step, StepId, StepRunResult, StreamExt, Time,
+  Workflow, WorkflowId, WorkflowLog, WorkflowLogType, WorkflowRunResult, WorkflowState,
+  WorkflowStateEvent,
 };
 pub use context_payload::*;
 use std::sync::Arc;
@@ -19,24 +21,32 @@ pub struct ExecutionContext {
   signal_manager: SignalManager,
   condition_matcher: condition_matcher::ConditionMatcher,
   payload: Option<ContextPayload>,
+  event_sender: EventSender,
 }
 
 impl ExecutionContext {
   pub async fn run(&self, step: Step) -> StepRunResult {
-    let step = self.call_on_before_run_step(step).await;
+    let started_at = chrono::Utc::now();
+
+    let step = match self.call_on_before_run_step(step.clone()).await {
+      Ok(BeforeRun::Continue(step)) => step,
+      Ok(BeforeRun::Skip(reason)) => return self.skip_step(step.id, reason).await,
+      Ok(BeforeRun::Fail(reason)) => return self.abort_step(step.id, started_at, reason).await,
+      Err(err) => return self.abort_step(step.id, started_at, err.to_string()).await,
+    };
 
     let step_id = step.id.clone();
     let timeout = step.timeout;
 
-    let started_at = chrono::Utc::now();
-
     let event = crate::RunStepEvent {
       source: step.clone(),
       trigger_event: self.condition_matcher.event.clone(),
       payload: self.payload.clone(),
     };
 
-    self.call_on_run_step(event.clone()).await;
+    if let Err(err) = self.call_on_run_step(event.clone()).await {
+      return self.abort_step(step_id, started_at, err.to_string()).await;
+    }
 
     // Queued
     let event = WorkflowStateEvent::StepStateUpdated {
@@ -90,6 +100,7 @@ impl ExecutionContext {
           exit_code: Some(1),
           started_at: Some(started_at),
           completed_at: Some(completed_at),
+          summary: None,
         };
 
         self.call_on_step_completed(result.clone()).await;
@@ -124,11 +135,24 @@ impl ExecutionContext {
         }
         received = receiver.next() => {
           if let Some(log) = received {
+            let time = log.time.unwrap_or_else(chrono::Utc::now);
+            let sequence = log.sequence.unwrap_or_default();
+
+            // Plain output may contain workflow commands such as `::warning::`
+            let log = match log.log_type {
+              WorkflowLogType::Log | WorkflowLogType::Error => {
+                parse_workflow_command(&log.message).unwrap_or(log)
+              }
+              _ => log,
+            };
+
             let log = WorkflowLog {
               step_id: step_id.clone(),
               log_type: log.log_type,
               message: log.message,
-              time: chrono::Utc::now(),
+              time,
+              annotation: log.annotation,
+              sequence,
             };
 
             self.call_on_log(log.clone()).await;
@@ -147,6 +171,24 @@ impl ExecutionContext {
       ))
       .unwrap();
 
+    let metrics = receiver.metrics();
+    if metrics.dropped > 0 {
+      self
+        .call_on_log(WorkflowLog {
+          step_id: step_id.clone(),
+          log_type: WorkflowLogType::Warning,
+          message: format!(
+            "{} log lines were dropped because the log buffer was full",
+            metrics.dropped
+          ),
+          time: chrono::Utc::now(),
+          annotation: None,
+          sequence: metrics.sent,
+        })
+        .await;
+    }
+
+    let summary = receiver.summary();
     let completed_at = chrono::Utc::now();
     let duration = completed_at - started_at;
 
@@ -164,6 +206,7 @@ impl ExecutionContext {
         exit_code: None,
         started_at: Some(started_at),
         completed_at: Some(completed_at),
+        summary,
       },
       RunResult::Failed { exit_code } => StepRunResult {
         id: step_id.clone(),
@@ -171,6 +214,7 @@ impl ExecutionContext {
         exit_code: Some(exit_code),
         started_at: Some(started_at),
         completed_at: Some(completed_at),
+        summary,
       },
       RunResult::Cancelled => StepRunResult {
         id: step_id.clone(),
@@ -178,6 +222,7 @@ impl ExecutionContext {
         exit_code: None,
         started_at: Some(started_at),
         completed_at: Some(completed_at),
+        summary,
       },
     };
 
@@ -195,6 +240,65 @@ impl ExecutionContext {
     res
   }
 
+  /// Skips a step as requested by a plugin hook
+  async fn skip_step(&self, step_id: StepId, reason: String) -> StepRunResult {
+    log::info!("Step {} is skipped: {}", step_id, reason);
+
+    self
+      .call_on_state_change(WorkflowStateEvent::StepStateUpdated {
+        id: step_id.clone(),
+        state: WorkflowState::Skipped,
+      })
+      .await;
+
+    let result = StepRunResult {
+      id: step_id,
+      state: WorkflowState::Skipped,
+      exit_code: None,
+      started_at: None,
+      completed_at: None,
+      summary: None,
+    };
+
+    self.call_on_step_completed(result.clone()).await;
+
+    result
+  }
+
+  /// Fails a step which a plugin hook refused to run
+  async fn abort_step(&self, step_id: StepId, started_at: Time, message: String) -> StepRunResult {
+    self
+      .call_on_log(WorkflowLog {
+        step_id: step_id.clone(),
+        log_type: WorkflowLogType::Error,
+        message,
+        time: chrono::Utc::now(),
+        annotation: None,
+        sequence: 0,
+      })
+      .await;
+
+    self
+      .call_on_state_change(WorkflowStateEvent::StepStateUpdated {
+        id: step_id.clone(),
+        state: WorkflowState::Failed,
+      })
+      .await;
+
+    let result = StepRunResult {
+      id: step_id,
+      state: WorkflowState::Failed,
+      exit_code: None,
+      started_at: Some(started_at),
+      completed_at: Some(chrono::Utc::now()),
+      summary: None,
+    };
+
+    self.call_on_step_completed(result.clone()).await;
+
+    result
+  }
+
   pub fn cancel_job(&self, job_id: &JobId) -> Result<()> {
     self.signal_manager.cancel_job(job_id)
   }
@@ -203,19 +307,22 @@ impl ExecutionContext {
     self.condition_matcher.is_match(condition).await
   }
 
-  pub(crate) async fn call_on_run_workflow(&self, workflow: Workflow) {
+  pub(crate) async fn call_on_run_workflow(&self, workflow: Workflow) -> Result<()> {
     let event = crate::RunWorkflowEvent {
       source: workflow,
       trigger_event: self.condition_matcher.event.clone(),
       payload: self.payload.clone(),
     };
-    self.plugin_driver.on_run_workflow(event.clone()).await;
+    let res = self.plugin_driver.on_run_workflow(event.clone()).await;
+    self.publish(AstroRunEvent::WorkflowStarted(event.clone()));
     if let Err(err) = self.runner.on_run_workflow(event).await {
       log::error!("Failed to run workflow: {:?}", err);
     }
+
+    res
   }
 
-  pub(crate) async fn call_on_run_job(&self, job: Job) {
+  pub(crate) async fn call_on_run_job(&self, job: Job) -> Result<()> {
     self
       .signal_manager
       .register_signal(job.id.clone(), AstroRunSignal::new());
@@ -226,14 +333,18 @@ impl ExecutionContext {
       payload: self.payload.clone(),
     };
 
-    self.plugin_driver.on_run_job(event.clone()).await;
+    let res = self.plugin_driver.on_run_job(event.clone()).await;
+    self.publish(AstroRunEvent::JobStarted(event.clone()));
     if let Err(err) = self.runner.on_run_job(event).await {
       log::error!("Failed to run job: {:?}", err);
     }
+
+    res
   }
 
   pub(crate) async fn call_on_state_change(&self, event: WorkflowStateEvent) {
     self.plugin_driver.on_state_change(event.clone()).await;
+    self.publish(AstroRunEvent::StateChanged(event.clone()));
 
     if let Err(err) = self.runner.on_state_change(event).await {
       log::error!("Failed to handle state change: {:?}", err);
@@ -243,43 +354,71 @@ impl ExecutionContext {
   pub(crate) async fn call_on_job_completed(&self, result: JobRunResult) {
     self.signal_manager.unregister_signal(&result.id);
     self.plugin_driver.on_job_completed(result.clone()).await;
+    self.publish(AstroRunEvent::JobCompleted(result.clone()));
 
     if let Err(err) = self.runner.on_job_completed(result).await {
       log::error!("Failed to handle job completed: {:?}", err);
     }
   }
 
-  pub(crate) async fn call_on_run_step(&self, event: RunStepEvent) {
-    self.plugin_driver.on_run_step(event.clone()).await;
+  pub(crate) async fn call_on_run_step(&self, event: RunStepEvent) -> Result<()> {
+    let res = self.plugin_driver.on_run_step(event.clone()).await;
+    self.publish(AstroRunEvent::StepStarted(event.clone()));
 
     if let Err(err) = self.runner.on_run_step(event).await {
       log::error!("Failed to run step: {:?}", err);
     }
+
+    res
   }
 
   pub(crate) async fn call_on_step_completed(&self, result: StepRunResult) {
     self.plugin_driver.on_step_completed(result.clone()).await;
+    self.publish(AstroRunEvent::StepCompleted(result.clone()));
 
     if let Err(err) = self.runner.on_step_completed(result.clone()).await {
       log::error!("Failed to handle step completed: {:?}", err);
     }
   }
 
-  pub(crate) async fn call_on_before_run_step(&self, step: Step) -> Step {
-    let step = self.plugin_driver.on_before_run_step(step).await;
+  pub(crate) async fn call_on_before_run_workflow(
+    &self,
+    workflow: Workflow,
+  ) -> Result<BeforeRun<Workflow>> {
+    self.plugin_driver.on_before_run_workflow(workflow).await
+  }
+
+  pub(crate) async fn call_on_before_run_job(&self, job: Job) -> Result<BeforeRun<Job>> {
+    self.plugin_driver.on_before_run_job(job).await
+  }
+
+  pub(crate) async fn call_on_before_run_step(&self, step: Step) -> Result<BeforeRun<Step>> {
+    let step = match self.plugin_driver.on_before_run_step(step).await? {
+      BeforeRun::Continue(step) => step,
+      decision => return Ok(decision),
+    };
+
+    match self.runner.on_before_run_step(step.clone()).await {
+      Ok(decision) => Ok(decision),
+      Err(err) => {
+        log::error!("Failed to call before run step hook of the runner: {}", err);
 
-    if let Ok(step) = self.runner.on_before_run_step(step.clone()).await {
-      step
-    } else {
-      step
+        Ok(BeforeRun::Continue(step))
+      }
     }
   }
 
+  /// Whether a plugin hook failed during the workflow, see `PluginFailurePolicy::FailWorkflow`
+  pub(crate) fn take_plugin_failure(&self, workflow_id: &WorkflowId) -> bool {
+    self.plugin_driver.take_workflow_failure(workflow_id)
+  }
+
   pub(crate) async fn call_on_workflow_completed(&self, result: WorkflowRunResult) {
     self
       .plugin_driver
       .on_workflow_completed(result.clone())
       .await;
+    self.publish(AstroRunEvent::WorkflowCompleted(result.clone()));
 
     if let Err(err) = self.runner.on_workflow_completed(result).await {
       log::error!("Failed to handle workflow completed: {:?}", err);
@@ -288,12 +427,23 @@ impl ExecutionContext {
 
   pub(crate) async fn call_on_log(&self, log: WorkflowLog) {
     self.plugin_driver.on_log(log.clone()).await;
+    self.publish(AstroRunEvent::Log(log.clone()));
 
     if let Err(err) = self.runner.on_log(log).await {
       log::error!("Failed to handle log: {:?}", err);
     }
   }
 
+  /// Subscribes to the events of all the runs using this context
+  pub fn subscribe(&self) -> EventStream {
+    EventStream::new(self.event_sender.subscribe())
+  }
+
+  fn publish(&self, event: AstroRunEvent) {
+    // Fails only when nobody is subscribed
+    self.event_sender.send(event).ok();
+  }
+
   pub fn builder() -> ExecutionContextBuilder {
     ExecutionContextBuilder::new()
   }
@@ -1,24 +1,40 @@
 mod actions;
 mod astro_run;
+mod changed_files;
+mod cron_scheduler;
+mod event_matchers;
+mod events;
 mod execution_context;
+mod log_store;
 mod plugins;
 mod runner;
 mod signals;
+mod state_store;
 mod stream;
 mod types;
 mod user_config;
 mod workflow;
+mod workflow_commands;
+mod workflow_registry;
 
 pub use crate::astro_run::*;
 pub use actions::*;
+pub use changed_files::*;
+pub use cron_scheduler::*;
+pub use event_matchers::*;
+pub use events::*;
 pub use execution_context::*;
+pub use log_store::*;
 pub use plugins::*;
 pub use runner::*;
 pub use signals::*;
+pub use state_store::*;
 pub use stream::*;
 pub use types::*;
 pub use user_config::*;
 pub use workflow::*;
+pub use workflow_commands::*;
+pub use workflow_registry::*;
 
 pub use async_trait::async_trait;
 
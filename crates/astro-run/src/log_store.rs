@@ -0,0 +1,606 @@
+use crate::{
+  Error, HookNoopResult, Plugin, Result, StepId, StepRunResult, WorkflowLog, WorkflowLogType,
+  WorkflowRunResult,
+};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::{collections::HashMap, path::PathBuf, pin::Pin, sync::Arc, time::Duration};
+use tokio::{
+  fs::{File, OpenOptions},
+  io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, SeekFrom},
+  sync::{mpsc, watch, Mutex},
+};
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+
+/// Keeps ids and job keys readable while making them safe as path segments
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_');
+
+/// Size of an index entry: the byte offset of a log line as little-endian u64
+const INDEX_ENTRY_SIZE: u64 = 8;
+
+pub type LogStream = Pin<Box<dyn Stream<Item = WorkflowLog> + Send>>;
+
+/// Step which is currently being written
+struct LiveStep {
+  /// Locked while a log is appended, so that steps are written concurrently
+  writer: Mutex<StepWriter>,
+  /// Number of stored entries, observed by tailers
+  count: watch::Sender<u64>,
+}
+
+struct StepWriter {
+  log_file: File,
+  index_file: File,
+  /// Size of the log file
+  position: u64,
+  /// Size of the stored messages, counted against `max_step_size`
+  size: u64,
+  truncated: bool,
+}
+
+/// Reads the entries of a step in order, keeping its files open to follow new entries
+struct StepReader {
+  index_file: File,
+  log_file: BufReader<File>,
+  /// Bytes of an index entry which is still being written
+  partial_entry: Vec<u8>,
+}
+
+impl StepReader {
+  /// Returns `None` if the step has no log yet
+  async fn open(log_path: PathBuf, index_path: PathBuf) -> Result<Option<Self>> {
+    let index_file = match File::open(&index_path).await {
+      Ok(file) => file,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+      Err(err) => return Err(err.into()),
+    };
+    let log_file = File::open(&log_path).await?;
+
+    Ok(Some(Self {
+      index_file,
+      log_file: BufReader::new(log_file),
+      partial_entry: vec![],
+    }))
+  }
+
+  /// Moves to the entry `offset`, returns `false` if it isn't stored
+  async fn seek(&mut self, offset: u64) -> Result<bool> {
+    let start = SeekFrom::Start(offset * INDEX_ENTRY_SIZE);
+
+    self.index_file.seek(start).await?;
+    let mut entry = [0; INDEX_ENTRY_SIZE as usize];
+    match self.index_file.read_exact(&mut entry).await {
+      Ok(_) => {}
+      Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+      Err(err) => return Err(err.into()),
+    }
+
+    self.index_file.seek(start).await?;
+    self
+      .log_file
+      .seek(SeekFrom::Start(u64::from_le_bytes(entry)))
+      .await?;
+
+    Ok(true)
+  }
+
+  /// Reads the entries stored since the last read
+  async fn read(&mut self) -> Result<Vec<WorkflowLog>> {
+    let mut index = std::mem::take(&mut self.partial_entry);
+    self.index_file.read_to_end(&mut index).await?;
+
+    let count = index.len() / INDEX_ENTRY_SIZE as usize;
+    self.partial_entry = index.split_off(count * INDEX_ENTRY_SIZE as usize);
+
+    // A line is complete once its entry is in the index
+    let mut logs = Vec::with_capacity(count);
+    let mut line = String::new();
+    for _ in 0..count {
+      line.clear();
+      self.log_file.read_line(&mut line).await?;
+
+      let log = serde_json::from_str(&line)
+        .map_err(|err| Error::error(format!("Failed to parse log: {}", err)))?;
+      logs.push(log);
+    }
+
+    Ok(logs)
+  }
+}
+
+struct Inner {
+  dir: PathBuf,
+  max_step_size: Option<u64>,
+  retention: Option<Duration>,
+  live_steps: Mutex<HashMap<StepId, Arc<LiveStep>>>,
+  /// Tailers waiting for steps which haven't logged yet
+  pending_steps: Mutex<HashMap<StepId, watch::Sender<u64>>>,
+}
+
+/// # FileLogStore
+/// Persists the logs of every step, so that they can be read after the run or tailed while running.
+///
+/// Each step is stored in `<dir>/<workflow id>/<job key>/<step number>.log` as JSON lines,
+/// next to an `.idx` file holding the byte offset of every line. Offsets in the read APIs are
+/// entry numbers.
+///
+/// Register it as a plugin:
+///
+/// ```no_run
+/// # use astro_run::{AstroRun, FileLogStore};
+/// # fn build(runner: impl astro_run::Runner + 'static) -> AstroRun {
+/// let log_store = FileLogStore::new("/var/lib/astro-run/logs");
+///
+/// AstroRun::builder()
+///   .runner(runner)
+///   .plugin(log_store.clone())
+///   .build()
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct FileLogStore {
+  inner: Arc<Inner>,
+}
+
+impl FileLogStore {
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    Self::builder(dir).build()
+  }
+
+  pub fn builder(dir: impl Into<PathBuf>) -> FileLogStoreBuilder {
+    FileLogStoreBuilder::new(dir)
+  }
+
+  /// Appends a log to its step
+  pub async fn append(&self, log: &WorkflowLog) -> Result<()> {
+    let live_step = {
+      let mut live_steps = self.inner.live_steps.lock().await;
+
+      match live_steps.get(&log.step_id) {
+        Some(live_step) => Arc::clone(live_step),
+        None => {
+          let live_step = Arc::new(self.open_step(&log.step_id).await?);
+          live_steps.insert(log.step_id.clone(), Arc::clone(&live_step));
+          live_step
+        }
+      }
+    };
+
+    let mut step = live_step.writer.lock().await;
+    if step.truncated {
+      return Ok(());
+    }
+
+    let mut log = log.clone();
+    let size = log.message.len() as u64;
+
+    if let Some(max_step_size) = self.inner.max_step_size {
+      if step.size + size > max_step_size {
+        step.truncated = true;
+        log.log_type = WorkflowLogType::Error;
+        log.message = format!(
+          "Log size limit of {} bytes exceeded, the rest of the log is discarded",
+          max_step_size
+        );
+      }
+    }
+
+    let mut line = serde_json::to_vec(&log)
+      .map_err(|err| Error::error(format!("Failed to serialize log: {}", err)))?;
+    line.push(b'\n');
+
+    step.log_file.write_all(&line).await?;
+    step.log_file.flush().await?;
+    // The index is written last, so readers never see an entry before its line is complete
+    let entry = step.position.to_le_bytes();
+    step.index_file.write_all(&entry).await?;
+    step.index_file.flush().await?;
+
+    step.position += line.len() as u64;
+    step.size += size;
+    live_step.count.send_modify(|count| *count += 1);
+
+    Ok(())
+  }
+
+  /// Marks the step as completed, which ends its tails
+  pub async fn finish(&self, step_id: &StepId) {
+    self.inner.live_steps.lock().await.remove(step_id);
+    self.inner.pending_steps.lock().await.remove(step_id);
+  }
+
+  /// Reads the full log of a step
+  pub async fn read(&self, step_id: &StepId) -> Result<Vec<WorkflowLog>> {
+    self.read_from(step_id, 0).await
+  }
+
+  /// Reads the log of a step, starting from the entry `offset`
+  pub async fn read_from(&self, step_id: &StepId, offset: u64) -> Result<Vec<WorkflowLog>> {
+    let Some(mut reader) = self.reader(step_id).await? else {
+      return Ok(vec![]);
+    };
+
+    if !reader.seek(offset).await? {
+      return Ok(vec![]);
+    }
+
+    reader.read().await
+  }
+
+  /// Streams the stored log of a step, followed by new entries until the step completes.
+  ///
+  /// Tailing a step which hasn't started waits for its first log.
+  pub async fn tail(&self, step_id: &StepId) -> LogStream {
+    let mut count = self.subscribe(step_id).await;
+    let (sender, receiver) = mpsc::channel(64);
+    let store = self.clone();
+    let step_id = step_id.clone();
+
+    tokio::spawn(async move {
+      // Opened once the step has logged
+      let mut reader: Option<StepReader> = None;
+
+      loop {
+        // Ends once the step is finished, after reading what's left
+        let is_live = match &mut count {
+          Some(count) => {
+            count.borrow_and_update();
+            true
+          }
+          None => false,
+        };
+
+        let res = match &mut reader {
+          Some(reader) => reader.read().await,
+          None => match store.reader(&step_id).await {
+            Ok(Some(opened)) => reader.insert(opened).read().await,
+            Ok(None) => Ok(vec![]),
+            Err(err) => Err(err),
+          },
+        };
+
+        let logs = match res {
+          Ok(logs) => logs,
+          Err(err) => {
+            log::error!("Failed to tail step {}: {}", step_id, err);
+            break;
+          }
+        };
+
+        for log in logs {
+          if sender.send(log).await.is_err() {
+            return;
+          }
+        }
+
+        if !is_live {
+          break;
+        }
+
+        if let Some(receiver) = &mut count {
+          if receiver.changed().await.is_err() {
+            count = None;
+          }
+        }
+      }
+    });
+
+    Box::pin(ReceiverStream::new(receiver))
+  }
+
+  /// Removes the logs of workflows older than the retention period, keeping live ones
+  pub async fn prune(&self) -> Result<()> {
+    let Some(retention) = self.inner.retention else {
+      return Ok(());
+    };
+
+    let mut entries = match tokio::fs::read_dir(&self.inner.dir).await {
+      Ok(entries) => entries,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+      Err(err) => return Err(err.into()),
+    };
+
+    let live_workflows: Vec<PathBuf> = self
+      .inner
+      .live_steps
+      .lock()
+      .await
+      .keys()
+      .map(|step_id| self.workflow_dir(step_id))
+      .collect();
+
+    while let Some(entry) = entries.next_entry().await? {
+      let path = entry.path();
+      if live_workflows.contains(&path) {
+        continue;
+      }
+
+      let modified = entry.metadata().await?.modified()?;
+      let is_expired = modified
+        .elapsed()
+        .map(|elapsed| elapsed > retention)
+        .unwrap_or(false);
+
+      if is_expired {
+        log::trace!("Removing expired logs {}", path.display());
+        tokio::fs::remove_dir_all(&path).await?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Returns `None` if the step is already completed
+  async fn subscribe(&self, step_id: &StepId) -> Option<watch::Receiver<u64>> {
+    // Held until the tailer is registered, so that the step can't start in between
+    let live_steps = self.inner.live_steps.lock().await;
+    if let Some(live_step) = live_steps.get(step_id) {
+      return Some(live_step.count.subscribe());
+    }
+
+    let (_, index_path) = self.paths(step_id);
+    if tokio::fs::try_exists(&index_path).await.unwrap_or(false) {
+      return None;
+    }
+
+    let mut pending_steps = self.inner.pending_steps.lock().await;
+    let sender = pending_steps
+      .entry(step_id.clone())
+      .or_insert_with(|| watch::channel(0).0);
+
+    Some(sender.subscribe())
+  }
+
+  async fn open_step(&self, step_id: &StepId) -> Result<LiveStep> {
+    let (log_path, index_path) = self.paths(step_id);
+    if let Some(dir) = log_path.parent() {
+      tokio::fs::create_dir_all(dir).await?;
+    }
+
+    let open = |path: PathBuf| async move {
+      OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+    };
+
+    let log_file = open(log_path).await?;
+    let index_file = open(index_path).await?;
+    let position = log_file.metadata().await?.len();
+    let count = index_file.metadata().await?.len() / INDEX_ENTRY_SIZE;
+
+    // Wake up the tailers which were waiting for the step
+    let sender = match self.inner.pending_steps.lock().await.remove(step_id) {
+      Some(sender) => sender,
+      None => watch::channel(0).0,
+    };
+    sender.send_replace(count);
+
+    Ok(LiveStep {
+      writer: Mutex::new(StepWriter {
+        log_file,
+        index_file,
+        position,
+        // A resumed step counts what it already stored, including the JSON around the messages
+        size: position,
+        truncated: false,
+      }),
+      count: sender,
+    })
+  }
+
+  async fn reader(&self, step_id: &StepId) -> Result<Option<StepReader>> {
+    let (log_path, index_path) = self.paths(step_id);
+
+    StepReader::open(log_path, index_path).await
+  }
+
+  fn workflow_dir(&self, step_id: &StepId) -> PathBuf {
+    self.inner.dir.join(encode(&step_id.workflow_id().inner()))
+  }
+
+  fn paths(&self, step_id: &StepId) -> (PathBuf, PathBuf) {
+    let dir = self.workflow_dir(step_id).join(encode(&step_id.job_key()));
+    let step_number = step_id.step_number();
+
+    (
+      dir.join(format!("{}.log", step_number)),
+      dir.join(format!("{}.idx", step_number)),
+    )
+  }
+}
+
+fn encode(segment: &str) -> String {
+  utf8_percent_encode(segment, PATH_SEGMENT).to_string()
+}
+
+#[async_trait::async_trait]
+impl Plugin for FileLogStore {
+  fn name(&self) -> &'static str {
+    "astro-run-log-store"
+  }
+
+  async fn on_log(&self, log: WorkflowLog) -> HookNoopResult {
+    self.append(&log).await
+  }
+
+  async fn on_step_completed(&self, result: StepRunResult) -> HookNoopResult {
+    self.finish(&result.id).await;
+
+    Ok(())
+  }
+
+  async fn on_workflow_completed(&self, _result: WorkflowRunResult) -> HookNoopResult {
+    self.prune().await
+  }
+}
+
+pub struct FileLogStoreBuilder {
+  dir: PathBuf,
+  max_step_size: Option<u64>,
+  retention: Option<Duration>,
+}
+
+impl FileLogStoreBuilder {
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    Self {
+      dir: dir.into(),
+      max_step_size: None,
+      retention: None,
+    }
+  }
+
+  /// Maximum size in bytes of the messages of a step. The rest of the log is discarded
+  pub fn max_step_size(mut self, max_step_size: u64) -> Self {
+    self.max_step_size = Some(max_step_size);
+    self
+  }
+
+  /// Logs of workflows older than `retention` are removed whenever a workflow completes
+  pub fn retention(mut self, retention: Duration) -> Self {
+    self.retention = Some(retention);
+    self
+  }
+
+  pub fn build(self) -> FileLogStore {
+    FileLogStore {
+      inner: Arc::new(Inner {
+        dir: self.dir,
+        max_step_size: self.max_step_size,
+        retention: self.retention,
+        live_steps: Mutex::new(HashMap::new()),
+        pending_steps: Mutex::new(HashMap::new()),
+      }),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tokio_stream::StreamExt;
+
+  fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("astro-run-logs-{}", uuid::Uuid::new_v4()))
+  }
+
+  fn log(step_id: &StepId, message: &str) -> WorkflowLog {
+    WorkflowLog {
+      step_id: step_id.clone(),
+      message: message.to_string(),
+      ..Default::default()
+    }
+  }
+
+  fn messages(logs: Vec<WorkflowLog>) -> Vec<String> {
+    logs.into_iter().map(|log| log.message).collect()
+  }
+
+  #[astro_run_test::test]
+  async fn test_read_logs() {
+    let dir = temp_dir();
+    let store = FileLogStore::new(&dir);
+    let step_id = StepId::new("workflow", "job/with spaces", 0);
+
+    assert!(store.read(&step_id).await.unwrap().is_empty());
+
+    for message in ["first", "second", "third"] {
+      store.append(&log(&step_id, message)).await.unwrap();
+    }
+    store.finish(&step_id).await;
+
+    assert_eq!(
+      messages(store.read(&step_id).await.unwrap()),
+      vec!["first", "second", "third"]
+    );
+    assert_eq!(
+      messages(store.read_from(&step_id, 1).await.unwrap()),
+      vec!["second", "third"]
+    );
+    assert!(store.read_from(&step_id, 3).await.unwrap().is_empty());
+
+    // Survives a restart
+    let store = FileLogStore::new(&dir);
+    assert_eq!(store.read(&step_id).await.unwrap().len(), 3);
+    let logs: Vec<_> = store.tail(&step_id).await.collect().await;
+    assert_eq!(messages(logs), vec!["first", "second", "third"]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[astro_run_test::test]
+  async fn test_tail() {
+    let dir = temp_dir();
+    let store = FileLogStore::new(&dir);
+    let step_id = StepId::new("workflow", "job", 1);
+
+    // Subscribed before the step starts
+    let tail = store.tail(&step_id).await;
+
+    store.append(&log(&step_id, "first")).await.unwrap();
+    let late_tail = store.tail(&step_id).await;
+    store.append(&log(&step_id, "second")).await.unwrap();
+
+    let writer = store.clone();
+    let id = step_id.clone();
+    tokio::spawn(async move {
+      tokio::time::sleep(Duration::from_millis(20)).await;
+      writer.append(&log(&id, "third")).await.unwrap();
+      writer.finish(&id).await;
+    });
+
+    let logs: Vec<_> = tail.collect().await;
+    assert_eq!(messages(logs), vec!["first", "second", "third"]);
+
+    let logs: Vec<_> = late_tail.collect().await;
+    assert_eq!(messages(logs), vec!["first", "second", "third"]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[astro_run_test::test]
+  async fn test_limits() {
+    let dir = temp_dir();
+    let store = FileLogStore::builder(&dir)
+      .max_step_size(10)
+      .retention(Duration::from_millis(10))
+      .build();
+    let step_id = StepId::new("workflow", "job", 0);
+
+    store.append(&log(&step_id, "12345")).await.unwrap();
+    store.append(&log(&step_id, "123456")).await.unwrap();
+    store.append(&log(&step_id, "1")).await.unwrap();
+
+    let logs = store.read(&step_id).await.unwrap();
+    assert_eq!(logs.len(), 2);
+    assert!(logs[1].is_error());
+    assert_eq!(
+      logs[1].message,
+      "Log size limit of 10 bytes exceeded, the rest of the log is discarded"
+    );
+
+    // The limit holds for a step resumed after a restart
+    let resumed_step_id = StepId::new("workflow", "job", 1);
+    store.append(&log(&resumed_step_id, "12345")).await.unwrap();
+    store.finish(&resumed_step_id).await;
+    let resumed = FileLogStore::builder(&dir).max_step_size(10).build();
+    resumed.append(&log(&resumed_step_id, "1")).await.unwrap();
+    resumed.finish(&resumed_step_id).await;
+
+    let logs = store.read(&resumed_step_id).await.unwrap();
+    assert_eq!(logs.len(), 2);
+    assert!(logs[1].is_error());
+
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // Live steps are kept
+    store.prune().await.unwrap();
+    assert_eq!(store.read(&step_id).await.unwrap().len(), 2);
+
+    store.finish(&step_id).await;
+    store.prune().await.unwrap();
+    assert!(store.read(&step_id).await.unwrap().is_empty());
+
+    std::fs::remove_dir_all(dir).ok();
+  }
+}
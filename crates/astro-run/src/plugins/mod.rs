@@ -27,24 +27,57 @@ pub type RunStepEvent = RunEvent<Step>;
 
 pub type HookNoopResult = Result<(), Error>;
 
-pub type HookBeforeRunStepResult = Result<Step, Error>;
+/// The decision of an `on_before_run_*` plugin hook
+#[derive(Debug, Clone)]
+pub enum BeforeRun<T> {
+  /// Runs it, including the changes made by the hook
+  Continue(T),
+  /// Skips it, with the reason
+  Skip(String),
+  /// Fails it without running, with the reason
+  Fail(String),
+}
+
+impl<T> From<T> for BeforeRun<T> {
+  fn from(value: T) -> Self {
+    BeforeRun::Continue(value)
+  }
+}
+
+pub type HookBeforeRunResult<T> = Result<BeforeRun<T>, Error>;
+
+pub type HookBeforeRunStepResult = HookBeforeRunResult<Step>;
 
 pub type HookResolveActionResult = Result<Option<Box<dyn Action>>, Error>;
 
 #[async_trait::async_trait]
 pub trait Plugin: Send + Sync {
   fn name(&self) -> &'static str;
+  /// Plugins with a higher priority run first. Plugins with the same priority
+  /// run in the order they were registered
+  fn priority(&self) -> i32 {
+    0
+  }
   async fn on_resolve_dynamic_action(&self, _step: UserActionStep) -> HookResolveActionResult {
     Ok(None)
   }
+  /// Called before a workflow runs. It can change the workflow, skip it or fail it
+  async fn on_before_run_workflow(&self, workflow: Workflow) -> HookBeforeRunResult<Workflow> {
+    Ok(BeforeRun::Continue(workflow))
+  }
   async fn on_run_workflow(&self, _event: RunWorkflowEvent) -> HookNoopResult {
     Ok(())
   }
+  /// Called before a job runs. It can change the job, skip it or fail it
+  async fn on_before_run_job(&self, job: Job) -> HookBeforeRunResult<Job> {
+    Ok(BeforeRun::Continue(job))
+  }
   async fn on_run_job(&self, _event: RunJobEvent) -> HookNoopResult {
     Ok(())
   }
-  async fn on_before_run_step(&self, step: Step) -> HookBeforeRunStepResult {
-    Ok(step)
+  /// Called before a step runs. It can change the step, skip it or fail it
+  async fn on_before_run_step(&self, step: Step) -> HookBeforeRunResult<Step> {
+    Ok(BeforeRun::Continue(step))
   }
   async fn on_run_step(&self, _event: RunStepEvent) -> HookNoopResult {
     Ok(())
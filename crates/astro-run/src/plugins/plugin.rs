@@ -1,26 +1,53 @@
 use crate::{
-  HookBeforeRunStepResult, HookNoopResult, HookResolveActionResult, JobRunResult, Plugin,
-  RunJobEvent, RunStepEvent, RunWorkflowEvent, Step, StepRunResult, UserActionStep, WorkflowLog,
-  WorkflowRunResult, WorkflowStateEvent,
+  BeforeRun, HookBeforeRunResult, HookNoopResult, HookResolveActionResult, Job, JobRunResult,
+  Plugin, RunJobEvent, RunStepEvent, RunWorkflowEvent, Step, StepRunResult, UserActionStep,
+  Workflow, WorkflowLog, WorkflowRunResult, WorkflowStateEvent,
 };
+use std::{future::Future, pin::Pin};
+
+type Hook<T, R> = dyn Fn(T) -> Pin<Box<dyn Future<Output = R> + Send>> + Send + Sync;
+
+type OnStateChange = Hook<WorkflowStateEvent, HookNoopResult>;
+type OnLog = Hook<WorkflowLog, HookNoopResult>;
+type OnRunWorkflow = Hook<RunWorkflowEvent, HookNoopResult>;
+type OnRunJob = Hook<RunJobEvent, HookNoopResult>;
+type OnRunStep = Hook<RunStepEvent, HookNoopResult>;
+type OnWorkflowComplete = Hook<WorkflowRunResult, HookNoopResult>;
+type OnJobComplete = Hook<JobRunResult, HookNoopResult>;
+type OnStepComplete = Hook<StepRunResult, HookNoopResult>;
+type OnResolveDynamicAction = Hook<UserActionStep, HookResolveActionResult>;
+type OnBeforeRunStep = Hook<Step, HookBeforeRunResult<Step>>;
+type OnBeforeRunJob = Hook<Job, HookBeforeRunResult<Job>>;
+type OnBeforeRunWorkflow = Hook<Workflow, HookBeforeRunResult<Workflow>>;
+
+fn sync_hook<T, R, F>(hook: F) -> Box<Hook<T, R>>
+where
+  F: Fn(T) -> R + Send + Sync + 'static,
+  R: Send + 'static,
+{
+  Box::new(move |arg| {
+    let result = hook(arg);
+    Box::pin(async move { result })
+  })
+}
 
-type OnStateChange = dyn Fn(WorkflowStateEvent) -> HookNoopResult + Send + Sync;
-type OnLog = dyn Fn(WorkflowLog) -> HookNoopResult + Send + Sync;
-type OnRunWorkflow = dyn Fn(RunWorkflowEvent) -> HookNoopResult + Send + Sync;
-type OnRunJob = dyn Fn(RunJobEvent) -> HookNoopResult + Send + Sync;
-type OnRunStep = dyn Fn(RunStepEvent) -> HookNoopResult + Send + Sync;
-type OnWorkflowComplete = dyn Fn(WorkflowRunResult) -> HookNoopResult + Send + Sync;
-type OnJobComplete = dyn Fn(JobRunResult) -> HookNoopResult + Send + Sync;
-type OnStepComplete = dyn Fn(StepRunResult) -> HookNoopResult + Send + Sync;
-type OnResolveDynamicAction = dyn Fn(UserActionStep) -> HookResolveActionResult + Send + Sync;
-type OnBeforeRunStep = dyn Fn(Step) -> HookBeforeRunStepResult + Send + Sync;
+fn async_hook<T, R, F, Fut>(hook: F) -> Box<Hook<T, R>>
+where
+  F: Fn(T) -> Fut + Send + Sync + 'static,
+  Fut: Future<Output = R> + Send + 'static,
+{
+  Box::new(move |arg| Box::pin(hook(arg)))
+}
 
 pub struct PluginBuilder {
   name: &'static str,
+  priority: i32,
   on_resolve_dynamic_action: Option<Box<OnResolveDynamicAction>>,
   on_run_workflow: Option<Box<OnRunWorkflow>>,
   on_run_job: Option<Box<OnRunJob>>,
   on_before_run_step: Option<Box<OnBeforeRunStep>>,
+  on_before_run_job: Option<Box<OnBeforeRunJob>>,
+  on_before_run_workflow: Option<Box<OnBeforeRunWorkflow>>,
   on_run_step: Option<Box<OnRunStep>>,
   on_state_change: Option<Box<OnStateChange>>,
   on_log: Option<Box<OnLog>>,
@@ -33,12 +60,15 @@ impl PluginBuilder {
   fn new(name: &'static str) -> Self {
     PluginBuilder {
       name,
+      priority: 0,
       on_resolve_dynamic_action: None,
       on_state_change: None,
       on_log: None,
       on_run_workflow: None,
       on_run_job: None,
       on_before_run_step: None,
+      on_before_run_job: None,
+      on_before_run_workflow: None,
       on_run_step: None,
       on_step_completed: None,
       on_job_completed: None,
@@ -46,11 +76,26 @@ impl PluginBuilder {
     }
   }
 
+  /// See `Plugin::priority`
+  pub fn priority(mut self, priority: i32) -> Self {
+    self.priority = priority;
+    self
+  }
+
   pub fn on_state_change<T>(mut self, on_state_change: T) -> Self
   where
     T: Fn(WorkflowStateEvent) -> HookNoopResult + 'static + Send + Sync,
   {
-    self.on_state_change = Some(Box::new(on_state_change));
+    self.on_state_change = Some(sync_hook(on_state_change));
+    self
+  }
+
+  pub fn on_state_change_async<T, Fut>(mut self, on_state_change: T) -> Self
+  where
+    T: Fn(WorkflowStateEvent) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookNoopResult> + Send + 'static,
+  {
+    self.on_state_change = Some(async_hook(on_state_change));
     self
   }
 
@@ -58,7 +103,16 @@ impl PluginBuilder {
   where
     T: Fn(WorkflowLog) -> HookNoopResult + 'static + Send + Sync,
   {
-    self.on_log = Some(Box::new(on_log));
+    self.on_log = Some(sync_hook(on_log));
+    self
+  }
+
+  pub fn on_log_async<T, Fut>(mut self, on_log: T) -> Self
+  where
+    T: Fn(WorkflowLog) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookNoopResult> + Send + 'static,
+  {
+    self.on_log = Some(async_hook(on_log));
     self
   }
 
@@ -66,7 +120,16 @@ impl PluginBuilder {
   where
     T: Fn(RunWorkflowEvent) -> HookNoopResult + 'static + Send + Sync,
   {
-    self.on_run_workflow = Some(Box::new(on_run_workflow));
+    self.on_run_workflow = Some(sync_hook(on_run_workflow));
+    self
+  }
+
+  pub fn on_run_workflow_async<T, Fut>(mut self, on_run_workflow: T) -> Self
+  where
+    T: Fn(RunWorkflowEvent) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookNoopResult> + Send + 'static,
+  {
+    self.on_run_workflow = Some(async_hook(on_run_workflow));
     self
   }
 
@@ -74,7 +137,16 @@ impl PluginBuilder {
   where
     T: Fn(RunJobEvent) -> HookNoopResult + 'static + Send + Sync,
   {
-    self.on_run_job = Some(Box::new(on_run_job));
+    self.on_run_job = Some(sync_hook(on_run_job));
+    self
+  }
+
+  pub fn on_run_job_async<T, Fut>(mut self, on_run_job: T) -> Self
+  where
+    T: Fn(RunJobEvent) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookNoopResult> + Send + 'static,
+  {
+    self.on_run_job = Some(async_hook(on_run_job));
     self
   }
 
@@ -82,7 +154,16 @@ impl PluginBuilder {
   where
     T: Fn(RunStepEvent) -> HookNoopResult + 'static + Send + Sync,
   {
-    self.on_run_step = Some(Box::new(on_run_step));
+    self.on_run_step = Some(sync_hook(on_run_step));
+    self
+  }
+
+  pub fn on_run_step_async<T, Fut>(mut self, on_run_step: T) -> Self
+  where
+    T: Fn(RunStepEvent) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookNoopResult> + Send + 'static,
+  {
+    self.on_run_step = Some(async_hook(on_run_step));
     self
   }
 
@@ -90,7 +171,17 @@ impl PluginBuilder {
   where
     T: Fn(WorkflowRunResult) -> HookNoopResult + Send + Sync + 'static,
   {
-    self.on_workflow_completed = Some(Box::new(on_workflow_completed));
+    self.on_workflow_completed = Some(sync_hook(on_workflow_completed));
+
+    self
+  }
+
+  pub fn on_workflow_completed_async<T, Fut>(mut self, on_workflow_completed: T) -> Self
+  where
+    T: Fn(WorkflowRunResult) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookNoopResult> + Send + 'static,
+  {
+    self.on_workflow_completed = Some(async_hook(on_workflow_completed));
 
     self
   }
@@ -99,7 +190,17 @@ impl PluginBuilder {
   where
     T: Fn(JobRunResult) -> HookNoopResult + Send + Sync + 'static,
   {
-    self.on_job_completed = Some(Box::new(on_job_completed));
+    self.on_job_completed = Some(sync_hook(on_job_completed));
+
+    self
+  }
+
+  pub fn on_job_completed_async<T, Fut>(mut self, on_job_completed: T) -> Self
+  where
+    T: Fn(JobRunResult) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookNoopResult> + Send + 'static,
+  {
+    self.on_job_completed = Some(async_hook(on_job_completed));
 
     self
   }
@@ -108,7 +209,17 @@ impl PluginBuilder {
   where
     T: Fn(StepRunResult) -> HookNoopResult + Send + Sync + 'static,
   {
-    self.on_step_completed = Some(Box::new(on_step_completed));
+    self.on_step_completed = Some(sync_hook(on_step_completed));
+
+    self
+  }
+
+  pub fn on_step_completed_async<T, Fut>(mut self, on_step_completed: T) -> Self
+  where
+    T: Fn(StepRunResult) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookNoopResult> + Send + 'static,
+  {
+    self.on_step_completed = Some(async_hook(on_step_completed));
 
     self
   }
@@ -117,16 +228,74 @@ impl PluginBuilder {
   where
     T: Fn(UserActionStep) -> HookResolveActionResult + Send + Sync + 'static,
   {
-    self.on_resolve_dynamic_action = Some(Box::new(on_resolve_dynamic_action));
+    self.on_resolve_dynamic_action = Some(sync_hook(on_resolve_dynamic_action));
+
+    self
+  }
+
+  pub fn on_resolve_dynamic_action_async<T, Fut>(mut self, on_resolve_dynamic_action: T) -> Self
+  where
+    T: Fn(UserActionStep) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookResolveActionResult> + Send + 'static,
+  {
+    self.on_resolve_dynamic_action = Some(async_hook(on_resolve_dynamic_action));
 
     self
   }
 
   pub fn on_before_run_step<T>(mut self, on_before_run_step: T) -> Self
   where
-    T: Fn(Step) -> HookBeforeRunStepResult + Send + Sync + 'static,
+    T: Fn(Step) -> HookBeforeRunResult<Step> + Send + Sync + 'static,
   {
-    self.on_before_run_step = Some(Box::new(on_before_run_step));
+    self.on_before_run_step = Some(sync_hook(on_before_run_step));
+
+    self
+  }
+
+  pub fn on_before_run_step_async<T, Fut>(mut self, on_before_run_step: T) -> Self
+  where
+    T: Fn(Step) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookBeforeRunResult<Step>> + Send + 'static,
+  {
+    self.on_before_run_step = Some(async_hook(on_before_run_step));
+
+    self
+  }
+
+  pub fn on_before_run_job<T>(mut self, on_before_run_job: T) -> Self
+  where
+    T: Fn(Job) -> HookBeforeRunResult<Job> + Send + Sync + 'static,
+  {
+    self.on_before_run_job = Some(sync_hook(on_before_run_job));
+
+    self
+  }
+
+  pub fn on_before_run_job_async<T, Fut>(mut self, on_before_run_job: T) -> Self
+  where
+    T: Fn(Job) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookBeforeRunResult<Job>> + Send + 'static,
+  {
+    self.on_before_run_job = Some(async_hook(on_before_run_job));
+
+    self
+  }
+
+  pub fn on_before_run_workflow<T>(mut self, on_before_run_workflow: T) -> Self
+  where
+    T: Fn(Workflow) -> HookBeforeRunResult<Workflow> + Send + Sync + 'static,
+  {
+    self.on_before_run_workflow = Some(sync_hook(on_before_run_workflow));
+
+    self
+  }
+
+  pub fn on_before_run_workflow_async<T, Fut>(mut self, on_before_run_workflow: T) -> Self
+  where
+    T: Fn(Workflow) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = HookBeforeRunResult<Workflow>> + Send + 'static,
+  {
+    self.on_before_run_workflow = Some(async_hook(on_before_run_workflow));
 
     self
   }
@@ -134,6 +303,7 @@ impl PluginBuilder {
   pub fn build(self) -> AstroRunPlugin {
     AstroRunPlugin {
       name: self.name,
+      priority: self.priority,
       on_state_change: self.on_state_change,
       on_log: self.on_log,
       on_run_workflow: self.on_run_workflow,
@@ -144,18 +314,26 @@ impl PluginBuilder {
       on_step_completed: self.on_step_completed,
       on_resolve_dynamic_action: self.on_resolve_dynamic_action,
       on_before_run_step: self.on_before_run_step,
+      on_before_run_job: self.on_before_run_job,
+      on_before_run_workflow: self.on_before_run_workflow,
     }
   }
 }
 
-/// `AstroRunPlugin` enables rapid definition of a synchronous astro-run plugin
+/// `AstroRunPlugin` enables rapid definition of an astro-run plugin
 /// without the need to declare a new struct to implement the `Plugin` trait.
+///
+/// Every hook accepts a synchronous closure, or an async one with the `_async` variant,
+/// such as `on_log_async`.
 pub struct AstroRunPlugin {
   name: &'static str,
+  priority: i32,
   on_resolve_dynamic_action: Option<Box<OnResolveDynamicAction>>,
   on_run_workflow: Option<Box<OnRunWorkflow>>,
   on_run_job: Option<Box<OnRunJob>>,
   on_before_run_step: Option<Box<OnBeforeRunStep>>,
+  on_before_run_job: Option<Box<OnBeforeRunJob>>,
+  on_before_run_workflow: Option<Box<OnBeforeRunWorkflow>>,
   on_run_step: Option<Box<OnRunStep>>,
   on_state_change: Option<Box<OnStateChange>>,
   on_log: Option<Box<OnLog>>,
@@ -176,9 +354,13 @@ impl Plugin for AstroRunPlugin {
     self.name
   }
 
+  fn priority(&self) -> i32 {
+    self.priority
+  }
+
   async fn on_state_change(&self, event: WorkflowStateEvent) -> HookNoopResult {
     if let Some(on_state_change) = &self.on_state_change {
-      on_state_change(event)?;
+      on_state_change(event).await?;
     }
 
     Ok(())
@@ -186,7 +368,7 @@ impl Plugin for AstroRunPlugin {
 
   async fn on_log(&self, log: WorkflowLog) -> HookNoopResult {
     if let Some(on_log) = &self.on_log {
-      on_log(log)?;
+      on_log(log).await?;
     }
 
     Ok(())
@@ -194,7 +376,7 @@ impl Plugin for AstroRunPlugin {
 
   async fn on_run_workflow(&self, event: RunWorkflowEvent) -> HookNoopResult {
     if let Some(on_run_workflow) = &self.on_run_workflow {
-      on_run_workflow(event)?;
+      on_run_workflow(event).await?;
     }
 
     Ok(())
@@ -202,7 +384,7 @@ impl Plugin for AstroRunPlugin {
 
   async fn on_run_job(&self, event: RunJobEvent) -> HookNoopResult {
     if let Some(on_run_job) = &self.on_run_job {
-      on_run_job(event)?;
+      on_run_job(event).await?;
     }
 
     Ok(())
@@ -210,7 +392,7 @@ impl Plugin for AstroRunPlugin {
 
   async fn on_run_step(&self, event: RunStepEvent) -> HookNoopResult {
     if let Some(on_run_step) = &self.on_run_step {
-      on_run_step(event)?;
+      on_run_step(event).await?;
     }
 
     Ok(())
@@ -218,7 +400,7 @@ impl Plugin for AstroRunPlugin {
 
   async fn on_workflow_completed(&self, result: WorkflowRunResult) -> HookNoopResult {
     if let Some(on_workflow_completed) = &self.on_workflow_completed {
-      on_workflow_completed(result)?;
+      on_workflow_completed(result).await?;
     }
 
     Ok(())
@@ -226,7 +408,7 @@ impl Plugin for AstroRunPlugin {
 
   async fn on_job_completed(&self, result: JobRunResult) -> HookNoopResult {
     if let Some(on_job_completed) = &self.on_job_completed {
-      on_job_completed(result)?;
+      on_job_completed(result).await?;
     }
 
     Ok(())
@@ -234,30 +416,36 @@ impl Plugin for AstroRunPlugin {
 
   async fn on_step_completed(&self, result: StepRunResult) -> HookNoopResult {
     if let Some(on_step_completed) = &self.on_step_completed {
-      on_step_completed(result)?;
+      on_step_completed(result).await?;
     }
 
     Ok(())
   }
 
-  async fn on_before_run_step(&self, step: Step) -> HookBeforeRunStepResult {
-    let mut step = step;
+  async fn on_before_run_step(&self, step: Step) -> HookBeforeRunResult<Step> {
+    match &self.on_before_run_step {
+      Some(on_before_run_step) => on_before_run_step(step).await,
+      None => Ok(BeforeRun::Continue(step)),
+    }
+  }
 
-    if let Some(on_before_run_step) = &self.on_before_run_step {
-      match on_before_run_step(step.clone()) {
-        Ok(new_step) => {
-          step = new_step;
-        }
-        Err(err) => return Err(err),
-      }
+  async fn on_before_run_job(&self, job: Job) -> HookBeforeRunResult<Job> {
+    match &self.on_before_run_job {
+      Some(on_before_run_job) => on_before_run_job(job).await,
+      None => Ok(BeforeRun::Continue(job)),
     }
+  }
 
-    Ok(step)
+  async fn on_before_run_workflow(&self, workflow: Workflow) -> HookBeforeRunResult<Workflow> {
+    match &self.on_before_run_workflow {
+      Some(on_before_run_workflow) => on_before_run_workflow(workflow).await,
+      None => Ok(BeforeRun::Continue(workflow)),
+    }
   }
 
   async fn on_resolve_dynamic_action(&self, step: UserActionStep) -> HookResolveActionResult {
     if let Some(on_resolve_dynamic_action) = &self.on_resolve_dynamic_action {
-      return on_resolve_dynamic_action(step);
+      return on_resolve_dynamic_action(step).await;
     }
 
     Ok(None)
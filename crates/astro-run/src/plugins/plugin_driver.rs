@@ -1,28 +1,63 @@
 use crate::{
-  Action, JobRunResult, Plugin, RunJobEvent, RunStepEvent, RunWorkflowEvent, Step, StepRunResult,
-  UserActionStep, WorkflowLog, WorkflowRunResult, WorkflowStateEvent,
+  Action, BeforeRun, Error, Job, JobRunResult, Plugin, Result, RunJobEvent, RunStepEvent,
+  RunWorkflowEvent, Step, StepRunResult, UserActionStep, Workflow, WorkflowId, WorkflowLog,
+  WorkflowRunResult, WorkflowStateEvent,
 };
-use std::sync::Arc;
+use parking_lot::Mutex;
+use std::{collections::HashSet, sync::Arc};
 
 pub type SharedPluginDriver = Arc<PluginDriver>;
 
+/// What happens when a plugin hook returns an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PluginFailurePolicy {
+  /// Logs the error and continues
+  #[default]
+  Continue,
+  /// Fails the step instead of running it when `on_before_run_step` or `on_run_step` fails.
+  /// Errors of the other hooks are logged
+  AbortStep,
+  /// Fails the workflow on any error raised while it runs. A failing `on_before_run_*` or
+  /// `on_run_*` hook stops what it was about to run, errors of the other hooks fail the
+  /// workflow once its jobs complete
+  FailWorkflow,
+}
+
 pub struct PluginDriver {
   pub(crate) plugins: Vec<Box<dyn Plugin>>,
+  failure_policy: PluginFailurePolicy,
+  failed_workflows: Mutex<HashSet<WorkflowId>>,
 }
 
 impl PluginDriver {
   pub fn new(plugins: Vec<Box<dyn Plugin>>) -> Self {
-    PluginDriver { plugins }
+    let mut plugins = plugins;
+    // Stable, so that plugins with the same priority keep their registration order
+    plugins.sort_by_key(|plugin| std::cmp::Reverse(plugin.priority()));
+
+    PluginDriver {
+      plugins,
+      failure_policy: PluginFailurePolicy::default(),
+      failed_workflows: Mutex::new(HashSet::new()),
+    }
+  }
+
+  pub fn failure_policy(mut self, failure_policy: PluginFailurePolicy) -> Self {
+    self.failure_policy = failure_policy;
+
+    self
   }
 
   pub async fn on_state_change(&self, event: WorkflowStateEvent) {
+    let workflow_id = match &event {
+      WorkflowStateEvent::WorkflowStateUpdated { id, .. } => id.clone(),
+      WorkflowStateEvent::JobStateUpdated { id, .. } => id.workflow_id(),
+      WorkflowStateEvent::StepStateUpdated { id, .. } => id.workflow_id(),
+    };
+
     for plugin in &self.plugins {
       if let Err(err) = plugin.on_state_change(event.clone()).await {
-        log::error!(
-          "Plugin {} failed to handle state change: {}",
-          plugin.name(),
-          err
-        );
+        self.handle_error(plugin.as_ref(), "state change", &workflow_id, err);
       }
     }
   }
@@ -30,46 +65,55 @@ impl PluginDriver {
   pub async fn on_log(&self, log: WorkflowLog) {
     for plugin in &self.plugins {
       if let Err(err) = plugin.on_log(log.clone()).await {
-        log::error!("Plugin {} failed to handle log: {}", plugin.name(), err);
+        self.handle_error(plugin.as_ref(), "log", &log.step_id.workflow_id(), err);
       }
     }
   }
 
-  pub async fn on_run_workflow(&self, event: RunWorkflowEvent) {
+  pub async fn on_run_workflow(&self, event: RunWorkflowEvent) -> Result<()> {
     for plugin in &self.plugins {
       if let Err(err) = plugin.on_run_workflow(event.clone()).await {
-        log::error!(
-          "Plugin {} failed to handle run workflow: {}",
-          plugin.name(),
-          err
-        );
+        self.handle_abortable_error(
+          plugin.as_ref(),
+          "run workflow",
+          &event.source.id,
+          err,
+          false,
+        )?;
       }
     }
+
+    Ok(())
   }
 
-  pub async fn on_run_job(&self, event: RunJobEvent) {
+  pub async fn on_run_job(&self, event: RunJobEvent) -> Result<()> {
+    let workflow_id = event.source.id.workflow_id();
+
     for plugin in &self.plugins {
       if let Err(err) = plugin.on_run_job(event.clone()).await {
-        log::error!("Plugin {} failed to handle run job: {}", plugin.name(), err);
+        self.handle_abortable_error(plugin.as_ref(), "run job", &workflow_id, err, false)?;
       }
     }
+
+    Ok(())
   }
 
-  pub async fn on_run_step(&self, event: RunStepEvent) {
+  pub async fn on_run_step(&self, event: RunStepEvent) -> Result<()> {
+    let workflow_id = event.source.id.workflow_id();
+
     for plugin in &self.plugins {
       if let Err(err) = plugin.on_run_step(event.clone()).await {
-        log::error!(
-          "Plugin {} failed to handle run step: {}",
-          plugin.name(),
-          err
-        );
+        self.handle_abortable_error(plugin.as_ref(), "run step", &workflow_id, err, true)?;
       }
     }
+
+    Ok(())
   }
 
   pub async fn on_workflow_completed(&self, result: WorkflowRunResult) {
     for plugin in &self.plugins {
       if let Err(err) = plugin.on_workflow_completed(result.clone()).await {
+        // The workflow can't fail anymore
         log::error!(
           "Plugin {} failed to handle workflow completed: {}",
           plugin.name(),
@@ -82,10 +126,11 @@ impl PluginDriver {
   pub async fn on_job_completed(&self, result: JobRunResult) {
     for plugin in &self.plugins {
       if let Err(err) = plugin.on_job_completed(result.clone()).await {
-        log::error!(
-          "Plugin {} failed to handle job completed: {}",
-          plugin.name(),
-          err
+        self.handle_error(
+          plugin.as_ref(),
+          "job completed",
+          &result.id.workflow_id(),
+          err,
         );
       }
     }
@@ -94,31 +139,83 @@ impl PluginDriver {
   pub async fn on_step_completed(&self, result: StepRunResult) {
     for plugin in &self.plugins {
       if let Err(err) = plugin.on_step_completed(result.clone()).await {
-        log::error!(
-          "Plugin {} failed to handle step completed: {}",
-          plugin.name(),
-          err
+        self.handle_error(
+          plugin.as_ref(),
+          "step completed",
+          &result.id.workflow_id(),
+          err,
         );
       }
     }
   }
 
-  pub async fn on_before_run_step(&self, step: Step) -> Step {
+  pub async fn on_before_run_workflow(&self, workflow: Workflow) -> Result<BeforeRun<Workflow>> {
+    let workflow_id = workflow.id.clone();
+    let mut workflow = workflow;
+
+    for plugin in &self.plugins {
+      match plugin.on_before_run_workflow(workflow.clone()).await {
+        Ok(BeforeRun::Continue(new_workflow)) => workflow = new_workflow,
+        Ok(decision) => return Ok(decision),
+        Err(err) => {
+          self.handle_abortable_error(
+            plugin.as_ref(),
+            "before run workflow",
+            &workflow_id,
+            err,
+            false,
+          )?;
+        }
+      }
+    }
+
+    Ok(BeforeRun::Continue(workflow))
+  }
+
+  pub async fn on_before_run_job(&self, job: Job) -> Result<BeforeRun<Job>> {
+    let workflow_id = job.id.workflow_id();
+    let mut job = job;
+
+    for plugin in &self.plugins {
+      match plugin.on_before_run_job(job.clone()).await {
+        Ok(BeforeRun::Continue(new_job)) => job = new_job,
+        Ok(decision) => return Ok(decision),
+        Err(err) => {
+          self.handle_abortable_error(
+            plugin.as_ref(),
+            "before run job",
+            &workflow_id,
+            err,
+            false,
+          )?;
+        }
+      }
+    }
+
+    Ok(BeforeRun::Continue(job))
+  }
+
+  pub async fn on_before_run_step(&self, step: Step) -> Result<BeforeRun<Step>> {
+    let workflow_id = step.id.workflow_id();
     let mut step = step;
+
     for plugin in &self.plugins {
       match plugin.on_before_run_step(step.clone()).await {
-        Ok(new_step) => step = new_step,
+        Ok(BeforeRun::Continue(new_step)) => step = new_step,
+        Ok(decision) => return Ok(decision),
         Err(err) => {
-          log::error!(
-            "Plugin {} failed to handle before run step: {}",
-            plugin.name(),
-            err
-          );
+          self.handle_abortable_error(
+            plugin.as_ref(),
+            "before run step",
+            &workflow_id,
+            err,
+            true,
+          )?;
         }
       }
     }
 
-    step
+    Ok(BeforeRun::Continue(step))
   }
 
   pub async fn on_resolve_dynamic_action(&self, step: UserActionStep) -> Option<Box<dyn Action>> {
@@ -138,6 +235,52 @@ impl PluginDriver {
 
     None
   }
+
+  /// Returns whether a hook failed while the workflow was running, and forgets about it
+  pub(crate) fn take_workflow_failure(&self, workflow_id: &WorkflowId) -> bool {
+    self.failed_workflows.lock().remove(workflow_id)
+  }
+
+  /// Logs the error, and returns the logged message
+  fn handle_error(
+    &self,
+    plugin: &dyn Plugin,
+    hook: &str,
+    workflow_id: &WorkflowId,
+    err: Error,
+  ) -> String {
+    let message = format!(
+      "Plugin {} failed to handle {}: {}",
+      plugin.name(),
+      hook,
+      err
+    );
+    log::error!("{}", message);
+
+    if self.failure_policy == PluginFailurePolicy::FailWorkflow {
+      self.failed_workflows.lock().insert(workflow_id.clone());
+    }
+
+    message
+  }
+
+  /// Returns an error if the policy stops what the hook was about to run
+  fn handle_abortable_error(
+    &self,
+    plugin: &dyn Plugin,
+    hook: &str,
+    workflow_id: &WorkflowId,
+    err: Error,
+    is_step_hook: bool,
+  ) -> Result<()> {
+    let message = self.handle_error(plugin, hook, workflow_id, err);
+
+    match self.failure_policy {
+      PluginFailurePolicy::Continue => Ok(()),
+      PluginFailurePolicy::AbortStep if !is_step_hook => Ok(()),
+      _ => Err(Error::error(message)),
+    }
+  }
 }
 
 #[cfg(test)]
@@ -181,7 +324,7 @@ mod tests {
         let mut step = step;
         step.run = "Updated".to_string();
 
-        Ok(step)
+        Ok(step.into())
       })
       .build();
 
@@ -190,7 +333,7 @@ mod tests {
         let mut step = step;
         step.name = Some("Updated".to_string());
 
-        Ok(step)
+        Ok(step.into())
       })
       .build();
 
@@ -204,11 +347,17 @@ mod tests {
       Box::new(update_name_plugin),
     ]);
 
-    plugin_driver
+    let Ok(BeforeRun::Continue(step)) = plugin_driver
       .on_before_run_step(Step {
         ..Default::default()
       })
-      .await;
+      .await
+    else {
+      panic!("The step should continue");
+    };
+
+    assert_eq!(step.run, "Updated");
+    assert_eq!(step.name, Some("Updated".to_string()));
   }
 
   #[astro_run_test::test]
@@ -1,6 +1,7 @@
 use crate::{
-  stream::StreamReceiver, Context, HookBeforeRunStepResult, HookNoopResult, JobRunResult,
-  StepRunResult, WorkflowLog, WorkflowLogType, WorkflowRunResult, WorkflowStateEvent,
+  stream::StreamReceiver, BeforeRun, Context, HookBeforeRunStepResult, HookNoopResult,
+  JobRunResult, LogAnnotation, StepRunResult, Time, WorkflowLog, WorkflowLogType,
+  WorkflowRunResult, WorkflowStateEvent,
 };
 use serde::{Deserialize, Serialize};
 pub use tokio_stream::{Stream, StreamExt};
@@ -16,22 +17,61 @@ pub enum RunResult {
 pub struct Log {
   pub log_type: WorkflowLogType,
   pub message: String,
+  #[serde(default)]
+  pub annotation: Option<LogAnnotation>,
+  /// When the line was captured. Set by `StreamSender` when missing
+  #[serde(default)]
+  pub time: Option<Time>,
+  /// Position of the log in the step, starting from 0. Set by `StreamSender` if not provided
+  #[serde(default)]
+  pub sequence: Option<u64>,
 }
 
 impl Log {
-  #[allow(clippy::self_named_constructors)]
-  pub fn log(message: impl Into<String>) -> Self {
+  pub fn new(log_type: WorkflowLogType, message: impl Into<String>) -> Self {
     Self {
-      log_type: WorkflowLogType::Log,
+      log_type,
       message: message.into(),
+      annotation: None,
+      time: None,
+      sequence: None,
     }
   }
 
+  #[allow(clippy::self_named_constructors)]
+  pub fn log(message: impl Into<String>) -> Self {
+    Self::new(WorkflowLogType::Log, message)
+  }
+
   pub fn error(message: impl Into<String>) -> Self {
-    Self {
-      log_type: WorkflowLogType::Error,
-      message: message.into(),
-    }
+    Self::new(WorkflowLogType::Error, message)
+  }
+
+  pub fn warning(message: impl Into<String>) -> Self {
+    Self::new(WorkflowLogType::Warning, message)
+  }
+
+  pub fn debug(message: impl Into<String>) -> Self {
+    Self::new(WorkflowLogType::Debug, message)
+  }
+
+  pub fn group_start(title: impl Into<String>) -> Self {
+    Self::new(WorkflowLogType::GroupStart, title)
+  }
+
+  pub fn group_end() -> Self {
+    Self::new(WorkflowLogType::GroupEnd, "")
+  }
+
+  pub fn annotation(mut self, annotation: LogAnnotation) -> Self {
+    self.annotation = Some(annotation);
+    self
+  }
+
+  /// Sets the capture time, for runners which read the output before sending it
+  pub fn time(mut self, time: Time) -> Self {
+    self.time = Some(time);
+    self
   }
 
   pub fn is_error(&self) -> bool {
@@ -83,7 +123,7 @@ pub trait Runner: Send + Sync {
     Ok(())
   }
   async fn on_before_run_step(&self, step: crate::Step) -> HookBeforeRunStepResult {
-    Ok(step)
+    Ok(BeforeRun::Continue(step))
   }
   async fn on_run_step(&self, _event: crate::RunStepEvent) -> HookNoopResult {
     Ok(())
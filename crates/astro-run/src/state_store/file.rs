@@ -0,0 +1,150 @@
+use super::{StateStore, WorkflowRunRecord};
+use crate::{Error, Result, WorkflowId};
+use std::path::PathBuf;
+
+/// Stores every run as `<dir>/<workflow id>.json`.
+///
+/// Files are replaced atomically, so a crash never leaves a partially written record.
+pub struct FileStateStore {
+  dir: PathBuf,
+}
+
+impl FileStateStore {
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    Self { dir: dir.into() }
+  }
+
+  fn path(&self, id: &WorkflowId) -> Result<PathBuf> {
+    let id = id.inner();
+    let is_valid = !id.is_empty()
+      && id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !is_valid {
+      return Err(Error::error(format!(
+        "Workflow id `{}` can't be used as a file name",
+        id
+      )));
+    }
+
+    Ok(self.dir.join(format!("{}.json", id)))
+  }
+}
+
+#[async_trait::async_trait]
+impl StateStore for FileStateStore {
+  async fn save(&self, record: WorkflowRunRecord) -> Result<()> {
+    let path = self.path(&record.id)?;
+    let content = serde_json::to_vec_pretty(&record)
+      .map_err(|err| Error::error(format!("Failed to serialize workflow run: {}", err)))?;
+
+    tokio::fs::create_dir_all(&self.dir).await?;
+
+    let temp_path = path.with_extension("json.tmp");
+    tokio::fs::write(&temp_path, content).await?;
+    tokio::fs::rename(&temp_path, &path).await?;
+
+    Ok(())
+  }
+
+  async fn get(&self, id: &WorkflowId) -> Result<Option<WorkflowRunRecord>> {
+    let path = self.path(id)?;
+
+    let content = match tokio::fs::read(&path).await {
+      Ok(content) => content,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+      Err(err) => return Err(err.into()),
+    };
+
+    let record = serde_json::from_slice(&content).map_err(|err| {
+      Error::error(format!(
+        "Failed to parse workflow run {}: {}",
+        path.display(),
+        err
+      ))
+    })?;
+
+    Ok(Some(record))
+  }
+
+  async fn list(&self) -> Result<Vec<WorkflowRunRecord>> {
+    let mut entries = match tokio::fs::read_dir(&self.dir).await {
+      Ok(entries) => entries,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+      Err(err) => return Err(err.into()),
+    };
+
+    let mut records: Vec<WorkflowRunRecord> = vec![];
+
+    while let Some(entry) = entries.next_entry().await? {
+      let path = entry.path();
+      if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+        continue;
+      }
+
+      let content = tokio::fs::read(&path).await?;
+      match serde_json::from_slice(&content) {
+        Ok(record) => records.push(record),
+        Err(err) => log::error!("Failed to parse workflow run {}: {}", path.display(), err),
+      }
+    }
+
+    records.sort_by_key(|record| std::cmp::Reverse(record.updated_at));
+
+    Ok(records)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::WorkflowState;
+
+  #[astro_run_test::test]
+  async fn test_file_state_store() {
+    let dir = std::env::temp_dir().join(format!("astro-run-state-{}", uuid::Uuid::new_v4()));
+    let store = FileStateStore::new(&dir);
+
+    assert!(store.list().await.unwrap().is_empty());
+    assert!(store
+      .get(&WorkflowId::new("missing"))
+      .await
+      .unwrap()
+      .is_none());
+
+    let mut first = WorkflowRunRecord::new(WorkflowId::new("first"));
+    store.save(first.clone()).await.unwrap();
+    store
+      .save(WorkflowRunRecord::new(WorkflowId::new("second")))
+      .await
+      .unwrap();
+
+    first.state = WorkflowState::Succeeded;
+    first.updated_at = chrono::Utc::now();
+    store.save(first).await.unwrap();
+
+    let record = store.get(&WorkflowId::new("first")).await.unwrap().unwrap();
+    assert_eq!(record.state, WorkflowState::Succeeded);
+
+    let ids: Vec<_> = store
+      .list()
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|record| record.id.inner())
+      .collect();
+    assert_eq!(ids, vec!["first", "second"]);
+
+    let error = store
+      .save(WorkflowRunRecord::new(WorkflowId::new("../escape")))
+      .await
+      .unwrap_err();
+    assert_eq!(
+      error,
+      Error::error("Workflow id `../escape` can't be used as a file name")
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+}
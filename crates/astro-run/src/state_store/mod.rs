@@ -0,0 +1,107 @@
+mod file;
+mod query;
+mod recorder;
+
+pub use file::*;
+pub use query::*;
+pub(crate) use recorder::*;
+
+use crate::{
+  Id, JobRunResult, Result, Time, TriggerEvent, WorkflowId, WorkflowRunResult, WorkflowState,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+
+/// The persisted state of a workflow run, updated on every state transition
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkflowRunRecord {
+  pub id: WorkflowId,
+  pub name: Option<String>,
+  pub trigger_event: Option<TriggerEvent>,
+  pub state: WorkflowState,
+  pub started_at: Option<Time>,
+  pub completed_at: Option<Time>,
+  pub updated_at: Time,
+  /// Set when the run was found unfinished after a restart, see `AstroRun::recover_interrupted_runs`
+  #[serde(default)]
+  pub interrupted: bool,
+  pub jobs: HashMap<Id, JobRunResult>,
+}
+
+impl WorkflowRunRecord {
+  pub fn new(id: WorkflowId) -> Self {
+    Self {
+      id,
+      name: None,
+      trigger_event: None,
+      state: WorkflowState::Pending,
+      started_at: None,
+      completed_at: None,
+      updated_at: chrono::Utc::now(),
+      interrupted: false,
+      jobs: HashMap::new(),
+    }
+  }
+
+  /// Time between start and completion, `None` until the run completes
+  pub fn duration(&self) -> Option<std::time::Duration> {
+    (self.completed_at? - self.started_at?).to_std().ok()
+  }
+
+  /// Marks the run and all of its unfinished jobs and steps as failed
+  pub fn mark_interrupted(&mut self) {
+    let now = chrono::Utc::now();
+
+    let interrupt = |state: &mut WorkflowState, completed_at: &mut Option<Time>| {
+      if !state.is_terminal() {
+        *state = WorkflowState::Failed;
+        completed_at.get_or_insert(now);
+      }
+    };
+
+    for job in self.jobs.values_mut() {
+      for step in job.steps.iter_mut() {
+        interrupt(&mut step.state, &mut step.completed_at);
+      }
+      interrupt(&mut job.state, &mut job.completed_at);
+    }
+    interrupt(&mut self.state, &mut self.completed_at);
+
+    self.interrupted = true;
+    self.updated_at = now;
+  }
+}
+
+impl From<WorkflowRunRecord> for WorkflowRunResult {
+  fn from(record: WorkflowRunRecord) -> Self {
+    WorkflowRunResult {
+      id: record.id,
+      state: record.state,
+      started_at: record.started_at,
+      completed_at: record.completed_at,
+      summary: WorkflowRunResult::aggregate_summary(&record.jobs),
+      jobs: record.jobs,
+    }
+  }
+}
+
+/// # StateStore
+/// Persists workflow runs, so that the state survives restarts and can be queried as history.
+///
+/// Registered with `AstroRunBuilder::state_store`, which records every workflow, job and step
+/// transition as it happens. `FileStateStore` keeps one JSON file per run.
+#[async_trait::async_trait]
+pub trait StateStore: Send + Sync {
+  /// Inserts or replaces the record with the same id
+  async fn save(&self, record: WorkflowRunRecord) -> Result<()>;
+  async fn get(&self, id: &WorkflowId) -> Result<Option<WorkflowRunRecord>>;
+  /// Returns all the records, most recently updated first
+  async fn list(&self) -> Result<Vec<WorkflowRunRecord>>;
+  /// Filters and paginates the records. Stores with an index should override the default,
+  /// which loads every record
+  async fn query(&self, query: &RunQuery) -> Result<RunPage> {
+    Ok(query.apply(self.list().await?))
+  }
+}
+
+pub type SharedStateStore = Arc<Box<dyn StateStore>>;
@@ -0,0 +1,197 @@
+use super::WorkflowRunRecord;
+use crate::{Time, WorkflowState};
+
+/// Filters and paginates the run history, see `AstroRun::query_runs`.
+///
+/// Runs are ordered by start time, most recent first. Runs which never started
+/// (for example skipped ones) are ordered by their last update.
+#[derive(Debug, Clone, Default)]
+pub struct RunQuery {
+  pub repo_owner: Option<String>,
+  pub repo_name: Option<String>,
+  pub branch: Option<String>,
+  pub event: Option<String>,
+  pub state: Option<WorkflowState>,
+  /// Inclusive lower bound of the start time
+  pub since: Option<Time>,
+  /// Exclusive upper bound of the start time
+  pub until: Option<Time>,
+  pub offset: usize,
+  /// Defaults to 20
+  pub limit: Option<usize>,
+}
+
+/// One page of runs, along with the number of runs matching the query
+#[derive(Debug, Clone)]
+pub struct RunPage {
+  pub runs: Vec<WorkflowRunRecord>,
+  pub total: usize,
+}
+
+const DEFAULT_LIMIT: usize = 20;
+
+impl RunQuery {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn repo(mut self, owner: impl Into<String>, name: impl Into<String>) -> Self {
+    self.repo_owner = Some(owner.into());
+    self.repo_name = Some(name.into());
+    self
+  }
+
+  pub fn branch(mut self, branch: impl Into<String>) -> Self {
+    self.branch = Some(branch.into());
+    self
+  }
+
+  pub fn event(mut self, event: impl Into<String>) -> Self {
+    self.event = Some(event.into());
+    self
+  }
+
+  pub fn state(mut self, state: WorkflowState) -> Self {
+    self.state = Some(state);
+    self
+  }
+
+  pub fn since(mut self, since: Time) -> Self {
+    self.since = Some(since);
+    self
+  }
+
+  pub fn until(mut self, until: Time) -> Self {
+    self.until = Some(until);
+    self
+  }
+
+  pub fn offset(mut self, offset: usize) -> Self {
+    self.offset = offset;
+    self
+  }
+
+  pub fn limit(mut self, limit: usize) -> Self {
+    self.limit = Some(limit);
+    self
+  }
+
+  pub fn is_match(&self, record: &WorkflowRunRecord) -> bool {
+    let event = record.trigger_event.as_ref();
+    // Runs without a trigger event only match queries which don't filter on it
+    let matches_event =
+      |expected: &Option<String>, actual: fn(&crate::TriggerEvent) -> &String| match expected {
+        Some(expected) => event.is_some_and(|event| actual(event) == expected),
+        None => true,
+      };
+
+    if !matches_event(&self.repo_owner, |event| &event.repo_owner)
+      || !matches_event(&self.repo_name, |event| &event.repo_name)
+      || !matches_event(&self.branch, |event| &event.branch)
+      || !matches_event(&self.event, |event| &event.event)
+    {
+      return false;
+    }
+
+    if let Some(state) = &self.state {
+      if record.state != *state {
+        return false;
+      }
+    }
+
+    let time = sort_time(record);
+    if self.since.is_some_and(|since| time < since) {
+      return false;
+    }
+
+    if self.until.is_some_and(|until| time >= until) {
+      return false;
+    }
+
+    true
+  }
+
+  /// Applies the query to all the records
+  pub fn apply(&self, records: Vec<WorkflowRunRecord>) -> RunPage {
+    let mut runs: Vec<_> = records
+      .into_iter()
+      .filter(|record| self.is_match(record))
+      .collect();
+
+    runs.sort_by_key(|record| std::cmp::Reverse(sort_time(record)));
+
+    let total = runs.len();
+    let runs = runs
+      .into_iter()
+      .skip(self.offset)
+      .take(self.limit.unwrap_or(DEFAULT_LIMIT))
+      .collect();
+
+    RunPage { runs, total }
+  }
+}
+
+fn sort_time(record: &WorkflowRunRecord) -> Time {
+  record.started_at.unwrap_or(record.updated_at)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{TriggerEvent, WorkflowId};
+  use chrono::{Duration, Utc};
+
+  fn record(id: &str, branch: &str, state: WorkflowState, minutes_ago: i64) -> WorkflowRunRecord {
+    let mut record = WorkflowRunRecord::new(WorkflowId::new(id));
+    record.state = state;
+    record.started_at = Some(Utc::now() - Duration::minutes(minutes_ago));
+    record.trigger_event = Some(TriggerEvent {
+      branch: branch.to_string(),
+      ..Default::default()
+    });
+
+    record
+  }
+
+  fn ids(page: &RunPage) -> Vec<String> {
+    page.runs.iter().map(|run| run.id.inner()).collect()
+  }
+
+  #[test]
+  fn test_run_query() {
+    let records = vec![
+      record("a", "main", WorkflowState::Succeeded, 30),
+      record("b", "main", WorkflowState::Failed, 20),
+      record("c", "dev", WorkflowState::Succeeded, 10),
+      WorkflowRunRecord::new(WorkflowId::new("d")),
+    ];
+
+    let page = RunQuery::new().apply(records.clone());
+    assert_eq!(ids(&page), vec!["d", "c", "b", "a"]);
+    assert_eq!(page.total, 4);
+
+    let page = RunQuery::new().branch("main").apply(records.clone());
+    assert_eq!(ids(&page), vec!["b", "a"]);
+
+    let page = RunQuery::new()
+      .repo("panghu-huang", "astro-run")
+      .state(WorkflowState::Succeeded)
+      .apply(records.clone());
+    assert_eq!(ids(&page), vec!["c", "a"]);
+
+    let page = RunQuery::new()
+      .event("push")
+      .since(Utc::now() - Duration::minutes(25))
+      .until(Utc::now() - Duration::minutes(5))
+      .apply(records.clone());
+    assert_eq!(ids(&page), vec!["c", "b"]);
+
+    let page = RunQuery::new().offset(1).limit(2).apply(records.clone());
+    assert_eq!(ids(&page), vec!["c", "b"]);
+    assert_eq!(page.total, 4);
+
+    let page = RunQuery::new().repo("other", "repo").apply(records);
+    assert!(page.runs.is_empty());
+    assert_eq!(page.total, 0);
+  }
+}
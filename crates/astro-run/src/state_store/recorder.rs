@@ -0,0 +1,300 @@
+use super::{SharedStateStore, WorkflowRunRecord};
+use crate::{
+  HookNoopResult, JobId, JobRunResult, Plugin, Result, RunWorkflowEvent, StepId, StepRunResult,
+  Time, WorkflowId, WorkflowRunResult, WorkflowState, WorkflowStateEvent,
+};
+use parking_lot::Mutex;
+use std::{collections::HashMap, sync::Arc};
+
+/// Record of a run in progress
+#[derive(Default)]
+struct RunEntry {
+  // The record and the number of updates applied to it, `None` until it's loaded
+  record: Mutex<Option<(WorkflowRunRecord, u64)>>,
+  // Number of updates written to the store, serializes the writes of the run
+  saved: tokio::sync::Mutex<u64>,
+}
+
+/// Built-in plugin writing every transition to the `StateStore`
+pub(crate) struct StateRecorder {
+  store: SharedStateStore,
+  runs: Mutex<HashMap<WorkflowId, Arc<RunEntry>>>,
+}
+
+impl StateRecorder {
+  pub fn new(store: SharedStateStore) -> Self {
+    Self {
+      store,
+      runs: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Applies `f` to the in-memory record, then writes it. Runs are written independently,
+  /// and updates made while a write of the same run is in progress are coalesced into the next one
+  async fn update<F>(&self, id: &WorkflowId, f: F) -> Result<()>
+  where
+    F: FnOnce(&mut WorkflowRunRecord) + Send,
+  {
+    let entry = Arc::clone(self.runs.lock().entry(id.clone()).or_default());
+
+    if entry.record.lock().is_none() {
+      // Loaded by a single task, the run may have been recorded before a restart
+      let _saved = entry.saved.lock().await;
+
+      if entry.record.lock().is_none() {
+        let record = self
+          .store
+          .get(id)
+          .await?
+          .unwrap_or_else(|| WorkflowRunRecord::new(id.clone()));
+
+        *entry.record.lock() = Some((record, 0));
+      }
+    }
+
+    let version = {
+      let mut guard = entry.record.lock();
+      let (record, version) = guard.as_mut().expect("Record is loaded");
+
+      f(record);
+      record.updated_at = chrono::Utc::now();
+      *version += 1;
+
+      *version
+    };
+
+    let mut saved = entry.saved.lock().await;
+    if *saved >= version {
+      // Already written by a later update
+      return Ok(());
+    }
+
+    let (record, version) = entry.record.lock().clone().expect("Record is loaded");
+    self.store.save(record).await?;
+    *saved = version;
+
+    Ok(())
+  }
+
+  /// Forgets a completed run, its record is in the store
+  fn remove(&self, id: &WorkflowId) {
+    self.runs.lock().remove(id);
+  }
+}
+
+fn update_times(
+  state: &WorkflowState,
+  started_at: &mut Option<Time>,
+  completed_at: &mut Option<Time>,
+) {
+  let now = chrono::Utc::now();
+
+  if state.is_in_progress() {
+    started_at.get_or_insert(now);
+  } else if state.is_terminal() {
+    completed_at.get_or_insert(now);
+  }
+}
+
+fn job_entry<'a>(record: &'a mut WorkflowRunRecord, id: &JobId) -> &'a mut JobRunResult {
+  record
+    .jobs
+    .entry(id.job_key())
+    .or_insert_with(|| JobRunResult {
+      id: id.clone(),
+      state: WorkflowState::Pending,
+      started_at: None,
+      completed_at: None,
+      steps: vec![],
+      summary: None,
+    })
+}
+
+fn step_entry<'a>(record: &'a mut WorkflowRunRecord, id: &StepId) -> &'a mut StepRunResult {
+  let job = job_entry(record, &id.job_id());
+
+  let index = match job.steps.iter().position(|step| step.id == *id) {
+    Some(index) => index,
+    None => {
+      job.steps.push(StepRunResult {
+        id: id.clone(),
+        state: WorkflowState::Pending,
+        exit_code: None,
+        started_at: None,
+        completed_at: None,
+        summary: None,
+      });
+      job.steps.sort_by_key(|step| step.id.step_number());
+
+      job.steps.iter().position(|step| step.id == *id).unwrap()
+    }
+  };
+
+  &mut job.steps[index]
+}
+
+#[async_trait::async_trait]
+impl Plugin for StateRecorder {
+  fn name(&self) -> &'static str {
+    "astro-run-state-recorder"
+  }
+
+  // Recorded first, so that other plugins observe the persisted state
+  fn priority(&self) -> i32 {
+    i32::MAX
+  }
+
+  async fn on_run_workflow(&self, event: RunWorkflowEvent) -> HookNoopResult {
+    let workflow = event.source;
+
+    self
+      .update(&workflow.id, |record| {
+        record.name = workflow.name.clone();
+        record.trigger_event = event.trigger_event;
+
+        for job in workflow.jobs.values() {
+          job_entry(record, &job.id);
+
+          for step in &job.steps {
+            step_entry(record, &step.id);
+          }
+        }
+      })
+      .await
+  }
+
+  async fn on_state_change(&self, event: WorkflowStateEvent) -> HookNoopResult {
+    match event {
+      WorkflowStateEvent::WorkflowStateUpdated { id, state } => {
+        self
+          .update(&id, |record| {
+            update_times(&state, &mut record.started_at, &mut record.completed_at);
+            record.state = state;
+          })
+          .await
+      }
+      WorkflowStateEvent::JobStateUpdated { id, state } => {
+        self
+          .update(&id.workflow_id(), |record| {
+            let job = job_entry(record, &id);
+            update_times(&state, &mut job.started_at, &mut job.completed_at);
+            job.state = state;
+          })
+          .await
+      }
+      WorkflowStateEvent::StepStateUpdated { id, state } => {
+        self
+          .update(&id.workflow_id(), |record| {
+            let step = step_entry(record, &id);
+            update_times(&state, &mut step.started_at, &mut step.completed_at);
+            step.state = state;
+          })
+          .await
+      }
+    }
+  }
+
+  async fn on_step_completed(&self, result: StepRunResult) -> HookNoopResult {
+    let id = result.id.clone();
+
+    self
+      .update(&id.workflow_id(), move |record| {
+        *step_entry(record, &id) = result;
+      })
+      .await
+  }
+
+  async fn on_job_completed(&self, result: JobRunResult) -> HookNoopResult {
+    let id = result.id.clone();
+
+    self
+      .update(&id.workflow_id(), move |record| {
+        *job_entry(record, &id) = result;
+      })
+      .await
+  }
+
+  async fn on_workflow_completed(&self, result: WorkflowRunResult) -> HookNoopResult {
+    let id = result.id.clone();
+
+    let res = self
+      .update(&id, move |record| {
+        record.state = result.state;
+        record.started_at = result.started_at;
+        record.completed_at = result.completed_at;
+        record.jobs.extend(result.jobs);
+      })
+      .await;
+    self.remove(&id);
+
+    res
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::StateStore;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  /// Keeps the records in memory and counts the writes, which take a while
+  #[derive(Default)]
+  struct SlowStore {
+    records: Mutex<HashMap<WorkflowId, WorkflowRunRecord>>,
+    saves: AtomicUsize,
+  }
+
+  #[async_trait::async_trait]
+  impl StateStore for Arc<SlowStore> {
+    async fn save(&self, record: WorkflowRunRecord) -> Result<()> {
+      tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+      self.saves.fetch_add(1, Ordering::SeqCst);
+      self.records.lock().insert(record.id.clone(), record);
+
+      Ok(())
+    }
+
+    async fn get(&self, id: &WorkflowId) -> Result<Option<WorkflowRunRecord>> {
+      Ok(self.records.lock().get(id).cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<WorkflowRunRecord>> {
+      Ok(self.records.lock().values().cloned().collect())
+    }
+  }
+
+  #[astro_run_test::test]
+  async fn test_concurrent_updates() {
+    let store = Arc::new(SlowStore::default());
+    let recorder = Arc::new(StateRecorder::new(Arc::new(Box::new(store.clone()))));
+    let id = WorkflowId::new("workflow");
+
+    let handles: Vec<_> = (0..10)
+      .map(|i| {
+        let recorder = Arc::clone(&recorder);
+        let id = JobId::new("workflow", format!("job-{}", i));
+
+        tokio::spawn(async move {
+          recorder
+            .update(&id.workflow_id(), |record| {
+              job_entry(record, &id).state = WorkflowState::Succeeded;
+            })
+            .await
+            .unwrap();
+        })
+      })
+      .collect();
+    for handle in handles {
+      handle.await.unwrap();
+    }
+
+    let record = store.get(&id).await.unwrap().unwrap();
+    assert_eq!(record.jobs.len(), 10);
+    assert!(record
+      .jobs
+      .values()
+      .all(|job| job.state == WorkflowState::Succeeded));
+    // Updates made during a write are written together
+    assert!(store.saves.load(Ordering::SeqCst) < 10);
+  }
+}
@@ -1,29 +1,93 @@
 use super::{Log, RunResult};
 use parking_lot::Mutex;
-use std::{sync::Arc, task::Waker};
+use std::{collections::VecDeque, sync::Arc, task::Waker};
+use tokio::sync::Notify;
 use tokio_stream::Stream;
 
+/// Number of log lines buffered by `stream()`
+pub const DEFAULT_STREAM_CAPACITY: usize = 1024;
+
+/// What happens when a log is sent while the buffer of the stream is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+  /// `StreamSender::send_async` waits until the receiver catches up. The synchronous
+  /// methods, such as `StreamSender::log`, can't wait and drop the log being sent
+  #[default]
+  Backpressure,
+  /// Drops the log being sent
+  DropNewest,
+  /// Drops the oldest buffered log to make room
+  DropOldest,
+}
+
+/// Counters of a stream, see `StreamReceiver::metrics`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StreamMetrics {
+  /// Logs accepted by the stream, including the dropped ones
+  pub sent: u64,
+  /// Logs dropped because the buffer was full
+  pub dropped: u64,
+  /// Logs waiting for the receiver
+  pub buffered: usize,
+  /// Highest number of buffered logs
+  pub peak_buffered: usize,
+}
+
 struct SharedState {
-  logs: Vec<Log>,
+  logs: VecDeque<Log>,
+  capacity: usize,
+  overflow: OverflowPolicy,
+  metrics: StreamMetrics,
+  // Sequence of the next log without one, after the highest forwarded sequence
+  next_sequence: u64,
   result: Option<RunResult>,
+  summary: Option<String>,
   waker: Option<Waker>,
+  receiver_dropped: bool,
+}
+
+impl SharedState {
+  fn is_full(&self) -> bool {
+    self.logs.len() >= self.capacity
+  }
+
+  fn wake(&mut self) {
+    if let Some(waker) = self.waker.take() {
+      waker.wake();
+    }
+  }
+}
+
+struct Shared {
+  state: Mutex<SharedState>,
+  // Notified when the receiver takes a log, for senders waiting on a full buffer
+  space: Notify,
 }
 
 pub struct StreamReceiver {
-  current_index: Mutex<usize>,
-  state: Arc<Mutex<SharedState>>,
+  shared: Arc<Shared>,
 }
 
 impl StreamReceiver {
-  fn new(state: Arc<Mutex<SharedState>>) -> Self {
-    Self {
-      current_index: Mutex::new(0),
-      state,
-    }
+  fn new(shared: Arc<Shared>) -> Self {
+    Self { shared }
   }
 
   pub fn result(&self) -> Option<RunResult> {
-    self.state.lock().result.clone()
+    self.shared.state.lock().result.clone()
+  }
+
+  pub fn summary(&self) -> Option<String> {
+    self.shared.state.lock().summary.clone()
+  }
+
+  pub fn metrics(&self) -> StreamMetrics {
+    let state = self.shared.state.lock();
+
+    StreamMetrics {
+      buffered: state.logs.len(),
+      ..state.metrics
+    }
   }
 }
 
@@ -34,18 +98,11 @@ impl Stream for StreamReceiver {
     self: std::pin::Pin<&mut Self>,
     cx: &mut std::task::Context<'_>,
   ) -> std::task::Poll<Option<Self::Item>> {
-    let mut state = self.state.lock();
-    state.waker = Some(cx.waker().clone());
-
-    let logs = state.logs.clone();
-    let total = logs.len();
-    let current_index = *self.current_index.lock();
-
-    if current_index < total {
-      let log = logs[current_index].clone();
-      *self.current_index.lock() += 1;
+    let mut state = self.shared.state.lock();
 
-      cx.waker().wake_by_ref();
+    if let Some(log) = state.logs.pop_front() {
+      drop(state);
+      self.shared.space.notify_waiters();
 
       return std::task::Poll::Ready(Some(log));
     }
@@ -54,36 +111,122 @@ impl Stream for StreamReceiver {
       return std::task::Poll::Ready(None);
     }
 
+    state.waker = Some(cx.waker().clone());
+
     std::task::Poll::Pending
   }
 }
 
+impl Drop for StreamReceiver {
+  fn drop(&mut self) {
+    let mut state = self.shared.state.lock();
+    state.receiver_dropped = true;
+    state.logs.clear();
+    drop(state);
+
+    // Nobody will read the logs anymore, release the waiting senders
+    self.shared.space.notify_waiters();
+  }
+}
+
 #[derive(Clone)]
 pub struct StreamSender {
-  state: Arc<Mutex<SharedState>>,
+  shared: Arc<Shared>,
 }
 
 impl StreamSender {
-  fn new(state: Arc<Mutex<SharedState>>) -> Self {
-    Self { state }
+  fn new(shared: Arc<Shared>) -> Self {
+    Self { shared }
   }
 
+  /// Never waits, the log is dropped if the buffer is full. Use `send_async` to wait for the
+  /// receiver under `OverflowPolicy::Backpressure`
   pub fn log(&self, message: impl Into<String>) {
-    let mut state = self.state.lock();
-    state.logs.push(Log::log(message.into()));
+    self.send(Log::log(message.into()));
+  }
 
-    if let Some(waker) = state.waker.take() {
-      waker.wake();
+  /// Never waits, like `log`
+  pub fn error(&self, message: impl Into<String>) {
+    self.send(Log::error(message.into()));
+  }
+
+  /// Never waits, like `log`
+  pub fn warning(&self, message: impl Into<String>) {
+    self.send(Log::warning(message.into()));
+  }
+
+  /// Sends a log of any type, for example a group or an annotated warning.
+  ///
+  /// Never waits, a full buffer is handled by the `OverflowPolicy` of the stream.
+  /// `OverflowPolicy::Backpressure` drops the log like `OverflowPolicy::DropNewest`, only
+  /// `send_async` waits for the receiver.
+  pub fn send(&self, log: Log) {
+    let mut log = log;
+    log.time.get_or_insert_with(chrono::Utc::now);
+
+    Self::push(&mut self.shared.state.lock(), log);
+  }
+
+  /// Sends a log, waiting for the receiver to catch up while the buffer is full
+  /// if the stream applies `OverflowPolicy::Backpressure`
+  pub async fn send_async(&self, log: Log) {
+    // Captured before waiting
+    let mut log = log;
+    log.time.get_or_insert_with(chrono::Utc::now);
+
+    loop {
+      // Created before checking, so that no notification is missed
+      let space = self.shared.space.notified();
+
+      {
+        // Pushed under the same lock, so that another sender can't fill the buffer in between
+        let mut state = self.shared.state.lock();
+        if state.overflow != OverflowPolicy::Backpressure
+          || !state.is_full()
+          || state.receiver_dropped
+        {
+          Self::push(&mut state, log);
+          return;
+        }
+      }
+
+      space.await;
     }
   }
 
-  pub fn error(&self, message: impl Into<String>) {
-    let mut state = self.state.lock();
-    state.logs.push(Log::error(message.into()));
+  fn push(state: &mut SharedState, log: Log) {
+    let mut log = log;
+    // Dropped logs keep their number, so that gaps show up. Logs forwarded from another
+    // stream, such as a remote runner, keep the sequence they were given there
+    let sequence = *log.sequence.get_or_insert(state.next_sequence);
+    state.next_sequence = state.next_sequence.max(sequence + 1);
+    state.metrics.sent += 1;
 
-    if let Some(waker) = state.waker.take() {
-      waker.wake();
+    if state.receiver_dropped {
+      return;
     }
+
+    if state.is_full() {
+      match state.overflow {
+        OverflowPolicy::Backpressure | OverflowPolicy::DropNewest => {
+          state.metrics.dropped += 1;
+          return;
+        }
+        OverflowPolicy::DropOldest => {
+          state.logs.pop_front();
+          state.metrics.dropped += 1;
+        }
+      }
+    }
+
+    state.logs.push_back(log);
+    state.metrics.peak_buffered = state.metrics.peak_buffered.max(state.logs.len());
+    state.wake();
+  }
+
+  /// Sets the markdown summary of the step. Must be called before `end`
+  pub fn summary(&self, summary: impl Into<String>) {
+    self.shared.state.lock().summary = Some(summary.into());
   }
 
   pub fn succeeded(&self) {
@@ -104,33 +247,55 @@ impl StreamSender {
   }
 
   pub fn end(&self, result: RunResult) {
-    if self.is_ended() {
+    let mut state = self.shared.state.lock();
+
+    if state.result.is_some() {
       log::trace!("StreamSender: already ended");
       return;
     }
 
-    let mut state = self.state.lock();
     state.result = Some(result);
-
-    if let Some(waker) = state.waker.take() {
-      waker.wake();
-    }
+    state.wake();
   }
 
   pub fn is_ended(&self) -> bool {
-    self.state.lock().result.is_some()
+    self.shared.state.lock().result.is_some()
+  }
+
+  pub fn metrics(&self) -> StreamMetrics {
+    let state = self.shared.state.lock();
+
+    StreamMetrics {
+      buffered: state.logs.len(),
+      ..state.metrics
+    }
   }
 }
 
+/// Creates a stream buffering up to `DEFAULT_STREAM_CAPACITY` logs with `OverflowPolicy::Backpressure`
 pub fn stream() -> (StreamSender, StreamReceiver) {
-  let state = Arc::new(Mutex::new(SharedState {
-    logs: Vec::new(),
-    waker: None,
-    result: None,
-  }));
+  bounded_stream(DEFAULT_STREAM_CAPACITY, OverflowPolicy::default())
+}
 
-  let sender = StreamSender::new(state.clone());
-  let receiver = StreamReceiver::new(state);
+/// Creates a stream buffering up to `capacity` logs
+pub fn bounded_stream(capacity: usize, overflow: OverflowPolicy) -> (StreamSender, StreamReceiver) {
+  let shared = Arc::new(Shared {
+    state: Mutex::new(SharedState {
+      logs: VecDeque::new(),
+      capacity: capacity.max(1),
+      overflow,
+      metrics: StreamMetrics::default(),
+      next_sequence: 0,
+      waker: None,
+      result: None,
+      summary: None,
+      receiver_dropped: false,
+    }),
+    space: Notify::new(),
+  });
+
+  let sender = StreamSender::new(shared.clone());
+  let receiver = StreamReceiver::new(shared);
 
   (sender, receiver)
 }
@@ -138,8 +303,13 @@ pub fn stream() -> (StreamSender, StreamReceiver) {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::WorkflowLogType;
   use tokio_stream::StreamExt;
 
+  fn messages(logs: &[Log]) -> Vec<&str> {
+    logs.iter().map(|log| log.message.as_str()).collect()
+  }
+
   #[tokio::test]
   async fn test_stream() {
     let (sender, mut receiver) = stream();
@@ -153,10 +323,31 @@ mod tests {
       logs.push(log);
     }
 
-    assert_eq!(logs, vec![Log::log("test"), Log::error("error"),]);
+    assert_eq!(messages(&logs), vec!["test", "error"]);
+    assert_eq!(logs[0].log_type, WorkflowLogType::Log);
+    assert_eq!(logs[1].log_type, WorkflowLogType::Error);
+    assert_eq!(logs[0].sequence, Some(0));
+    assert_eq!(logs[1].sequence, Some(1));
+    assert!(logs[0].time.unwrap() <= logs[1].time.unwrap());
     assert_eq!(receiver.result().unwrap(), RunResult::Succeeded);
   }
 
+  #[tokio::test]
+  async fn test_forwarded_sequence() {
+    let (sender, receiver) = stream();
+
+    let mut forwarded = Log::log("forwarded");
+    forwarded.sequence = Some(5);
+    sender.log("first");
+    sender.send(forwarded);
+    sender.log("last");
+    sender.succeeded();
+
+    let logs: Vec<_> = receiver.collect().await;
+    let sequences: Vec<_> = logs.iter().map(|log| log.sequence).collect();
+    assert_eq!(sequences, vec![Some(0), Some(5), Some(6)]);
+  }
+
   #[tokio::test]
   async fn test_stream_twice() {
     let (sender, receiver) = stream();
@@ -165,4 +356,93 @@ mod tests {
     sender.cancelled();
     assert_eq!(receiver.result().unwrap(), RunResult::Succeeded);
   }
+
+  #[tokio::test]
+  async fn test_drop_policies() {
+    let (sender, receiver) = bounded_stream(2, OverflowPolicy::DropNewest);
+    for i in 0..5 {
+      sender.log(i.to_string());
+    }
+    sender.succeeded();
+
+    let metrics = receiver.metrics();
+    assert_eq!(metrics.sent, 5);
+    assert_eq!(metrics.dropped, 3);
+    assert_eq!(metrics.buffered, 2);
+    let logs: Vec<_> = receiver.collect().await;
+    assert_eq!(messages(&logs), vec!["0", "1"]);
+
+    let (sender, receiver) = bounded_stream(2, OverflowPolicy::DropOldest);
+    for i in 0..5 {
+      sender.log(i.to_string());
+    }
+    sender.succeeded();
+
+    assert_eq!(sender.metrics().dropped, 3);
+    let logs: Vec<_> = receiver.collect().await;
+    assert_eq!(messages(&logs), vec!["3", "4"]);
+    // The gap shows the dropped logs
+    assert_eq!(logs[0].sequence, Some(3));
+  }
+
+  #[tokio::test]
+  async fn test_backpressure() {
+    let (sender, mut receiver) = bounded_stream(2, OverflowPolicy::Backpressure);
+
+    let handle = tokio::spawn({
+      let sender = sender.clone();
+
+      async move {
+        for i in 0..100 {
+          sender.send_async(Log::log(i.to_string())).await;
+        }
+        sender.succeeded();
+      }
+    });
+
+    let mut logs = Vec::new();
+    while let Some(log) = receiver.next().await {
+      logs.push(log);
+    }
+    handle.await.unwrap();
+
+    assert_eq!(logs.len(), 100);
+    assert_eq!(logs[99].message, "99");
+    assert_eq!(logs[99].sequence, Some(99));
+
+    let metrics = receiver.metrics();
+    assert_eq!(metrics.dropped, 0);
+    assert!(metrics.peak_buffered <= 2);
+  }
+
+  #[tokio::test]
+  async fn test_backpressure_sync_send() {
+    let (sender, receiver) = bounded_stream(2, OverflowPolicy::Backpressure);
+
+    // The synchronous methods can't wait, so they drop instead of exceeding the capacity
+    for i in 0..5 {
+      sender.log(i.to_string());
+    }
+    sender.succeeded();
+
+    let metrics = receiver.metrics();
+    assert_eq!(metrics.sent, 5);
+    assert_eq!(metrics.dropped, 3);
+    assert_eq!(metrics.buffered, 2);
+    assert_eq!(metrics.peak_buffered, 2);
+    let logs: Vec<_> = receiver.collect().await;
+    assert_eq!(messages(&logs), vec!["0", "1"]);
+  }
+
+  #[tokio::test]
+  async fn test_receiver_dropped() {
+    let (sender, receiver) = bounded_stream(1, OverflowPolicy::Backpressure);
+
+    sender.log("buffered");
+    drop(receiver);
+
+    // Doesn't wait for a receiver which is gone
+    sender.send_async(Log::log("discarded")).await;
+    sender.succeeded();
+  }
 }
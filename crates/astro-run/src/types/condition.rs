@@ -1,10 +1,45 @@
-use serde::{Deserialize, Serialize};
+use crate::{Error, EventMatcherDriver, TriggerEvent, WorkflowDispatchCondition};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct ConditionPayload {
   pub event: String,
   pub branch: String,
   pub paths: Vec<String>,
+  /// The event which triggered the run, used to resolve event filters
+  pub trigger_event: Option<TriggerEvent>,
+}
+
+impl ConditionPayload {
+  /// Resolves a filter key against the trigger event.
+  ///
+  /// Top-level `TriggerEvent` fields (such as `repo_owner` or `ref_name`) are looked up first,
+  /// then the key is resolved in `TriggerEvent::payload`, where `.` separates nested keys.
+  pub fn field(&self, key: &str) -> Option<String> {
+    resolve_field(&self.trigger_event_value()?, key)
+  }
+
+  /// The trigger event as JSON, serialized once to resolve several keys with `resolve_field`
+  fn trigger_event_value(&self) -> Option<serde_json::Value> {
+    serde_json::to_value(self.trigger_event.as_ref()?).ok()
+  }
+}
+
+/// See `ConditionPayload::field`
+fn resolve_field(event: &serde_json::Value, key: &str) -> Option<String> {
+  if key != "payload" {
+    if let Some(value) = event.get(key).and_then(value_to_string) {
+      return Some(value);
+    }
+  }
+
+  let mut value = event.get("payload")?;
+  for segment in key.split('.') {
+    value = value.get(segment)?;
+  }
+
+  value_to_string(value)
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -22,10 +57,53 @@ pub struct PullRequestCondition {
   pub paths: Option<Vec<String>>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+/// A cron schedule declared in `on.schedule`.
+///
+/// ```yaml
+/// on:
+///   schedule:
+///     - cron: "0 2 * * *"
+///       timezone: Asia/Shanghai
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScheduleCondition {
+  /// Cron expression, either 5 fields (`min hour day month weekday`) or 6 fields with seconds
+  pub cron: String,
+  /// IANA time zone name. Defaults to UTC
+  pub timezone: Option<String>,
+}
+
+/// Condition of an event without a dedicated config, such as `release`
+/// or a custom event name.
+///
+/// ```yaml
+/// on:
+///   deploy_request:
+///     branches: [main]
+///     repo_owner: [panghu-huang]
+///     environment: [production, staging]
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct EventCondition {
+  pub branches: Option<Vec<String>>,
+  /// Only `push` and `pull_request` have changed files, so `paths` is rejected
+  /// when the workflow is parsed
+  pub paths: Option<Vec<String>>,
+  /// Glob patterns keyed by a `TriggerEvent` field or a payload key
+  #[serde(flatten)]
+  pub filters: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Default)]
 pub struct ConditionConfig {
   pub push: Option<PushCondition>,
   pub pull_request: Option<PullRequestCondition>,
+  pub schedule: Option<Vec<ScheduleCondition>>,
+  #[serde(default, deserialize_with = "deserialize_present")]
+  pub workflow_dispatch: Option<WorkflowDispatchCondition>,
+  /// Conditions of all the other events, keyed by event name
+  #[serde(flatten, deserialize_with = "deserialize_event_conditions")]
+  pub events: HashMap<String, EventCondition>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -73,8 +151,85 @@ impl PullRequestCondition {
   }
 }
 
+impl ScheduleCondition {
+  /// Scheduled runs carry the cron expression which fired them in the `schedule` payload key
+  pub fn is_match(&self, payload: &ConditionPayload) -> bool {
+    match payload.field("schedule") {
+      Some(cron) => cron == self.cron,
+      None => true,
+    }
+  }
+}
+
+impl EventCondition {
+  pub fn is_match(&self, payload: &ConditionPayload) -> bool {
+    if let Some(branches) = &self.branches {
+      if !is_match_patterns(&vec![payload.branch.clone()], branches) {
+        return false;
+      }
+    }
+
+    if self.filters.is_empty() {
+      return true;
+    }
+
+    let event = payload.trigger_event_value();
+    for (key, patterns) in &self.filters {
+      let Some(value) = event.as_ref().and_then(|event| resolve_field(event, key)) else {
+        log::trace!("Filter `{}` is not found in trigger event", key);
+        return false;
+      };
+
+      if !is_match_patterns(&vec![value], patterns) {
+        return false;
+      }
+    }
+
+    true
+  }
+}
+
 impl Condition {
+  /// Rejects filters which can never match
+  pub fn validate(&self) -> crate::Result<()> {
+    if let Condition::Config(config) = self {
+      if let Some(workflow_dispatch) = &config.workflow_dispatch {
+        workflow_dispatch.validate()?;
+      }
+
+      for (event, condition) in &config.events {
+        if condition.paths.is_some() {
+          return Err(Error::workflow_config_error(format!(
+            "Event `{}` has no changed files, `paths` is only supported by `push` and `pull_request`",
+            event
+          )));
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Whether the condition listens to `event` at all, regardless of its filters
+  pub fn has_event(&self, event: &str) -> bool {
+    match self {
+      Condition::Event(events) => events.iter().any(|e| e == event),
+      Condition::Config(config) => match event {
+        "push" => config.push.is_some(),
+        "pull_request" => config.pull_request.is_some(),
+        "schedule" => config.schedule.is_some(),
+        "workflow_dispatch" => config.workflow_dispatch.is_some(),
+        event => config.events.contains_key(event),
+      },
+    }
+  }
+
   pub fn is_match(&self, payload: &ConditionPayload) -> bool {
+    self.is_match_with(payload, &EventMatcherDriver::default())
+  }
+
+  /// Matches the condition, resolving custom events through the given event matchers
+  pub fn is_match_with(&self, payload: &ConditionPayload, matchers: &EventMatcherDriver) -> bool {
     log::trace!("Matching condition {:#?} with payload {:#?}", self, payload);
     match self {
       Condition::Event(events) => events.contains(&payload.event),
@@ -93,12 +248,63 @@ impl Condition {
             false
           }
         }
-        _ => false,
+        "schedule" => {
+          if let Some(schedules) = &config.schedule {
+            schedules.iter().any(|schedule| schedule.is_match(payload))
+          } else {
+            false
+          }
+        }
+        "workflow_dispatch" => config.workflow_dispatch.is_some(),
+        event => {
+          if let Some(condition) = config.events.get(event) {
+            matchers.is_match(event, condition, payload)
+          } else {
+            false
+          }
+        }
       },
     }
   }
 }
 
+/// Allows events without any filter to be declared as `release:`
+fn deserialize_event_conditions<'de, D>(
+  deserializer: D,
+) -> Result<HashMap<String, EventCondition>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  let events: HashMap<String, Option<EventCondition>> = HashMap::deserialize(deserializer)?;
+
+  Ok(
+    events
+      .into_iter()
+      .map(|(event, condition)| (event, condition.unwrap_or_default()))
+      .collect(),
+  )
+}
+
+/// Treats a present but empty key (`workflow_dispatch:`) as the default config
+fn deserialize_present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: Deserialize<'de> + Default,
+{
+  let value: Option<T> = Option::deserialize(deserializer)?;
+
+  Ok(Some(value.unwrap_or_default()))
+}
+
+fn value_to_string(value: &serde_json::Value) -> Option<String> {
+  match value {
+    serde_json::Value::String(s) => Some(s.clone()),
+    serde_json::Value::Number(n) => Some(n.to_string()),
+    serde_json::Value::Bool(b) => Some(b.to_string()),
+    _ => None,
+  }
+}
+
 fn is_match_patterns(values: &Vec<String>, patterns: &Vec<String>) -> bool {
   for value in values {
     for pattern in patterns {
@@ -218,6 +424,7 @@ mod tests {
       event: "pull_request".to_string(),
       branch: "master".to_string(),
       paths: vec!["src/main.rs".to_string()],
+      ..Default::default()
     };
 
     assert!(condition.is_match(&payload));
@@ -226,6 +433,7 @@ mod tests {
       event: "pull_request".to_string(),
       branch: "main".to_string(),
       paths: vec!["src/main.rs".to_string()],
+      ..Default::default()
     };
 
     assert!(!condition.is_match(&payload));
@@ -242,6 +450,7 @@ mod tests {
       event: "push".to_string(),
       branch: "master".to_string(),
       paths: vec!["src/main.rs".to_string()],
+      ..Default::default()
     };
 
     assert!(condition.is_match(&payload));
@@ -258,12 +467,14 @@ mod tests {
         branches: Some(vec!["master".to_string()]),
         paths: Some(vec!["src/main.rs".to_string()]),
       }),
+      ..Default::default()
     });
 
     let payload = ConditionPayload {
       event: "push".to_string(),
       branch: "master".to_string(),
       paths: vec!["src/main.rs".to_string()],
+      ..Default::default()
     };
 
     assert!(condition.is_match(&payload));
@@ -278,6 +489,7 @@ mod tests {
       event: "push".to_string(),
       branch: "master".to_string(),
       paths: vec!["src/main.rs".to_string()],
+      ..Default::default()
     };
 
     assert!(push.is_match(&payload));
@@ -292,6 +504,7 @@ mod tests {
       event: "invalid".to_string(),
       branch: "".to_string(),
       paths: vec![],
+      ..Default::default()
     };
 
     assert!(!pull_request.is_match(&payload));
@@ -305,14 +518,78 @@ mod tests {
         paths: Some(vec!["src/main.rs".to_string()]),
       }),
       pull_request: None,
+      ..Default::default()
     });
 
     let payload = ConditionPayload {
       event: "invalid".to_string(),
       branch: "master".to_string(),
       paths: vec!["src/main.rs".to_string()],
+      ..Default::default()
+    };
+
+    assert!(!condition.is_match(&payload));
+  }
+
+  #[test]
+  fn test_schedule_condition() {
+    let condition: Condition = serde_yaml::from_str(
+      r#"
+schedule:
+  - cron: "0 2 * * *"
+  - cron: "0 12 * * 1"
+    timezone: Asia/Shanghai
+"#,
+    )
+    .unwrap();
+
+    let payload = |cron: &str| ConditionPayload {
+      event: "schedule".to_string(),
+      trigger_event: Some(crate::TriggerEvent {
+        event: "schedule".to_string(),
+        payload: Some(serde_json::json!({ "schedule": cron })),
+        ..Default::default()
+      }),
+      ..Default::default()
+    };
+
+    assert!(condition.is_match(&payload("0 2 * * *")));
+    assert!(condition.is_match(&payload("0 12 * * 1")));
+    assert!(!condition.is_match(&payload("0 3 * * *")));
+
+    let push = Condition::Config(ConditionConfig {
+      push: Some(PushCondition {
+        branches: None,
+        paths: None,
+      }),
+      ..Default::default()
+    });
+
+    assert!(!push.is_match(&payload("0 2 * * *")));
+  }
+
+  #[test]
+  fn test_workflow_dispatch_condition() {
+    let payload = ConditionPayload {
+      event: "workflow_dispatch".to_string(),
+      ..Default::default()
     };
 
+    let condition: Condition = serde_yaml::from_str("workflow_dispatch:").unwrap();
+    assert!(condition.is_match(&payload));
+
+    let condition: Condition = serde_yaml::from_str(
+      r#"
+workflow_dispatch:
+  inputs:
+    version:
+      required: true
+"#,
+    )
+    .unwrap();
+    assert!(condition.is_match(&payload));
+
+    let condition: Condition = serde_yaml::from_str("push:").unwrap();
     assert!(!condition.is_match(&payload));
   }
 
@@ -5,6 +5,7 @@ mod error;
 mod id;
 mod results;
 mod trigger_event;
+mod workflow_dispatch;
 mod workflow_state;
 mod workflow_state_event;
 
@@ -15,6 +16,7 @@ pub use error::*;
 pub use id::*;
 pub use results::*;
 pub use trigger_event::*;
+pub use workflow_dispatch::*;
 pub use workflow_state::*;
 pub use workflow_state_event::*;
 
@@ -37,6 +39,12 @@ impl GithubAuthorization {
 pub enum WorkflowLogType {
   Error,
   Log,
+  Warning,
+  Debug,
+  /// Starts a collapsible section, the message is its title
+  GroupStart,
+  /// Ends the current collapsible section
+  GroupEnd,
 }
 
 impl std::fmt::Display for WorkflowLogType {
@@ -44,6 +52,10 @@ impl std::fmt::Display for WorkflowLogType {
     match self {
       WorkflowLogType::Error => write!(f, "error"),
       WorkflowLogType::Log => write!(f, "log"),
+      WorkflowLogType::Warning => write!(f, "warning"),
+      WorkflowLogType::Debug => write!(f, "debug"),
+      WorkflowLogType::GroupStart => write!(f, "group_start"),
+      WorkflowLogType::GroupEnd => write!(f, "group_end"),
     }
   }
 }
@@ -53,17 +65,38 @@ impl From<String> for WorkflowLogType {
     match s.as_str() {
       "error" => WorkflowLogType::Error,
       "log" => WorkflowLogType::Log,
+      "warning" => WorkflowLogType::Warning,
+      "debug" => WorkflowLogType::Debug,
+      "group_start" => WorkflowLogType::GroupStart,
+      "group_end" => WorkflowLogType::GroupEnd,
       _ => WorkflowLogType::Log,
     }
   }
 }
 
+/// Source location attached to a warning or an error, for example by
+/// `::warning file=src/main.rs,line=3::message`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct LogAnnotation {
+  pub file: Option<String>,
+  pub line: Option<u32>,
+  pub end_line: Option<u32>,
+  pub column: Option<u32>,
+  pub end_column: Option<u32>,
+  pub title: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WorkflowLog {
   pub step_id: StepId,
   pub log_type: WorkflowLogType,
   pub message: String,
   pub time: chrono::DateTime<chrono::Utc>,
+  #[serde(default)]
+  pub annotation: Option<LogAnnotation>,
+  /// Position of the log in the step. Orders logs which share the same time
+  #[serde(default)]
+  pub sequence: u64,
 }
 
 impl Default for WorkflowLog {
@@ -73,6 +106,8 @@ impl Default for WorkflowLog {
       log_type: WorkflowLogType::Log,
       message: "".to_string(),
       time: chrono::Utc::now(),
+      annotation: None,
+      sequence: 0,
     }
   }
 }
@@ -97,6 +132,10 @@ mod tests {
       WorkflowLogType::Log,
       WorkflowLogType::from("log".to_string())
     );
+    assert_eq!(
+      WorkflowLogType::GroupStart,
+      WorkflowLogType::from(WorkflowLogType::GroupStart.to_string())
+    );
     assert_eq!(
       WorkflowLogType::Log,
       WorkflowLogType::from("unknown".to_string())
@@ -107,6 +146,8 @@ mod tests {
   fn test_workflow_log_type_to_string() {
     assert_eq!("error", WorkflowLogType::Error.to_string());
     assert_eq!("log", WorkflowLogType::Log.to_string());
+    assert_eq!("warning", WorkflowLogType::Warning.to_string());
+    assert_eq!("group_end", WorkflowLogType::GroupEnd.to_string());
   }
 
   #[test]
@@ -4,6 +4,9 @@ use std::collections::HashMap;
 
 pub type Time = chrono::DateTime<chrono::Utc>;
 
+/// Environment variable holding the path of the file a step writes its markdown summary to
+pub const STEP_SUMMARY_ENV: &str = "ASTRO_RUN_STEP_SUMMARY";
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StepRunResult {
   pub id: StepId,
@@ -11,6 +14,9 @@ pub struct StepRunResult {
   pub exit_code: Option<i32>,
   pub started_at: Option<Time>,
   pub completed_at: Option<Time>,
+  /// Markdown written by the step to the file at `ASTRO_RUN_STEP_SUMMARY`
+  #[serde(default)]
+  pub summary: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -20,6 +26,9 @@ pub struct JobRunResult {
   pub started_at: Option<Time>,
   pub completed_at: Option<Time>,
   pub steps: Vec<StepRunResult>,
+  /// Summaries of the steps, in order
+  #[serde(default)]
+  pub summary: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -29,4 +38,102 @@ pub struct WorkflowRunResult {
   pub started_at: Option<Time>,
   pub completed_at: Option<Time>,
   pub jobs: HashMap<Id, JobRunResult>,
+  /// Summaries of the jobs, ordered by job key
+  #[serde(default)]
+  pub summary: Option<String>,
+}
+
+fn duration(started_at: Option<Time>, completed_at: Option<Time>) -> Option<std::time::Duration> {
+  (completed_at? - started_at?).to_std().ok()
+}
+
+/// Joins the non-empty summaries with blank lines
+pub(crate) fn join_summaries<'a>(
+  summaries: impl Iterator<Item = &'a Option<String>>,
+) -> Option<String> {
+  let summaries: Vec<&str> = summaries
+    .flatten()
+    .map(|summary| summary.trim())
+    .filter(|summary| !summary.is_empty())
+    .collect();
+
+  (!summaries.is_empty()).then(|| summaries.join("\n\n"))
+}
+
+impl StepRunResult {
+  /// Time between start and completion, `None` until the step completes
+  pub fn duration(&self) -> Option<std::time::Duration> {
+    duration(self.started_at, self.completed_at)
+  }
+}
+
+impl JobRunResult {
+  pub(crate) fn aggregate_summary(steps: &[StepRunResult]) -> Option<String> {
+    join_summaries(steps.iter().map(|step| &step.summary))
+  }
+
+  /// Time between start and completion, `None` until the job completes
+  pub fn duration(&self) -> Option<std::time::Duration> {
+    duration(self.started_at, self.completed_at)
+  }
+}
+
+impl WorkflowRunResult {
+  pub(crate) fn aggregate_summary(jobs: &HashMap<Id, JobRunResult>) -> Option<String> {
+    let mut keys: Vec<_> = jobs.keys().collect();
+    keys.sort();
+
+    join_summaries(keys.into_iter().map(|key| &jobs[key].summary))
+  }
+
+  /// Time between start and completion, `None` until the workflow completes
+  pub fn duration(&self) -> Option<std::time::Duration> {
+    duration(self.started_at, self.completed_at)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_aggregate_summary() {
+    let step = |index: usize, summary: Option<&str>| StepRunResult {
+      id: StepId::new("workflow", "job", index),
+      state: WorkflowState::Succeeded,
+      exit_code: None,
+      started_at: None,
+      completed_at: None,
+      summary: summary.map(|summary| summary.to_string()),
+    };
+
+    let steps = vec![
+      step(0, Some("## Tests\n")),
+      step(1, None),
+      step(2, Some("  ")),
+      step(3, Some("| Benchmark | Time |")),
+    ];
+    assert_eq!(
+      JobRunResult::aggregate_summary(&steps),
+      Some("## Tests\n\n| Benchmark | Time |".to_string())
+    );
+    assert_eq!(JobRunResult::aggregate_summary(&steps[1..3]), None);
+
+    let job = |key: &str, summary: Option<&str>| JobRunResult {
+      id: JobId::new("workflow", key),
+      state: WorkflowState::Succeeded,
+      started_at: None,
+      completed_at: None,
+      steps: vec![],
+      summary: summary.map(|summary| summary.to_string()),
+    };
+    let jobs = HashMap::from([
+      ("test".to_string(), job("test", Some("Test"))),
+      ("bench".to_string(), job("bench", Some("Bench"))),
+    ]);
+    assert_eq!(
+      WorkflowRunResult::aggregate_summary(&jobs),
+      Some("Bench\n\nTest".to_string())
+    );
+  }
 }
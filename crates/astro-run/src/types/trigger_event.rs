@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TriggerEvent {
-  /// push / pull_request
+  /// push / pull_request / release / any custom event name
   pub event: String,
   pub repo_owner: String,
   pub repo_name: String,
@@ -11,6 +11,18 @@ pub struct TriggerEvent {
   pub branch: String,
   /// refs/heads/master / refs/tags/v1.0.0 / refs/pull/1/merge
   pub ref_name: String,
+  /// Git hosting provider of the repository: github / gitlab / gitea. Defaults to github
+  #[serde(default)]
+  pub provider: Option<String>,
+  /// Head of the branch before a push, used to compare the pushed commits
+  #[serde(default)]
+  pub before: Option<String>,
+  /// Target branch of a pull request (merge request)
+  #[serde(default)]
+  pub base_ref: Option<String>,
+  /// Raw event payload, used by event conditions to filter on arbitrary keys
+  #[serde(default)]
+  pub payload: Option<serde_json::Value>,
 }
 
 impl Default for TriggerEvent {
@@ -23,6 +35,20 @@ impl Default for TriggerEvent {
       branch: "main".to_string(),
       sha: "123456".to_string(),
       pr_number: None,
+      provider: None,
+      before: None,
+      base_ref: None,
+      payload: None,
     }
   }
 }
+
+impl TriggerEvent {
+  /// Returns `before` unless it is missing or the all-zero sha of a newly created branch
+  pub fn before_sha(&self) -> Option<&str> {
+    self
+      .before
+      .as_deref()
+      .filter(|before| !before.is_empty() && before.chars().any(|c| c != '0'))
+  }
+}
@@ -0,0 +1,376 @@
+use crate::{EnvironmentVariable, EnvironmentVariables, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkflowDispatchInputType {
+  #[default]
+  String,
+  Boolean,
+  Choice,
+  Number,
+}
+
+/// An input declared in `on.workflow_dispatch.inputs`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Default)]
+pub struct WorkflowDispatchInput {
+  pub description: Option<String>,
+  #[serde(rename = "type", default)]
+  pub input_type: WorkflowDispatchInputType,
+  #[serde(default)]
+  pub required: bool,
+  pub default: Option<EnvironmentVariable>,
+  /// Allowed values of a `choice` input
+  pub options: Option<Vec<String>>,
+}
+
+/// Condition of the `workflow_dispatch` event.
+///
+/// ```yaml
+/// on:
+///   workflow_dispatch:
+///     inputs:
+///       version:
+///         required: true
+///       environment:
+///         type: choice
+///         options: [staging, production]
+///         default: staging
+///       dry-run:
+///         type: boolean
+///         default: false
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Default)]
+pub struct WorkflowDispatchCondition {
+  #[serde(default)]
+  pub inputs: HashMap<String, WorkflowDispatchInput>,
+}
+
+impl WorkflowDispatchCondition {
+  /// Rejects inputs which can't accept any value
+  pub fn validate(&self) -> Result<()> {
+    let mut keys: Vec<_> = self.inputs.keys().collect();
+    keys.sort();
+
+    // Inputs are passed to the steps as `INPUT_<NAME>` environment variables
+    let mut envs: HashMap<String, &String> = HashMap::new();
+    for key in keys {
+      let is_valid = !key.is_empty()
+        && key
+          .chars()
+          .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+      if !is_valid {
+        return Err(Error::workflow_config_error(format!(
+          "Input name `{}` may only contain letters, digits, `_` and `-`",
+          key
+        )));
+      }
+
+      let env = workflow_dispatch_input_env(key);
+      if let Some(other) = envs.insert(env.clone(), key) {
+        return Err(Error::workflow_config_error(format!(
+          "Inputs `{}` and `{}` are both passed as `{}`",
+          other, key, env
+        )));
+      }
+    }
+
+    for (key, input) in &self.inputs {
+      let has_options = input
+        .options
+        .as_ref()
+        .is_some_and(|options| !options.is_empty());
+
+      if input.input_type == WorkflowDispatchInputType::Choice && !has_options {
+        return Err(Error::workflow_config_error(format!(
+          "Choice input `{}` must have `options`",
+          key
+        )));
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Validates the provided inputs against the declared ones and fills in defaults.
+  /// Optional inputs without a value resolve to an empty string.
+  pub fn validate_inputs(&self, inputs: EnvironmentVariables) -> Result<EnvironmentVariables> {
+    if let Some(key) = inputs.keys().find(|key| !self.inputs.contains_key(*key)) {
+      return Err(Error::workflow_config_error(format!(
+        "Unexpected input `{}`",
+        key
+      )));
+    }
+
+    let mut resolved = HashMap::new();
+
+    for (key, input) in &self.inputs {
+      let value = match inputs.get(key).or(input.default.as_ref()) {
+        Some(value) => input.validate(key, value)?,
+        None if input.required => {
+          return Err(Error::workflow_config_error(format!(
+            "Input `{}` is required",
+            key
+          )));
+        }
+        None => EnvironmentVariable::String(String::new()),
+      };
+
+      resolved.insert(key.clone(), value);
+    }
+
+    Ok(resolved)
+  }
+}
+
+impl WorkflowDispatchInput {
+  fn validate(&self, key: &str, value: &EnvironmentVariable) -> Result<EnvironmentVariable> {
+    let invalid = || {
+      Error::workflow_config_error(format!(
+        "Input `{}` expects a {:?} value, got `{}`",
+        key, self.input_type, value
+      ))
+    };
+
+    let value = match (self.input_type, value) {
+      (WorkflowDispatchInputType::String, value) => EnvironmentVariable::String(value.to_string()),
+      (WorkflowDispatchInputType::Boolean, EnvironmentVariable::Boolean(b)) => {
+        EnvironmentVariable::Boolean(*b)
+      }
+      (WorkflowDispatchInputType::Boolean, EnvironmentVariable::String(s)) => {
+        EnvironmentVariable::Boolean(s.parse().map_err(|_| invalid())?)
+      }
+      (WorkflowDispatchInputType::Number, EnvironmentVariable::Number(n)) => {
+        EnvironmentVariable::Number(*n)
+      }
+      (WorkflowDispatchInputType::Number, EnvironmentVariable::String(s)) => {
+        EnvironmentVariable::Number(s.parse().map_err(|_| invalid())?)
+      }
+      (WorkflowDispatchInputType::Choice, value) => {
+        let value = value.to_string();
+        // Checked by `WorkflowDispatchCondition::validate` when the workflow is parsed
+        let options = self.options.clone().unwrap_or_default();
+
+        if !options.contains(&value) {
+          return Err(Error::workflow_config_error(format!(
+            "Input `{}` must be one of {:?}, got `{}`",
+            key, options, value
+          )));
+        }
+
+        EnvironmentVariable::String(value)
+      }
+      _ => return Err(invalid()),
+    };
+
+    Ok(value)
+  }
+}
+
+/// Environment variable name of an input, e.g. `dry-run` -> `INPUT_DRY_RUN`
+pub fn workflow_dispatch_input_env(key: &str) -> String {
+  format!("INPUT_{}", key.to_uppercase().replace('-', "_"))
+}
+
+/// Replaces `${{ inputs.<name> }}` expressions with a reference to the `INPUT_<NAME>`
+/// environment variable, such as `${INPUT_VERSION}`. The values are not written into the
+/// script, so they can't inject shell code. Unknown expressions are kept as they are.
+pub fn interpolate_workflow_dispatch_inputs(text: &str, inputs: &EnvironmentVariables) -> String {
+  let mut result = String::with_capacity(text.len());
+  let mut rest = text;
+
+  while let Some(start) = rest.find("${{") {
+    let Some(end) = rest[start..].find("}}") else {
+      break;
+    };
+
+    let end = start + end + 2;
+    let expression = rest[start + 3..end - 2].trim();
+
+    result.push_str(&rest[..start]);
+
+    match expression
+      .strip_prefix("inputs.")
+      .filter(|key| inputs.contains_key(*key))
+    {
+      Some(key) => result.push_str(&format!("${{{}}}", workflow_dispatch_input_env(key))),
+      None => result.push_str(&rest[start..end]),
+    }
+
+    rest = &rest[end..];
+  }
+
+  result.push_str(rest);
+
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn condition() -> WorkflowDispatchCondition {
+    serde_yaml::from_str(
+      r#"
+inputs:
+  version:
+    required: true
+  environment:
+    type: choice
+    options: [staging, production]
+    default: staging
+  dry-run:
+    type: boolean
+    default: false
+  replicas:
+    type: number
+  notes:
+    description: Release notes
+"#,
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn test_validate_inputs() {
+    let inputs = condition()
+      .validate_inputs(HashMap::from([
+        ("version".to_string(), "1.2.0".into()),
+        ("dry-run".to_string(), "true".into()),
+        ("replicas".to_string(), 3.0.into()),
+      ]))
+      .unwrap();
+
+    assert_eq!(inputs.get("version"), Some(&"1.2.0".into()));
+    assert_eq!(inputs.get("environment"), Some(&"staging".into()));
+    assert_eq!(inputs.get("dry-run"), Some(&true.into()));
+    assert_eq!(inputs.get("replicas"), Some(&3.0.into()));
+    assert_eq!(inputs.get("notes"), Some(&"".into()));
+  }
+
+  #[test]
+  fn test_invalid_inputs() {
+    let condition = condition();
+
+    let error = condition.validate_inputs(HashMap::new()).unwrap_err();
+    assert_eq!(
+      error,
+      Error::workflow_config_error("Input `version` is required")
+    );
+
+    let error = condition
+      .validate_inputs(HashMap::from([
+        ("version".to_string(), "1.2.0".into()),
+        ("environment".to_string(), "dev".into()),
+      ]))
+      .unwrap_err();
+    assert_eq!(
+      error,
+      Error::workflow_config_error(
+        "Input `environment` must be one of [\"staging\", \"production\"], got `dev`"
+      )
+    );
+
+    let error = condition
+      .validate_inputs(HashMap::from([
+        ("version".to_string(), "1.2.0".into()),
+        ("replicas".to_string(), "many".into()),
+      ]))
+      .unwrap_err();
+    assert_eq!(
+      error,
+      Error::workflow_config_error("Input `replicas` expects a Number value, got `many`")
+    );
+
+    let error = condition
+      .validate_inputs(HashMap::from([
+        ("version".to_string(), "1.2.0".into()),
+        ("unknown".to_string(), "1".into()),
+      ]))
+      .unwrap_err();
+    assert_eq!(
+      error,
+      Error::workflow_config_error("Unexpected input `unknown`")
+    );
+  }
+
+  #[test]
+  fn test_interpolate_inputs() {
+    let inputs = HashMap::from([
+      ("version".to_string(), "\"; rm -rf ~ #".into()),
+      ("dry-run".to_string(), false.into()),
+    ]);
+
+    assert_eq!(
+      interpolate_workflow_dispatch_inputs(
+        "release \"${{ inputs.version }}\" --dry-run=${{inputs.dry-run}} ${{ secrets.TOKEN }}",
+        &inputs
+      ),
+      "release \"${INPUT_VERSION}\" --dry-run=${INPUT_DRY_RUN} ${{ secrets.TOKEN }}"
+    );
+    assert_eq!(
+      workflow_dispatch_input_env("dry-run"),
+      "INPUT_DRY_RUN".to_string()
+    );
+  }
+
+  #[test]
+  fn test_choice_without_options() {
+    let yaml = r#"
+on:
+  workflow_dispatch:
+    inputs:
+      environment:
+        type: choice
+jobs:
+  test:
+    steps:
+      - run: echo "deploy"
+"#;
+
+    let error = crate::UserWorkflow::try_from(yaml).unwrap_err();
+
+    assert_eq!(
+      error,
+      Error::workflow_config_error("Choice input `environment` must have `options`")
+    );
+  }
+
+  #[test]
+  fn test_input_names() {
+    let workflow = |inputs: &str| {
+      format!(
+        r#"
+on:
+  workflow_dispatch:
+    inputs:
+{}
+jobs:
+  test:
+    steps:
+      - run: echo "deploy"
+"#,
+        inputs
+      )
+    };
+
+    let yaml =
+      workflow("      dry-run:\n        type: boolean\n      dry_run:\n        type: boolean");
+    assert_eq!(
+      crate::UserWorkflow::try_from(yaml.as_str()).unwrap_err(),
+      Error::workflow_config_error(
+        "Inputs `dry-run` and `dry_run` are both passed as `INPUT_DRY_RUN`"
+      )
+    );
+
+    let yaml = workflow("      \"version$(id)\":\n        type: string");
+    assert_eq!(
+      crate::UserWorkflow::try_from(yaml.as_str()).unwrap_err(),
+      Error::workflow_config_error(
+        "Input name `version$(id)` may only contain letters, digits, `_` and `-`"
+      )
+    );
+  }
+}
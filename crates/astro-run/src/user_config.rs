@@ -1,15 +1,63 @@
-use crate::{Condition, EnvironmentVariables, Error, Id, Result};
+use crate::{Condition, EnvironmentVariables, Error, HealthCheck, Id, Result, Service};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
 pub struct ContainerOptions {
+  /// Image name, may be omitted when the image is built with `build`
+  #[serde(default)]
   pub name: String,
+  /// Builds the image from a Dockerfile in the repository
+  pub build: Option<ContainerBuild>,
   pub volumes: Option<Vec<String>>,
   #[serde(rename = "security-opts")]
   pub security_opts: Option<Vec<String>>,
+  /// Number of CPUs, such as `1.5`
+  pub cpus: Option<f64>,
+  /// Memory limit, such as `512m` or `2g`
+  pub memory: Option<String>,
+  /// User the steps run as, such as `1000:1000`
+  pub user: Option<String>,
+  /// Network to connect to, instead of the network of the job services
+  pub network: Option<String>,
+  pub privileged: Option<bool>,
+  /// Published ports, such as `8080:80`
+  pub ports: Option<Vec<String>>,
+  /// Mounts a tmpfs, such as `/tmp:size=64m`
+  pub tmpfs: Option<Vec<String>>,
+  #[serde(rename = "env-file")]
+  pub env_file: Option<String>,
+  /// Program the step script is run with, such as `/bin/bash`
+  pub entrypoint: Option<String>,
+  /// Extra `docker run` options, passed as is
+  pub options: Option<String>,
+  /// When the image is pulled before the step runs, defaults to `if-not-present`
+  pub pull: Option<PullPolicy>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
+pub struct ContainerBuild {
+  /// Build context, relative to the working directory. Defaults to `.`
+  pub context: Option<String>,
+  /// Dockerfile, relative to the context. Defaults to `Dockerfile`
+  pub dockerfile: Option<String>,
+  /// Build arguments
+  pub args: Option<HashMap<String, String>>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PullPolicy {
+  /// Pulls the image before every step
+  Always,
+  /// Pulls the image if it's not on the runner
+  #[default]
+  IfNotPresent,
+  /// Never pulls, the image must be on the runner
+  Never,
+}
+
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Container {
@@ -17,6 +65,26 @@ pub enum Container {
   Name(String),
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UserHealthCheck {
+  pub command: String,
+  /// Defaults to `10s`
+  pub interval: Option<String>,
+  /// Defaults to `5s`
+  pub timeout: Option<String>,
+  /// Defaults to 3
+  pub retries: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UserService {
+  pub image: String,
+  pub environments: Option<EnvironmentVariables>,
+  pub ports: Option<Vec<String>>,
+  #[serde(rename = "health-check")]
+  pub health_check: Option<UserHealthCheck>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct UserCommandStep {
   pub name: Option<String>,
@@ -62,6 +130,8 @@ pub struct UserJob {
   pub on: Option<Condition>,
   #[serde(rename = "depends-on")]
   pub depends_on: Option<Vec<String>>,
+  /// Containers started before the steps, keyed by the name steps reach them with
+  pub services: Option<HashMap<Id, UserService>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -79,6 +149,24 @@ impl UserWorkflow {
       ));
     }
 
+    let step_conditions = workflow
+      .jobs
+      .values()
+      .flat_map(|job| job.steps.iter())
+      .filter_map(|step| match step {
+        UserStep::Command(step) => step.on.as_ref(),
+        UserStep::Action(step) => step.on.as_ref(),
+      });
+    let job_conditions = workflow.jobs.values().filter_map(|job| job.on.as_ref());
+    for condition in workflow
+      .on
+      .iter()
+      .chain(job_conditions)
+      .chain(step_conditions)
+    {
+      condition.validate()?;
+    }
+
     let mut is_all_jobs_has_dependencies = true;
     // Validate dependencies key in jobs
     for (job_name, job) in &workflow.jobs {
@@ -105,6 +193,36 @@ impl UserWorkflow {
           job_name
         )));
       }
+
+      let step_containers = job.steps.iter().filter_map(|step| match step {
+        UserStep::Command(step) => step.container.as_ref(),
+        UserStep::Action(_) => None,
+      });
+      for container in job.container.iter().chain(step_containers) {
+        if let Container::Options(options) = container {
+          if options.name.is_empty() && options.build.is_none() {
+            return Err(Error::workflow_config_error(format!(
+              "Container in job `{}` must have a name or a build",
+              job_name
+            )));
+          }
+        }
+      }
+
+      // Service names are used as host names
+      for service_name in job.services.iter().flat_map(|services| services.keys()) {
+        let is_valid = !service_name.is_empty()
+          && service_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+        if !is_valid {
+          return Err(Error::workflow_config_error(format!(
+            "Service `{}` of job `{}` must only contain letters, digits, `-`, `_` and `.`",
+            service_name, job_name
+          )));
+        }
+      }
     }
 
     if is_all_jobs_has_dependencies {
@@ -130,13 +248,43 @@ impl Container {
       Self::Options(docker) => docker.clone(),
       Self::Name(name) => ContainerOptions {
         name: name.clone(),
-        security_opts: None,
-        volumes: None,
+        ..Default::default()
       },
     }
   }
 }
 
+impl UserService {
+  pub fn normalize(self) -> Result<Service> {
+    let parse_duration = |duration: Option<String>, default: &str, field: &str| {
+      humantime::parse_duration(duration.as_deref().unwrap_or(default)).map_err(|err| {
+        log::error!("Invalid health check {}: {}", field, err);
+        Error::workflow_config_error(format!(
+          "Invalid health check {}. The format should like `10s` or `1m`.",
+          field
+        ))
+      })
+    };
+
+    let health_check = match self.health_check {
+      Some(health_check) => Some(HealthCheck {
+        command: health_check.command,
+        interval: parse_duration(health_check.interval, "10s", "interval")?,
+        timeout: parse_duration(health_check.timeout, "5s", "timeout")?,
+        retries: health_check.retries.unwrap_or(3),
+      }),
+      None => None,
+    };
+
+    Ok(Service {
+      image: self.image,
+      environments: self.environments.unwrap_or_default(),
+      ports: self.ports.unwrap_or_default(),
+      health_check,
+    })
+  }
+}
+
 impl TryFrom<&str> for UserWorkflow {
   type Error = Error;
 
@@ -361,6 +509,165 @@ jobs:
       normalized.volumes,
       Some(vec!["/home/runner/work".to_string()])
     );
+    assert_eq!(normalized.cpus, None);
+  }
+
+  #[test]
+  fn test_container_limits() {
+    let yaml = r#"
+jobs:
+  job1:
+    container:
+      name: test
+      cpus: 1.5
+      memory: 512m
+      user: "1000:1000"
+      network: host
+      privileged: true
+      ports:
+        - 8080:80
+      tmpfs:
+        - /tmp:size=64m
+      env-file: .env
+      entrypoint: /bin/bash
+      options: --pids-limit 100
+      pull: always
+    steps:
+      - run: echo "Hello World"
+"#;
+
+    let workflow = UserWorkflow::try_from(yaml).unwrap();
+    let container = workflow
+      .jobs
+      .get("job1")
+      .unwrap()
+      .container
+      .as_ref()
+      .unwrap();
+    let options = container.normalize();
+
+    assert_eq!(options.cpus, Some(1.5));
+    assert_eq!(options.memory, Some("512m".to_string()));
+    assert_eq!(options.user, Some("1000:1000".to_string()));
+    assert_eq!(options.network, Some("host".to_string()));
+    assert_eq!(options.privileged, Some(true));
+    assert_eq!(options.ports, Some(vec!["8080:80".to_string()]));
+    assert_eq!(options.tmpfs, Some(vec!["/tmp:size=64m".to_string()]));
+    assert_eq!(options.env_file, Some(".env".to_string()));
+    assert_eq!(options.entrypoint, Some("/bin/bash".to_string()));
+    assert_eq!(options.options, Some("--pids-limit 100".to_string()));
+    assert_eq!(options.pull, Some(PullPolicy::Always));
+  }
+
+  #[test]
+  fn test_container_build() {
+    let yaml = r#"
+jobs:
+  job1:
+    container:
+      build:
+        context: ci
+        args:
+          RUST_VERSION: "1.75"
+    steps:
+      - run: echo "Hello World"
+"#;
+
+    let workflow = UserWorkflow::try_from(yaml).unwrap();
+    let container = workflow
+      .jobs
+      .get("job1")
+      .unwrap()
+      .container
+      .as_ref()
+      .unwrap();
+    let options = container.normalize();
+    assert_eq!(options.name, "");
+
+    let build = options.build.unwrap();
+    assert_eq!(build.context, Some("ci".to_string()));
+    assert_eq!(build.dockerfile, None);
+    assert_eq!(
+      build.args.unwrap().get("RUST_VERSION"),
+      Some(&"1.75".to_string())
+    );
+
+    let yaml = r#"
+jobs:
+  job1:
+    steps:
+      - container:
+          volumes:
+            - /tmp:/tmp
+        run: echo "Hello World"
+"#;
+    assert_eq!(
+      UserWorkflow::try_from(yaml).unwrap_err(),
+      Error::workflow_config_error("Container in job `job1` must have a name or a build")
+    );
+  }
+
+  #[test]
+  fn test_services() {
+    let yaml = r#"
+jobs:
+  job1:
+    services:
+      postgres:
+        image: postgres:15
+        environments:
+          POSTGRES_PASSWORD: postgres
+        ports:
+          - 5432:5432
+        health-check:
+          command: pg_isready
+          interval: 2s
+      redis:
+        image: redis
+    steps:
+      - run: echo "Hello World"
+"#;
+
+    let workflow = UserWorkflow::try_from(yaml).unwrap();
+    let mut services = workflow.jobs.get("job1").unwrap().services.clone().unwrap();
+
+    let postgres = services.remove("postgres").unwrap().normalize().unwrap();
+    assert_eq!(postgres.image, "postgres:15");
+    assert_eq!(
+      postgres.environments.get("POSTGRES_PASSWORD"),
+      Some(&EnvironmentVariable::String("postgres".to_string()))
+    );
+    assert_eq!(postgres.ports, vec!["5432:5432".to_string()]);
+    let health_check = postgres.health_check.unwrap();
+    assert_eq!(health_check.command, "pg_isready");
+    assert_eq!(health_check.interval, std::time::Duration::from_secs(2));
+    assert_eq!(health_check.timeout, std::time::Duration::from_secs(5));
+    assert_eq!(health_check.retries, 3);
+
+    let redis = services.remove("redis").unwrap().normalize().unwrap();
+    assert!(redis.ports.is_empty());
+    assert!(redis.health_check.is_none());
+  }
+
+  #[test]
+  fn test_invalid_service_name() {
+    let yaml = r#"
+jobs:
+  job1:
+    services:
+      my db:
+        image: postgres
+    steps:
+      - run: echo "Hello World"
+"#;
+
+    let res = UserWorkflow::try_from(yaml);
+    assert_eq!(
+      res.unwrap_err(),
+      Error::workflow_config_error(
+        "Service `my db` of job `job1` must only contain letters, digits, `-`, `_` and `.`"
+      )
+    );
   }
 
   #[test]
@@ -433,6 +740,7 @@ jobs:
         paths: Some(vec!["src/**".to_string()]),
       }),
       pull_request: None,
+      ..Default::default()
     }));
 
     assert_eq!(workflow.on, on);
@@ -443,6 +751,7 @@ jobs:
         paths: Some(vec!["src/**".to_string()]),
       }),
       pull_request: None,
+      ..Default::default()
     }));
     let job = workflow.jobs.get("job").unwrap();
     assert_eq!(job.on, on);
@@ -458,10 +767,34 @@ jobs:
             branches: Some(vec!["master".to_string()]),
             paths: None,
           }),
+          ..Default::default()
         }))
       );
     } else {
       panic!("Step should be command step");
     }
   }
+
+  #[test]
+  fn test_event_condition_paths() {
+    let yaml = r#"
+jobs:
+  job:
+    steps:
+      - run: echo "Hello World"
+        on:
+          deploy_request:
+            paths:
+              - "src/**"
+"#;
+
+    let error = UserWorkflow::try_from(yaml).unwrap_err();
+
+    assert_eq!(
+      error,
+      Error::workflow_config_error(
+        "Event `deploy_request` has no changed files, `paths` is only supported by `push` and `pull_request`"
+      )
+    );
+  }
 }
@@ -1,9 +1,10 @@
-use super::Step;
+use super::{Service, Step};
 use crate::{
-  Condition, ExecutionContext, JobId, JobRunResult, StepRunResult, WorkflowState,
-  WorkflowStateEvent,
+  BeforeRun, Condition, ContainerOptions, ExecutionContext, Id, JobId, JobRunResult, StepRunResult,
+  WorkflowState, WorkflowStateEvent,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Job {
@@ -14,32 +15,58 @@ pub struct Job {
   /// For workflow run
   pub depends_on: Vec<String>,
   pub working_directories: Vec<String>,
+  /// Container of the job, steps may override it
+  #[serde(default)]
+  pub container: Option<ContainerOptions>,
+  #[serde(default)]
+  pub services: HashMap<Id, Service>,
 }
 
 impl Job {
   pub async fn run(&self, ctx: ExecutionContext) -> JobRunResult {
     if self.should_skip(&ctx).await {
-      ctx
-        .call_on_state_change(WorkflowStateEvent::JobStateUpdated {
-          id: self.id.clone(),
-          state: WorkflowState::Skipped,
-        })
-        .await;
+      return self.skip(&ctx).await;
+    }
+
+    match ctx.call_on_before_run_job(self.clone()).await {
+      Ok(BeforeRun::Continue(job)) => job.execute(ctx, false).await,
+      Ok(BeforeRun::Skip(reason)) => {
+        log::info!("Job {} is skipped: {}", self.id, reason);
+        self.skip(&ctx).await
+      }
+      Ok(BeforeRun::Fail(reason)) => {
+        log::error!("Job {} is failed by a plugin: {}", self.id, reason);
+        self.execute(ctx, true).await
+      }
+      Err(_) => self.execute(ctx, true).await,
+    }
+  }
 
-      return JobRunResult {
+  async fn skip(&self, ctx: &ExecutionContext) -> JobRunResult {
+    ctx
+      .call_on_state_change(WorkflowStateEvent::JobStateUpdated {
         id: self.id.clone(),
         state: WorkflowState::Skipped,
-        started_at: None,
-        completed_at: None,
-        steps: vec![],
-      };
+      })
+      .await;
+
+    JobRunResult {
+      id: self.id.clone(),
+      state: WorkflowState::Skipped,
+      started_at: None,
+      completed_at: None,
+      steps: vec![],
+      summary: None,
     }
+  }
 
+  /// Runs the steps. When `aborted`, the job fails and all of its steps are skipped
+  async fn execute(&self, ctx: ExecutionContext, aborted: bool) -> JobRunResult {
     let started_at = chrono::Utc::now();
     let mut job_state = WorkflowState::InProgress;
 
     // Dispatch run job event
-    ctx.call_on_run_job(self.clone()).await;
+    let aborted = ctx.call_on_run_job(self.clone()).await.is_err() || aborted;
     ctx
       .call_on_state_change(WorkflowStateEvent::JobStateUpdated {
         id: self.id.clone(),
@@ -47,14 +74,20 @@ impl Job {
       })
       .await;
 
+    // A plugin refused to run the job
+    if aborted {
+      job_state = WorkflowState::Failed;
+    }
+
     let mut steps = Vec::new();
 
     for step in self.steps.iter().cloned() {
-      let mut skipped = match job_state {
-        WorkflowState::Failed => !step.continue_on_error,
-        WorkflowState::Cancelled | WorkflowState::Skipped => true,
-        _ => false,
-      };
+      let mut skipped = aborted
+        || match job_state {
+          WorkflowState::Failed => !step.continue_on_error,
+          WorkflowState::Cancelled | WorkflowState::Skipped => true,
+          _ => false,
+        };
 
       if !skipped && step.should_skip(&ctx).await {
         skipped = true;
@@ -76,6 +109,7 @@ impl Job {
           exit_code: None,
           started_at: None,
           completed_at: None,
+          summary: None,
         });
         continue;
       }
@@ -113,6 +147,7 @@ impl Job {
       state: job_state,
       started_at: Some(started_at),
       completed_at: Some(completed_at),
+      summary: JobRunResult::aggregate_summary(&steps),
       steps,
     };
 
@@ -1,13 +1,16 @@
 mod builder;
 mod job;
 mod parser;
+mod service;
 mod step;
 
 pub use self::job::Job;
+pub use self::service::{HealthCheck, Service};
 pub use self::step::Step;
 use crate::{
-  Condition, ExecutionContext, Id, JobRunResult, WorkflowId, WorkflowRunResult, WorkflowState,
-  WorkflowStateEvent,
+  interpolate_workflow_dispatch_inputs, workflow_dispatch_input_env, BeforeRun, Condition,
+  EnvironmentVariables, EventStream, ExecutionContext, Id, JobRunResult, Time,
+  WorkflowDispatchCondition, WorkflowId, WorkflowRunResult, WorkflowState, WorkflowStateEvent,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -27,27 +30,48 @@ pub struct Workflow {
 impl Workflow {
   pub async fn run(&self, ctx: ExecutionContext) -> WorkflowRunResult {
     if self.should_skip(&ctx).await {
-      ctx
-        .call_on_state_change(WorkflowStateEvent::WorkflowStateUpdated {
-          id: self.id.clone(),
-          state: WorkflowState::Skipped,
-        })
-        .await;
+      return self.skip(&ctx).await;
+    }
+
+    match ctx.call_on_before_run_workflow(self.clone()).await {
+      Ok(BeforeRun::Continue(workflow)) => workflow.execute(ctx, false).await,
+      Ok(BeforeRun::Skip(reason)) => {
+        log::info!("Workflow {} is skipped: {}", self.id, reason);
+        self.skip(&ctx).await
+      }
+      Ok(BeforeRun::Fail(reason)) => {
+        log::error!("Workflow {} is failed by a plugin: {}", self.id, reason);
+        self.execute(ctx, true).await
+      }
+      Err(_) => self.execute(ctx, true).await,
+    }
+  }
 
-      return WorkflowRunResult {
+  async fn skip(&self, ctx: &ExecutionContext) -> WorkflowRunResult {
+    ctx
+      .call_on_state_change(WorkflowStateEvent::WorkflowStateUpdated {
         id: self.id.clone(),
         state: WorkflowState::Skipped,
-        started_at: None,
-        completed_at: None,
-        jobs: HashMap::new(),
-      };
+      })
+      .await;
+
+    WorkflowRunResult {
+      id: self.id.clone(),
+      state: WorkflowState::Skipped,
+      started_at: None,
+      completed_at: None,
+      jobs: HashMap::new(),
+      summary: None,
     }
+  }
 
+  /// Runs the jobs. When `aborted`, the workflow fails without running any job
+  async fn execute(&self, ctx: ExecutionContext, aborted: bool) -> WorkflowRunResult {
     let started_at = chrono::Utc::now();
 
     let mut workflow_state = WorkflowState::InProgress;
     // Dispatch run workflow event
-    ctx.call_on_run_workflow(self.clone()).await;
+    let aborted = ctx.call_on_run_workflow(self.clone()).await.is_err() || aborted;
     ctx
       .call_on_state_change(WorkflowStateEvent::WorkflowStateUpdated {
         id: self.id.clone(),
@@ -55,6 +79,13 @@ impl Workflow {
       })
       .await;
 
+    // A plugin refused to run the workflow
+    if aborted {
+      return self
+        .complete(&ctx, WorkflowState::Failed, started_at, HashMap::new())
+        .await;
+    }
+
     let (sender, mut receiver) = channel::<Result>(10);
 
     let mut waiting_jobs: Vec<(Id, Job)> = vec![];
@@ -123,6 +154,23 @@ impl Workflow {
       }
     }
 
+    self
+      .complete(&ctx, workflow_state, started_at, job_results)
+      .await
+  }
+
+  async fn complete(
+    &self,
+    ctx: &ExecutionContext,
+    workflow_state: WorkflowState,
+    started_at: Time,
+    job_results: HashMap<Id, JobRunResult>,
+  ) -> WorkflowRunResult {
+    let mut workflow_state = workflow_state;
+    if ctx.take_plugin_failure(&self.id) {
+      workflow_state = WorkflowState::Failed;
+    }
+
     let completed_at = chrono::Utc::now();
 
     log::trace!(
@@ -142,14 +190,29 @@ impl Workflow {
       state: workflow_state,
       started_at: Some(started_at),
       completed_at: Some(completed_at),
+      summary: WorkflowRunResult::aggregate_summary(&job_results),
       jobs: job_results,
     };
 
     ctx.call_on_workflow_completed(result.clone()).await;
+    // Errors raised once the state is final can't fail the workflow anymore
+    ctx.take_plugin_failure(&self.id);
 
     result
   }
 
+  /// Starts the workflow in the background and streams its events, ending with
+  /// `AstroRunEvent::WorkflowCompleted` (or the `Skipped` state change)
+  pub fn run_with_events(&self, ctx: ExecutionContext) -> EventStream {
+    // Subscribed before the run starts, so that no event is missed
+    let events = ctx.subscribe().workflow(self.id.clone());
+
+    let workflow = self.clone();
+    let run = tokio::spawn(async move { workflow.run(ctx).await });
+
+    events.run(run)
+  }
+
   fn run_job(&self, key: Id, job: job::Job, context: ExecutionContext, sender: Sender<Result>) {
     tokio::spawn(async move {
       let result = job.run(context).await;
@@ -168,6 +231,37 @@ impl Workflow {
     }
   }
 
+  /// Returns the `workflow_dispatch` condition, or `None` if the workflow can't be dispatched manually
+  pub fn workflow_dispatch(&self) -> Option<WorkflowDispatchCondition> {
+    match &self.on {
+      Some(Condition::Event(events)) if events.iter().any(|e| e == "workflow_dispatch") => {
+        Some(WorkflowDispatchCondition::default())
+      }
+      Some(Condition::Config(config)) => config.workflow_dispatch.clone(),
+      _ => None,
+    }
+  }
+
+  /// Exposes the inputs to every step as `INPUT_<NAME>` environment variables.
+  /// `${{ inputs.<name> }}` expressions in scripts are replaced by references to them
+  pub(crate) fn with_inputs(&self, inputs: &EnvironmentVariables) -> Workflow {
+    let mut workflow = self.clone();
+
+    for job in workflow.jobs.values_mut() {
+      for step in job.steps.iter_mut() {
+        step.run = interpolate_workflow_dispatch_inputs(&step.run, inputs);
+
+        for (key, value) in inputs {
+          step
+            .environments
+            .insert(workflow_dispatch_input_env(key), value.clone());
+        }
+      }
+    }
+
+    workflow
+  }
+
   pub fn builder() -> builder::WorkflowBuilder {
     builder::WorkflowBuilder::new()
   }
@@ -96,6 +96,12 @@ impl<'a> WorkflowParser<'a> {
       let mut steps = Vec::new();
       let job_container = job.container;
       let job_working_dirs = job.working_dirs.unwrap_or_default();
+      let services = job
+        .services
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, service)| Ok((name, service.normalize()?)))
+        .collect::<Result<HashMap<_, _>>>()?;
 
       let job_steps = self
         .try_normalize_user_steps(&plugin_driver, &action_driver, job.steps)
@@ -146,6 +152,8 @@ impl<'a> WorkflowParser<'a> {
           steps,
           depends_on: job.depends_on.unwrap_or_default(),
           working_directories: job_working_dirs,
+          container: job_container.map(|c| c.normalize()),
+          services,
         },
       );
     }
@@ -240,6 +248,7 @@ jobs:
 
     let job = workflow.jobs.get("test-job2").unwrap();
     assert_eq!(job.steps.len(), 2);
+    assert_eq!(job.container.as_ref().unwrap().name, "alpine:latest");
 
     let step = job.steps.first().unwrap();
     assert_eq!(step.run, "echo \"Hello World2\"");
@@ -0,0 +1,24 @@
+use crate::EnvironmentVariables;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A container started before the steps of a job, such as a database. Steps reach it by
+/// the service name on the job network
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Service {
+  pub image: String,
+  pub environments: EnvironmentVariables,
+  /// Published ports, such as `5432:5432`
+  pub ports: Vec<String>,
+  pub health_check: Option<HealthCheck>,
+}
+
+/// The steps start once the command succeeds in the service container
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HealthCheck {
+  pub command: String,
+  pub interval: Duration,
+  pub timeout: Duration,
+  /// Consecutive failures before the service is considered unhealthy
+  pub retries: u32,
+}
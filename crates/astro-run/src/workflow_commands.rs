@@ -0,0 +1,123 @@
+use crate::{Log, LogAnnotation, WorkflowLogType};
+
+/// Parses a workflow command printed by a step, such as
+/// `::warning file=src/main.rs,line=3,col=5::Unused variable`.
+///
+/// Supported commands are `error`, `warning`, `debug`, `group` and `endgroup`.
+/// Returns `None` if the line is not a supported command.
+pub fn parse_workflow_command(line: &str) -> Option<Log> {
+  let command = line.trim_end_matches(['\r', '\n']).strip_prefix("::")?;
+  let (command, message) = command.split_once("::")?;
+  let (name, properties) = match command.split_once(' ') {
+    Some((name, properties)) => (name, properties.trim()),
+    None => (command, ""),
+  };
+
+  let log_type = match name {
+    "error" => WorkflowLogType::Error,
+    "warning" => WorkflowLogType::Warning,
+    "debug" => WorkflowLogType::Debug,
+    "group" => WorkflowLogType::GroupStart,
+    "endgroup" => WorkflowLogType::GroupEnd,
+    _ => return None,
+  };
+
+  let mut log = Log::new(log_type.clone(), unescape_data(message));
+
+  if matches!(log_type, WorkflowLogType::Error | WorkflowLogType::Warning) && !properties.is_empty()
+  {
+    log.annotation = Some(parse_annotation(properties));
+  }
+
+  Some(log)
+}
+
+fn parse_annotation(properties: &str) -> LogAnnotation {
+  let mut annotation = LogAnnotation::default();
+
+  for property in properties.split(',') {
+    let Some((key, value)) = property.split_once('=') else {
+      continue;
+    };
+
+    let value = unescape_property(value.trim());
+    let number = || value.parse().ok();
+
+    match key.trim() {
+      "file" => annotation.file = Some(value.clone()),
+      "line" => annotation.line = number(),
+      "endLine" => annotation.end_line = number(),
+      "col" => annotation.column = number(),
+      "endColumn" => annotation.end_column = number(),
+      "title" => annotation.title = Some(value.clone()),
+      key => log::trace!("Unknown workflow command property `{}`", key),
+    }
+  }
+
+  annotation
+}
+
+fn unescape_data(value: &str) -> String {
+  value
+    .replace("%0D", "\r")
+    .replace("%0A", "\n")
+    .replace("%25", "%")
+}
+
+fn unescape_property(value: &str) -> String {
+  unescape_data(&value.replace("%3A", ":").replace("%2C", ","))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_annotations() {
+    let log =
+      parse_workflow_command("::warning file=src/a.rs,line=3,endLine=4,col=1,endColumn=8::Unused")
+        .unwrap();
+    assert_eq!(log.log_type, WorkflowLogType::Warning);
+    assert_eq!(log.message, "Unused");
+    assert_eq!(
+      log.annotation,
+      Some(LogAnnotation {
+        file: Some("src/a.rs".to_string()),
+        line: Some(3),
+        end_line: Some(4),
+        column: Some(1),
+        end_column: Some(8),
+        title: None,
+      })
+    );
+
+    let log = parse_workflow_command("::error title=Build%2C failed::line 1%0Aline 2\n").unwrap();
+    assert_eq!(log.log_type, WorkflowLogType::Error);
+    assert_eq!(log.message, "line 1\nline 2");
+    assert_eq!(
+      log.annotation.unwrap().title,
+      Some("Build, failed".to_string())
+    );
+
+    let log = parse_workflow_command("::error::Failed").unwrap();
+    assert_eq!(log.annotation, None);
+  }
+
+  #[test]
+  fn test_commands() {
+    let log = parse_workflow_command("::debug::Details").unwrap();
+    assert_eq!(log.log_type, WorkflowLogType::Debug);
+    assert_eq!(log.message, "Details");
+
+    let log = parse_workflow_command("::group::Install dependencies").unwrap();
+    assert_eq!(log.log_type, WorkflowLogType::GroupStart);
+    assert_eq!(log.message, "Install dependencies");
+
+    let log = parse_workflow_command("::endgroup::").unwrap();
+    assert_eq!(log.log_type, WorkflowLogType::GroupEnd);
+
+    assert!(parse_workflow_command("Hello World").is_none());
+    assert!(parse_workflow_command("::unknown::message").is_none());
+    assert!(parse_workflow_command("::warning without message").is_none());
+  }
+}
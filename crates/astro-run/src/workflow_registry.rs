@@ -0,0 +1,343 @@
+use crate::{AstroRun, Error, Result, TriggerEvent, Workflow, WorkflowId, WorkflowRunResult};
+use std::path::Path;
+use tokio::task::JoinHandle;
+
+/// Why a workflow was not started by `WorkflowRegistry::trigger`
+#[derive(Debug)]
+pub enum SkipReason {
+  /// The workflow doesn't listen to the event
+  EventNotMatched,
+  /// The workflow listens to the event, but its filters (branches, paths, ...) don't match
+  ConditionNotMatched,
+  /// The workflow config could not be parsed
+  InvalidConfig(Error),
+}
+
+impl std::fmt::Display for SkipReason {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SkipReason::EventNotMatched => write!(f, "Event not matched"),
+      SkipReason::ConditionNotMatched => write!(f, "Condition not matched"),
+      SkipReason::InvalidConfig(err) => write!(f, "Invalid config: {}", err),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct StartedWorkflow {
+  pub name: String,
+  pub id: WorkflowId,
+  pub handle: JoinHandle<WorkflowRunResult>,
+}
+
+#[derive(Debug)]
+pub struct SkippedWorkflow {
+  pub name: String,
+  pub reason: SkipReason,
+}
+
+#[derive(Debug, Default)]
+pub struct WorkflowTriggerResult {
+  pub started: Vec<StartedWorkflow>,
+  pub skipped: Vec<SkippedWorkflow>,
+}
+
+impl WorkflowTriggerResult {
+  /// Waits for all the started workflows to complete
+  pub async fn wait(self) -> Vec<WorkflowRunResult> {
+    let mut results = vec![];
+
+    for workflow in self.started {
+      match workflow.handle.await {
+        Ok(result) => results.push(result),
+        Err(err) => log::error!("Workflow `{}` panicked: {}", workflow.name, err),
+      }
+    }
+
+    results
+  }
+}
+
+/// # WorkflowRegistry
+/// A named set of workflow configs, usually loaded from a directory such as `.astro/workflows`.
+///
+/// `trigger` starts every workflow whose `on` condition matches the event.
+/// Each run is built from the config again, so it gets its own `WorkflowId`.
+pub struct WorkflowRegistry {
+  astro_run: AstroRun,
+  // Name, config. Sorted by name
+  workflows: Vec<(String, String)>,
+}
+
+impl WorkflowRegistry {
+  pub fn new(astro_run: AstroRun) -> Self {
+    Self {
+      astro_run,
+      workflows: vec![],
+    }
+  }
+
+  /// Adds every `*.yml` / `*.yaml` file of `dir`, named after the file stem
+  pub async fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<()> {
+    let mut entries = tokio::fs::read_dir(dir.as_ref()).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+      let path = entry.path();
+      let is_yaml = matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("yml" | "yaml")
+      );
+
+      if !is_yaml || !path.is_file() {
+        continue;
+      }
+
+      let name = path
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+      let config = tokio::fs::read_to_string(&path).await?;
+      self.insert(name, config);
+    }
+
+    Ok(())
+  }
+
+  /// Adds a workflow, replacing the one with the same name.
+  /// Invalid configs are kept and reported as skipped on every trigger.
+  pub fn add(&mut self, name: impl Into<String>, config: impl Into<String>) {
+    self.insert(name.into(), config.into());
+  }
+
+  pub fn remove(&mut self, name: &str) -> bool {
+    let len = self.workflows.len();
+    self.workflows.retain(|(n, _)| n != name);
+
+    self.workflows.len() != len
+  }
+
+  pub fn names(&self) -> Vec<String> {
+    self
+      .workflows
+      .iter()
+      .map(|(name, _)| name.clone())
+      .collect()
+  }
+
+  /// Starts every workflow matching the event and reports the skipped ones
+  pub async fn trigger(&self, event: TriggerEvent) -> WorkflowTriggerResult {
+    let mut result = WorkflowTriggerResult::default();
+
+    for (name, config) in &self.workflows {
+      let workflow = match self.build_workflow(config).await {
+        Ok(workflow) => workflow,
+        Err(err) => {
+          log::error!("Invalid workflow `{}`: {}", name, err);
+          result.skipped.push(SkippedWorkflow {
+            name: name.clone(),
+            reason: SkipReason::InvalidConfig(err),
+          });
+          continue;
+        }
+      };
+
+      if let Some(on) = &workflow.on {
+        if !on.has_event(&event.event) {
+          log::trace!("Workflow `{}` does not listen to `{}`", name, event.event);
+          result.skipped.push(SkippedWorkflow {
+            name: name.clone(),
+            reason: SkipReason::EventNotMatched,
+          });
+          continue;
+        }
+      }
+
+      let ctx = self
+        .astro_run
+        .execution_context()
+        .event(event.clone())
+        .build();
+
+      if workflow.should_skip(&ctx).await {
+        log::trace!("Workflow `{}` does not match the event", name);
+        result.skipped.push(SkippedWorkflow {
+          name: name.clone(),
+          reason: SkipReason::ConditionNotMatched,
+        });
+        continue;
+      }
+
+      log::trace!("Starting workflow `{}` ({})", name, workflow.id);
+      let id = workflow.id.clone();
+      let handle = tokio::spawn(async move { workflow.run(ctx).await });
+
+      result.started.push(StartedWorkflow {
+        name: name.clone(),
+        id,
+        handle,
+      });
+    }
+
+    result
+  }
+
+  async fn build_workflow(&self, config: &str) -> Result<Workflow> {
+    Workflow::builder()
+      .config(config)
+      .build(&self.astro_run)
+      .await
+  }
+
+  fn insert(&mut self, name: String, config: String) {
+    self.workflows.retain(|(n, _)| *n != name);
+    self.workflows.push((name, config));
+    self.workflows.sort_by(|a, b| a.0.cmp(&b.0));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{stream, Context, RunResponse, RunResult, Runner, WorkflowState};
+
+  struct TestRunner;
+
+  #[async_trait::async_trait]
+  impl Runner for TestRunner {
+    async fn run(&self, ctx: Context) -> RunResponse {
+      let (tx, rx) = stream();
+      tx.log(ctx.command.run);
+      tx.end(RunResult::Succeeded);
+
+      Ok(rx)
+    }
+  }
+
+  fn registry() -> WorkflowRegistry {
+    let astro_run = AstroRun::builder().runner(TestRunner).build();
+    let mut registry = WorkflowRegistry::new(astro_run);
+
+    registry.add(
+      "release",
+      r#"
+on:
+  tag:
+    branches: [v*]
+jobs:
+  release:
+    steps:
+      - run: echo release
+"#,
+    );
+    registry.add(
+      "nightly",
+      r#"
+on:
+  tag:
+    branches: [nightly-*]
+jobs:
+  nightly:
+    steps:
+      - run: echo nightly
+"#,
+    );
+    registry.add(
+      "ci",
+      r#"
+on: [push]
+jobs:
+  test:
+    steps:
+      - run: echo test
+"#,
+    );
+    registry.add("invalid", "jobs: invalid");
+
+    registry
+  }
+
+  #[astro_run_test::test]
+  async fn test_trigger() {
+    let registry = registry();
+    assert_eq!(
+      registry.names(),
+      vec!["ci", "invalid", "nightly", "release"]
+    );
+
+    let event = TriggerEvent {
+      event: "tag".to_string(),
+      branch: "v1.0.0".to_string(),
+      ..Default::default()
+    };
+
+    let result = registry.trigger(event.clone()).await;
+    let started: Vec<_> = result.started.iter().map(|w| w.name.clone()).collect();
+    assert_eq!(started, vec!["release"]);
+
+    let skipped: Vec<_> = result
+      .skipped
+      .iter()
+      .map(|w| (w.name.clone(), w.reason.to_string()))
+      .collect();
+    assert_eq!(
+      skipped[0],
+      ("ci".to_string(), "Event not matched".to_string())
+    );
+    assert!(skipped[1].1.starts_with("Invalid config"));
+    assert_eq!(
+      skipped[2],
+      ("nightly".to_string(), "Condition not matched".to_string())
+    );
+
+    let results = result.wait().await;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].state, WorkflowState::Succeeded);
+
+    // Every run gets a new id
+    let first = registry.trigger(event.clone()).await;
+    let second = registry.trigger(event).await;
+    assert_ne!(first.started[0].id, second.started[0].id);
+  }
+
+  #[astro_run_test::test]
+  async fn test_load_dir() {
+    let dir = std::env::temp_dir().join(format!("astro-run-registry-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+      dir.join("ci.yml"),
+      "on: [push]\njobs:\n  test:\n    steps:\n      - run: echo test\n",
+    )
+    .unwrap();
+    std::fs::write(
+      dir.join("deploy.yaml"),
+      "jobs:\n  deploy:\n    steps:\n      - run: echo deploy\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("README.md"), "Not a workflow").unwrap();
+
+    let astro_run = AstroRun::builder().runner(TestRunner).build();
+    let mut registry = WorkflowRegistry::new(astro_run);
+    registry.load_dir(&dir).await.unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(registry.names(), vec!["ci", "deploy"]);
+
+    let result = registry
+      .trigger(TriggerEvent {
+        event: "push".to_string(),
+        ..Default::default()
+      })
+      .await;
+    // `deploy` has no `on` condition, so it runs for every event
+    assert_eq!(result.started.len(), 2);
+    assert!(result.skipped.is_empty());
+
+    assert!(registry.remove("ci"));
+    assert!(!registry.remove("ci"));
+    assert!(registry
+      .load_dir(std::env::temp_dir().join("astro-run-registry-missing"))
+      .await
+      .is_err());
+  }
+}
@@ -0,0 +1,120 @@
+use astro_run::{
+  stream, AstroRun, AstroRunEvent, Context, RunResult, Runner, Workflow, WorkflowState,
+  WorkflowStateEvent,
+};
+use tokio_stream::StreamExt;
+
+struct TestRunner;
+
+#[astro_run::async_trait]
+impl Runner for TestRunner {
+  async fn run(&self, ctx: Context) -> astro_run::RunResponse {
+    let (tx, rx) = stream();
+
+    tx.log(ctx.command.run);
+    tx.end(RunResult::Succeeded);
+
+    Ok(rx)
+  }
+}
+
+const WORKFLOW: &str = r#"
+jobs:
+  test:
+    steps:
+      - run: Hello World
+"#;
+
+#[astro_run_test::test]
+async fn test_run_with_events() {
+  let astro_run = AstroRun::builder().runner(TestRunner).build();
+
+  let workflow = Workflow::builder()
+    .config(WORKFLOW)
+    .build(&astro_run)
+    .await
+    .unwrap();
+
+  let events: Vec<_> = workflow
+    .run_with_events(astro_run.execution_context().build())
+    .collect()
+    .await;
+
+  assert!(matches!(events[0], AstroRunEvent::WorkflowStarted(_)));
+  assert!(events
+    .iter()
+    .any(|event| matches!(event, AstroRunEvent::JobStarted(_))));
+  assert!(events
+    .iter()
+    .any(|event| matches!(event, AstroRunEvent::StepCompleted(_))));
+  assert!(events.iter().any(|event| matches!(
+    event,
+    AstroRunEvent::Log(log) if log.message == "Hello World"
+  )));
+
+  match events.last().unwrap() {
+    AstroRunEvent::WorkflowCompleted(result) => {
+      assert_eq!(result.id, workflow.id);
+      assert_eq!(result.state, WorkflowState::Succeeded);
+    }
+    event => panic!("Unexpected event {:?}", event),
+  }
+}
+
+#[astro_run_test::test]
+async fn test_subscribe() {
+  let astro_run = AstroRun::builder().runner(TestRunner).build();
+  let mut events = astro_run.subscribe();
+
+  let workflow = Workflow::builder()
+    .config(WORKFLOW)
+    .build(&astro_run)
+    .await
+    .unwrap();
+  workflow.run(astro_run.execution_context().build()).await;
+
+  let mut states = vec![];
+  while let Some(event) = events.next().await {
+    match event {
+      AstroRunEvent::StateChanged(WorkflowStateEvent::WorkflowStateUpdated { state, .. }) => {
+        states.push(state)
+      }
+      AstroRunEvent::WorkflowCompleted(_) => break,
+      _ => {}
+    }
+  }
+
+  assert_eq!(
+    states,
+    vec![WorkflowState::InProgress, WorkflowState::Succeeded]
+  );
+}
+
+#[astro_run_test::test]
+async fn test_subscriber_lagged() {
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .event_capacity(2)
+    .build();
+  let mut events = astro_run.subscribe();
+
+  let workflow = Workflow::builder()
+    .config(WORKFLOW)
+    .build(&astro_run)
+    .await
+    .unwrap();
+  workflow.run(astro_run.execution_context().build()).await;
+
+  assert!(matches!(
+    events.next().await,
+    Some(AstroRunEvent::Lagged { skipped }) if skipped > 0
+  ));
+  assert!(matches!(
+    events.next().await,
+    Some(AstroRunEvent::StateChanged(_))
+  ));
+  assert!(matches!(
+    events.next().await,
+    Some(AstroRunEvent::WorkflowCompleted(_))
+  ));
+}
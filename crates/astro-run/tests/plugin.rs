@@ -1,9 +1,11 @@
 use astro_run::{
-  stream, AstroRun, AstroRunPlugin, Context, Error, HookBeforeRunStepResult, HookNoopResult,
-  JobRunResult, Plugin, RunJobEvent, RunResult, RunStepEvent, RunWorkflowEvent, Runner, Step,
-  StepRunResult, Workflow, WorkflowLog, WorkflowRunResult, WorkflowState, WorkflowStateEvent,
+  stream, AstroRun, AstroRunPlugin, BeforeRun, Context, Error, HookBeforeRunResult,
+  HookBeforeRunStepResult, HookNoopResult, JobRunResult, Plugin, PluginFailurePolicy, RunJobEvent,
+  RunResult, RunStepEvent, RunWorkflowEvent, Runner, Step, StepRunResult, Workflow, WorkflowLog,
+  WorkflowRunResult, WorkflowState, WorkflowStateEvent,
 };
 use parking_lot::Mutex;
+use std::sync::Arc;
 
 struct TestRunner;
 
@@ -53,7 +55,7 @@ impl Runner for TestRunner {
   async fn on_before_run_step(&self, step: Step) -> HookBeforeRunStepResult {
     let mut step = step;
     step.run = "Updated command".to_string();
-    Ok(step)
+    Ok(step.into())
   }
 }
 
@@ -65,7 +67,7 @@ impl Plugin for ErrorBeforeRunStepPlugin {
     "error-before-run-step-plugin"
   }
 
-  async fn on_before_run_step(&self, _step: Step) -> HookBeforeRunStepResult {
+  async fn on_before_run_step(&self, _step: Step) -> HookBeforeRunResult<Step> {
     Err(Error::error("Error"))
   }
 }
@@ -128,3 +130,226 @@ jobs:
 
   assert_eq!(res.state, WorkflowState::Succeeded);
 }
+
+const WORKFLOW: &str = r#"
+jobs:
+  test:
+    steps:
+      - run: Hello World
+      - run: Continue on error
+        continue-on-error: true
+"#;
+
+async fn run_workflow(astro_run: &AstroRun) -> WorkflowRunResult {
+  let workflow = Workflow::builder()
+    .config(WORKFLOW)
+    .build(astro_run)
+    .await
+    .unwrap();
+
+  workflow.run(astro_run.execution_context().build()).await
+}
+
+#[astro_run_test::test]
+async fn test_async_hooks_and_priority() {
+  let calls = Arc::new(Mutex::new(vec![]));
+
+  let plugin = |name: &'static str, priority: i32| {
+    let calls = calls.clone();
+
+    AstroRunPlugin::builder(name)
+      .priority(priority)
+      .on_run_workflow_async(move |_| {
+        let calls = calls.clone();
+
+        async move {
+          tokio::task::yield_now().await;
+          calls.lock().push(name);
+
+          Ok(())
+        }
+      })
+      .build()
+  };
+
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .plugin(plugin("first", 0))
+    .plugin(plugin("second", 0))
+    .plugin(plugin("high", 10))
+    .plugin(plugin("low", -10))
+    .build();
+
+  let res = run_workflow(&astro_run).await;
+
+  assert_eq!(res.state, WorkflowState::Succeeded);
+  assert_eq!(*calls.lock(), vec!["high", "first", "second", "low"]);
+}
+
+#[astro_run_test::test]
+async fn test_failure_policy_abort_step() {
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .plugin(
+      AstroRunPlugin::builder("abort")
+        .on_run_step(|event| {
+          if event.source.id.step_number() == 0 {
+            Err(Error::error("Not allowed"))
+          } else {
+            Ok(())
+          }
+        })
+        .on_log_async(|_| async { Err(Error::error("Ignored")) })
+        .build(),
+    )
+    .plugin_failure_policy(PluginFailurePolicy::AbortStep)
+    .build();
+
+  let res = run_workflow(&astro_run).await;
+
+  assert_eq!(res.state, WorkflowState::Failed);
+  let steps = &res.jobs.get("test").unwrap().steps;
+  assert_eq!(steps[0].state, WorkflowState::Failed);
+  assert_eq!(steps[0].exit_code, None);
+  assert_eq!(steps[1].state, WorkflowState::Succeeded);
+}
+
+#[astro_run_test::test]
+async fn test_failure_policy_fail_workflow() {
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .plugin(
+      AstroRunPlugin::builder("log")
+        .on_log(|_| Err(Error::error("Failed to store log")))
+        .build(),
+    )
+    .plugin_failure_policy(PluginFailurePolicy::FailWorkflow)
+    .build();
+
+  let res = run_workflow(&astro_run).await;
+
+  // The steps ran, but the workflow fails
+  assert_eq!(res.state, WorkflowState::Failed);
+  let job = res.jobs.get("test").unwrap();
+  assert_eq!(job.state, WorkflowState::Succeeded);
+
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .plugin(
+      AstroRunPlugin::builder("job")
+        .on_run_job(|_| Err(Error::error("Not allowed")))
+        .build(),
+    )
+    .plugin_failure_policy(PluginFailurePolicy::FailWorkflow)
+    .build();
+
+  let res = run_workflow(&astro_run).await;
+
+  assert_eq!(res.state, WorkflowState::Failed);
+  let job = res.jobs.get("test").unwrap();
+  assert_eq!(job.state, WorkflowState::Failed);
+  assert!(job
+    .steps
+    .iter()
+    .all(|step| step.state == WorkflowState::Skipped));
+
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .plugin(
+      AstroRunPlugin::builder("workflow")
+        .on_run_workflow(|_| Err(Error::error("Not allowed")))
+        .build(),
+    )
+    .plugin_failure_policy(PluginFailurePolicy::FailWorkflow)
+    .build();
+
+  let res = run_workflow(&astro_run).await;
+
+  assert_eq!(res.state, WorkflowState::Failed);
+  assert!(res.jobs.is_empty());
+}
+
+#[astro_run_test::test]
+async fn test_before_run_step_decisions() {
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .plugin(
+      AstroRunPlugin::builder("policy")
+        .on_before_run_step(|step| {
+          if step.id.step_number() == 0 {
+            Ok(BeforeRun::Skip("Not needed".to_string()))
+          } else {
+            Ok(BeforeRun::Fail(
+              "Privileged containers are not allowed".to_string(),
+            ))
+          }
+        })
+        .build(),
+    )
+    .plugin(assert_logs_plugin(vec![
+      "Privileged containers are not allowed",
+    ]))
+    .build();
+
+  let res = run_workflow(&astro_run).await;
+
+  let steps = &res.jobs.get("test").unwrap().steps;
+  assert_eq!(steps[0].state, WorkflowState::Skipped);
+  assert_eq!(steps[1].state, WorkflowState::Failed);
+  assert_eq!(res.state, WorkflowState::Failed);
+}
+
+#[astro_run_test::test]
+async fn test_before_run_job_and_workflow() {
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .plugin(
+      AstroRunPlugin::builder("rewrite")
+        .on_before_run_job_async(|job| async move {
+          let mut job = job;
+          job.steps.truncate(1);
+
+          Ok(job.into())
+        })
+        .build(),
+    )
+    .build();
+
+  let res = run_workflow(&astro_run).await;
+
+  assert_eq!(res.state, WorkflowState::Succeeded);
+  assert_eq!(res.jobs.get("test").unwrap().steps.len(), 1);
+
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .plugin(
+      AstroRunPlugin::builder("untrusted")
+        .on_before_run_job(|_| Ok(BeforeRun::Fail("Untrusted image".to_string())))
+        .build(),
+    )
+    .build();
+
+  let res = run_workflow(&astro_run).await;
+
+  assert_eq!(res.state, WorkflowState::Failed);
+  let job = res.jobs.get("test").unwrap();
+  assert_eq!(job.state, WorkflowState::Failed);
+  assert!(job
+    .steps
+    .iter()
+    .all(|step| step.state == WorkflowState::Skipped));
+
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .plugin(
+      AstroRunPlugin::builder("skip")
+        .on_before_run_workflow(|_| Ok(BeforeRun::Skip("Maintenance".to_string())))
+        .build(),
+    )
+    .build();
+
+  let res = run_workflow(&astro_run).await;
+
+  assert_eq!(res.state, WorkflowState::Skipped);
+  assert!(res.jobs.is_empty());
+}
@@ -0,0 +1,195 @@
+use astro_run::{
+  stream, AstroRun, Context, FileStateStore, RunQuery, RunResult, Runner, StateStore, StepId,
+  StepRunResult, Workflow, WorkflowId, WorkflowRunRecord, WorkflowState,
+};
+use std::path::PathBuf;
+
+struct TestRunner;
+
+#[astro_run::async_trait]
+impl Runner for TestRunner {
+  async fn run(&self, ctx: Context) -> astro_run::RunResponse {
+    let (tx, rx) = stream();
+
+    if ctx.command.run == "exit 1" {
+      tx.error(ctx.command.run);
+      tx.end(RunResult::Failed { exit_code: 1 });
+    } else {
+      tx.log(ctx.command.run);
+      tx.end(RunResult::Succeeded);
+    }
+
+    Ok(rx)
+  }
+}
+
+fn temp_dir() -> PathBuf {
+  std::env::temp_dir().join(format!(
+    "astro-run-state-store-{}",
+    std::time::SystemTime::now()
+      .duration_since(std::time::UNIX_EPOCH)
+      .unwrap()
+      .as_nanos()
+  ))
+}
+
+#[astro_run_test::test]
+async fn test_record_run() {
+  let dir = temp_dir();
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .state_store(FileStateStore::new(&dir))
+    .build();
+
+  let workflow = r#"
+name: Test
+jobs:
+  test:
+    steps:
+      - run: echo "Hello World"
+  fail:
+    steps:
+      - run: exit 1
+      - run: echo "Skipped"
+  "#;
+
+  let workflow = Workflow::builder()
+    .config(workflow)
+    .build(&astro_run)
+    .await
+    .unwrap();
+
+  let ctx = astro_run.execution_context().build();
+  let result = workflow.run(ctx).await;
+  assert_eq!(result.state, WorkflowState::Failed);
+
+  let state_store = astro_run.state_store().unwrap();
+  let record = state_store.get(&workflow.id).await.unwrap().unwrap();
+
+  assert_eq!(record.name, Some("Test".to_string()));
+  assert_eq!(record.state, WorkflowState::Failed);
+  assert!(record.started_at.is_some());
+  assert!(record.completed_at.is_some());
+  assert!(!record.interrupted);
+
+  let test = record.jobs.get("test").unwrap();
+  assert_eq!(test.state, WorkflowState::Succeeded);
+  assert_eq!(test.steps.len(), 1);
+
+  let fail = record.jobs.get("fail").unwrap();
+  assert_eq!(fail.state, WorkflowState::Failed);
+  assert_eq!(fail.steps[0].state, WorkflowState::Failed);
+  assert_eq!(fail.steps[0].exit_code, Some(1));
+  assert_eq!(fail.steps[1].state, WorkflowState::Skipped);
+
+  assert!(record.duration().is_some());
+  assert!(test.duration().is_some());
+  assert!(fail.steps[0].duration().is_some());
+
+  let page = astro_run
+    .query_runs(&RunQuery::new().state(WorkflowState::Failed))
+    .await
+    .unwrap();
+  assert_eq!(page.total, 1);
+  assert_eq!(page.runs[0].id, workflow.id);
+
+  let page = astro_run
+    .query_runs(&RunQuery::new().state(WorkflowState::Succeeded))
+    .await
+    .unwrap();
+  assert_eq!(page.total, 0);
+
+  let run = astro_run.get_run(&workflow.id).await.unwrap().unwrap();
+  assert_eq!(run.jobs.len(), 2);
+  assert!(astro_run
+    .get_run(&WorkflowId::new("missing"))
+    .await
+    .unwrap()
+    .is_none());
+
+  // Nothing to recover
+  assert!(astro_run
+    .recover_interrupted_runs()
+    .await
+    .unwrap()
+    .is_empty());
+
+  std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[astro_run_test::test]
+async fn test_state_store_not_configured() {
+  let astro_run = AstroRun::builder().runner(TestRunner).build();
+
+  let error = astro_run.query_runs(&RunQuery::new()).await.unwrap_err();
+  assert_eq!(
+    error,
+    astro_run::Error::init_error("State store is not configured")
+  );
+  assert!(astro_run
+    .recover_interrupted_runs()
+    .await
+    .unwrap()
+    .is_empty());
+}
+
+#[astro_run_test::test]
+async fn test_recover_interrupted_runs() {
+  let dir = temp_dir();
+  let state_store = FileStateStore::new(&dir);
+
+  // A run left behind by a crashed process
+  let mut record = WorkflowRunRecord::new(WorkflowId::new("interrupted"));
+  record.state = WorkflowState::InProgress;
+  record.started_at = Some(chrono::Utc::now());
+  let step_id = StepId::new("interrupted", "test", 0);
+  record.jobs.insert(
+    "test".to_string(),
+    astro_run::JobRunResult {
+      id: step_id.job_id(),
+      state: WorkflowState::InProgress,
+      started_at: Some(chrono::Utc::now()),
+      completed_at: None,
+      summary: None,
+      steps: vec![StepRunResult {
+        id: step_id,
+        state: WorkflowState::InProgress,
+        exit_code: None,
+        started_at: Some(chrono::Utc::now()),
+        completed_at: None,
+        summary: None,
+      }],
+    },
+  );
+  state_store.save(record).await.unwrap();
+
+  let mut completed = WorkflowRunRecord::new(WorkflowId::new("completed"));
+  completed.state = WorkflowState::Succeeded;
+  state_store.save(completed).await.unwrap();
+
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner)
+    .state_store(state_store)
+    .build();
+
+  let interrupted = astro_run.recover_interrupted_runs().await.unwrap();
+  assert_eq!(interrupted.len(), 1);
+  assert_eq!(interrupted[0].id, WorkflowId::new("interrupted"));
+
+  let record = astro_run
+    .state_store()
+    .unwrap()
+    .get(&WorkflowId::new("interrupted"))
+    .await
+    .unwrap()
+    .unwrap();
+  assert!(record.interrupted);
+  assert_eq!(record.state, WorkflowState::Failed);
+  assert!(record.completed_at.is_some());
+
+  let job = record.jobs.get("test").unwrap();
+  assert_eq!(job.state, WorkflowState::Failed);
+  assert_eq!(job.steps[0].state, WorkflowState::Failed);
+
+  std::fs::remove_dir_all(dir).unwrap();
+}
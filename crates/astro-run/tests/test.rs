@@ -1,9 +1,10 @@
 use astro_run::{
-  stream, Action, ActionSteps, AstroRun, AstroRunPlugin, Context, Error, HookBeforeRunStepResult,
-  RunResult, Runner, Step, TriggerEvent, UserActionStep, UserCommandStep, UserStep, Workflow,
-  WorkflowState,
+  stream, Action, ActionSteps, AstroRun, AstroRunPlugin, ConditionPayload, Context, Error,
+  EventCondition, EventMatcher, HookBeforeRunStepResult, RunResult, Runner, Step, TriggerEvent,
+  UserActionStep, UserCommandStep, UserStep, Workflow, WorkflowLogType, WorkflowState,
 };
 use parking_lot::Mutex;
+use std::collections::HashMap;
 
 struct TestRunner;
 
@@ -641,3 +642,263 @@ jobs:
 
   Ok(())
 }
+
+#[astro_run_test::test]
+async fn test_custom_event_condition() {
+  struct ProductionOnly;
+
+  impl EventMatcher for ProductionOnly {
+    fn is_match(&self, condition: &EventCondition, payload: &ConditionPayload) -> bool {
+      payload.field("environment").as_deref() == Some("production") && condition.is_match(payload)
+    }
+  }
+
+  let workflow = r#"
+on:
+  release:
+    ref_name: [refs/tags/v*]
+  deploy_request:
+
+jobs:
+  test:
+    steps:
+      - run: Hello World
+  "#;
+
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner::new())
+    .event_matcher("deploy_request", ProductionOnly)
+    .build();
+
+  let workflow = Workflow::builder()
+    .config(workflow)
+    .build(&astro_run)
+    .await
+    .unwrap();
+
+  let run = |event: TriggerEvent| {
+    let ctx = astro_run.execution_context().event(event).build();
+    let workflow = workflow.clone();
+
+    async move { workflow.run(ctx).await.state }
+  };
+
+  let state = run(TriggerEvent {
+    event: "release".to_string(),
+    ref_name: "refs/tags/v1.0.0".to_string(),
+    ..Default::default()
+  })
+  .await;
+  assert_eq!(state, WorkflowState::Succeeded);
+
+  let state = run(TriggerEvent {
+    event: "release".to_string(),
+    ref_name: "refs/heads/main".to_string(),
+    ..Default::default()
+  })
+  .await;
+  assert_eq!(state, WorkflowState::Skipped);
+
+  let state = run(TriggerEvent {
+    event: "deploy_request".to_string(),
+    payload: Some(serde_json::json!({ "environment": "production" })),
+    ..Default::default()
+  })
+  .await;
+  assert_eq!(state, WorkflowState::Succeeded);
+
+  let state = run(TriggerEvent {
+    event: "deploy_request".to_string(),
+    payload: Some(serde_json::json!({ "environment": "staging" })),
+    ..Default::default()
+  })
+  .await;
+  assert_eq!(state, WorkflowState::Skipped);
+
+  let state = run(TriggerEvent {
+    event: "schedule".to_string(),
+    ..Default::default()
+  })
+  .await;
+  assert_eq!(state, WorkflowState::Skipped);
+}
+
+#[astro_run_test::test]
+async fn test_workflow_dispatch() {
+  let workflow = r#"
+on:
+  workflow_dispatch:
+    inputs:
+      version:
+        required: true
+      environment:
+        type: choice
+        options: [staging, production]
+        default: staging
+
+jobs:
+  test:
+    steps:
+      - run: deploy ${{ inputs.version }} to ${{ inputs.environment }}
+  "#;
+
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner::new())
+    .plugin(
+      AstroRunPlugin::builder("assert-inputs")
+        .on_run_step(|event| {
+          let step = event.source;
+          assert_eq!(
+            step.environments.get("INPUT_VERSION"),
+            Some(&"1.2.0".into())
+          );
+          assert_eq!(
+            step.environments.get("INPUT_ENVIRONMENT"),
+            Some(&"staging".into())
+          );
+          // Inputs are only passed through the environment
+          assert_eq!(step.run, "deploy ${INPUT_VERSION} to ${INPUT_ENVIRONMENT}");
+
+          Ok(())
+        })
+        .build(),
+    )
+    .build();
+
+  let workflow = Workflow::builder()
+    .config(workflow)
+    .build(&astro_run)
+    .await
+    .unwrap();
+
+  let res = astro_run
+    .dispatch(
+      &workflow,
+      TriggerEvent::default(),
+      HashMap::from([("version".to_string(), "1.2.0".into())]),
+    )
+    .await
+    .unwrap();
+
+  assert_eq!(res.state, WorkflowState::Succeeded);
+  assert_eq!(
+    res.jobs.get("test").unwrap().state,
+    WorkflowState::Succeeded
+  );
+
+  let error = astro_run
+    .dispatch(&workflow, TriggerEvent::default(), HashMap::new())
+    .await
+    .unwrap_err();
+
+  assert_eq!(
+    error,
+    Error::workflow_config_error("Input `version` is required")
+  );
+}
+
+#[astro_run_test::test]
+async fn test_workflow_commands() {
+  let workflow = r#"
+jobs:
+  test:
+    steps:
+      - run: "::group::Build"
+      - run: "::warning file=src/a.rs,line=3::Unused variable"
+      - run: "::endgroup::"
+  "#;
+
+  let logs = std::sync::Arc::new(Mutex::new(vec![]));
+  let collected = logs.clone();
+
+  let astro_run = AstroRun::builder()
+    .runner(TestRunner::new())
+    .plugin(
+      AstroRunPlugin::builder("collect-logs")
+        .on_log(move |log| {
+          collected.lock().push(log);
+          Ok(())
+        })
+        .build(),
+    )
+    .build();
+
+  let workflow = Workflow::builder()
+    .config(workflow)
+    .build(&astro_run)
+    .await
+    .unwrap();
+
+  let res = workflow.run(astro_run.execution_context().build()).await;
+  assert_eq!(res.state, WorkflowState::Succeeded);
+
+  let logs = logs.lock();
+  assert_eq!(logs.len(), 3);
+  assert_eq!(logs[0].log_type, WorkflowLogType::GroupStart);
+  assert_eq!(logs[0].message, "Build");
+  assert_eq!(logs[1].log_type, WorkflowLogType::Warning);
+  assert_eq!(logs[1].message, "Unused variable");
+  let annotation = logs[1].annotation.clone().unwrap();
+  assert_eq!(annotation.file, Some("src/a.rs".to_string()));
+  assert_eq!(annotation.line, Some(3));
+  assert_eq!(logs[2].log_type, WorkflowLogType::GroupEnd);
+}
+
+struct TimedRunner;
+
+#[astro_run::async_trait]
+impl Runner for TimedRunner {
+  async fn run(&self, ctx: Context) -> astro_run::RunResponse {
+    let (tx, rx) = stream();
+
+    let captured_at = chrono::DateTime::parse_from_rfc3339("2023-01-01T00:00:00Z")
+      .unwrap()
+      .with_timezone(&chrono::Utc);
+    tx.send(astro_run::Log::log(ctx.command.run).time(captured_at));
+    tx.error("Error");
+    tx.end(RunResult::Succeeded);
+
+    Ok(rx)
+  }
+}
+
+#[astro_run_test::test]
+async fn test_log_time_and_sequence() {
+  let workflow = r#"
+jobs:
+  test:
+    steps:
+      - run: Hello World
+  "#;
+
+  let logs = std::sync::Arc::new(Mutex::new(vec![]));
+  let collected = logs.clone();
+
+  let astro_run = AstroRun::builder()
+    .runner(TimedRunner)
+    .plugin(
+      AstroRunPlugin::builder("collect-logs")
+        .on_log(move |log| {
+          collected.lock().push(log);
+          Ok(())
+        })
+        .build(),
+    )
+    .build();
+
+  let workflow = Workflow::builder()
+    .config(workflow)
+    .build(&astro_run)
+    .await
+    .unwrap();
+
+  workflow.run(astro_run.execution_context().build()).await;
+
+  let logs = logs.lock();
+  assert_eq!(logs.len(), 2);
+  // The time the runner captured the line is kept
+  assert_eq!(logs[0].time.to_rfc3339(), "2023-01-01T00:00:00+00:00");
+  assert_eq!(logs[0].sequence, 0);
+  assert!(logs[1].time > logs[0].time);
+  assert_eq!(logs[1].sequence, 1);
+}
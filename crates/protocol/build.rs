@@ -9,7 +9,7 @@ fn main() {
       rpc GetRunnerMetadata(crate::Empty) returns (astro_run_scheduler::RunnerMetadata) {}
       rpc Run(astro_run::Context) returns (stream crate::RunResponse) {}
       rpc SendEvent(crate::ProtocolEvent) returns (crate::Empty) {}
-      rpc CallBeforeRunStepHook(astro_run::Command) returns (astro_run::Command) {}
+      rpc CallBeforeRunStepHook(astro_run::Command) returns (crate::BeforeRunStepResponse) {}
     }
   };
 
@@ -29,6 +29,11 @@ pub enum RunResponse {
     step_id: astro_run::StepId,
     result: astro_run::RunResult,
   },
+  /// Sent before `Result` when the step wrote a summary
+  Summary {
+    step_id: astro_run::StepId,
+    summary: String,
+  },
 }
 
 impl RunResponse {
@@ -39,6 +44,38 @@ impl RunResponse {
   pub fn result(step_id: astro_run::StepId, result: astro_run::RunResult) -> Self {
     Self::Result { step_id, result }
   }
+
+  pub fn summary(step_id: astro_run::StepId, summary: String) -> Self {
+    Self::Summary { step_id, summary }
+  }
+}
+
+/// Decision of the `on_before_run_step` hooks of a remote runner
+#[derive(Debug, Serialize, Deserialize)]
+pub enum BeforeRunStepResponse {
+  Continue(Box<astro_run::Command>),
+  Skip(String),
+  Fail(String),
+}
+
+impl From<astro_run::BeforeRun<astro_run::Step>> for BeforeRunStepResponse {
+  fn from(decision: astro_run::BeforeRun<astro_run::Step>) -> Self {
+    match decision {
+      astro_run::BeforeRun::Continue(step) => Self::Continue(Box::new(step.into())),
+      astro_run::BeforeRun::Skip(reason) => Self::Skip(reason),
+      astro_run::BeforeRun::Fail(reason) => Self::Fail(reason),
+    }
+  }
+}
+
+impl From<BeforeRunStepResponse> for astro_run::BeforeRun<astro_run::Step> {
+  fn from(response: BeforeRunStepResponse) -> Self {
+    match response {
+      BeforeRunStepResponse::Continue(command) => Self::Continue((*command).into()),
+      BeforeRunStepResponse::Skip(reason) => Self::Skip(reason),
+      BeforeRunStepResponse::Fail(reason) => Self::Fail(reason),
+    }
+  }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -160,7 +160,10 @@ pub mod remote_runner_client {
         pub async fn call_before_run_step_hook(
             &mut self,
             request: impl tonic::IntoRequest<astro_run::Command>,
-        ) -> std::result::Result<tonic::Response<astro_run::Command>, tonic::Status> {
+        ) -> std::result::Result<
+            tonic::Response<crate::BeforeRunStepResponse>,
+            tonic::Status,
+        > {
             self.inner
                 .ready()
                 .await
@@ -217,7 +220,10 @@ pub mod remote_runner_server {
         async fn call_before_run_step_hook(
             &self,
             request: tonic::Request<astro_run::Command>,
-        ) -> std::result::Result<tonic::Response<astro_run::Command>, tonic::Status>;
+        ) -> std::result::Result<
+            tonic::Response<crate::BeforeRunStepResponse>,
+            tonic::Status,
+        >;
     }
     #[derive(Debug)]
     pub struct RemoteRunnerServer<T: RemoteRunner> {
@@ -436,7 +442,7 @@ pub mod remote_runner_server {
                     struct CallBeforeRunStepHookSvc<T: RemoteRunner>(pub Arc<T>);
                     impl<T: RemoteRunner> tonic::server::UnaryService<astro_run::Command>
                     for CallBeforeRunStepHookSvc<T> {
-                        type Response = astro_run::Command;
+                        type Response = crate::BeforeRunStepResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
@@ -1,8 +1,8 @@
-use astro_run::{Context, Error, HookBeforeRunStepResult, HookNoopResult, Result};
+use astro_run::{BeforeRun, Context, Error, HookBeforeRunStepResult, HookNoopResult, Result};
 use astro_run_protocol::remote_runner::RemoteRunnerClient;
 use astro_run_protocol::tonic;
 use astro_run_protocol::RunnerMetadata;
-use astro_run_protocol::{ProtocolEvent, RunResponse};
+use astro_run_protocol::{BeforeRunStepResponse, ProtocolEvent, RunResponse};
 use astro_run_scheduler::Scheduler;
 use parking_lot::Mutex;
 use std::{collections::HashMap, sync::Arc};
@@ -53,7 +53,10 @@ impl astro_run::Runner for AstroRunRemoteRunnerClient {
         log::error!("Failed to run: {}", err);
       }
       if !sender.is_ended() {
-        sender.error("Failed to run");
+        // Waits for room after the logs of the step, which fill the buffer
+        sender
+          .send_async(astro_run::Log::error("Failed to run"))
+          .await;
         sender.end(astro_run::RunResult::Failed { exit_code: 1 });
       }
     });
@@ -144,12 +147,17 @@ impl astro_run::Runner for AstroRunRemoteRunnerClient {
   async fn on_before_run_step(&self, step: astro_run::Step) -> HookBeforeRunStepResult {
     let mut clients = self.clients.lock().clone();
 
-    let mut command = step.into();
+    let mut command: astro_run::Command = step.into();
 
     for client in clients.values_mut() {
       match client.client.call_before_run_step_hook(command).await {
-        Ok(response) => {
-          command = response.into_inner();
+        Ok(response) => match response.into_inner() {
+          BeforeRunStepResponse::Continue(step) => command = *step,
+          decision => return Ok(decision.into()),
+        },
+        // Runners without `BeforeRunStepResponse` abort the call to skip or fail the step
+        Err(status) if status.code() == tonic::Code::Aborted => {
+          return Ok(BeforeRun::Fail(status.message().to_string()))
         }
         Err(err) => {
           log::error!("Failed to call before run step hook: {}", err);
@@ -158,7 +166,7 @@ impl astro_run::Runner for AstroRunRemoteRunnerClient {
       };
     }
 
-    Ok(command.into())
+    Ok(BeforeRun::Continue(command.into()))
   }
 }
 
@@ -260,11 +268,11 @@ impl AstroRunRemoteRunnerClient {
             Ok(response) => {
                 match response {
                   RunResponse::Log { step_id: _, log } => {
-                    if log.is_error() {
-                      sender.error(log.message);
-                    } else {
-                      sender.log(log.message);
-                    }
+                    // Stops reading the response while the orchestrator catches up
+                    sender.send_async(log).await;
+                  }
+                  RunResponse::Summary { step_id: _, summary } => {
+                    sender.summary(summary);
                   }
                   RunResponse::Result { step_id: _, result } => {
                     sender.end(result);
@@ -274,7 +282,7 @@ impl AstroRunRemoteRunnerClient {
             Err(e) => {
               let error = format!("Failed to run: {}", e);
 
-              sender.error(error.clone());
+              sender.send_async(astro_run::Log::error(error.clone())).await;
               sender.end(astro_run::RunResult::Failed { exit_code: 1 });
 
               return Err(Error::internal_runtime_error(error));
@@ -2,12 +2,16 @@ use astro_run::{Context, Error, Plugin, PluginDriver, Runner, SharedPluginDriver
 use astro_run_protocol::remote_runner::RemoteRunnerExt;
 use astro_run_protocol::remote_runner::RemoteRunnerServer;
 use astro_run_protocol::tonic;
-use astro_run_protocol::{ProtocolEvent, RunResponse, RunnerMetadata};
+use astro_run_protocol::{BeforeRunStepResponse, ProtocolEvent, RunResponse, RunnerMetadata};
 use parking_lot::Mutex;
 use std::{collections::HashMap, env, sync::Arc};
 use tokio::sync::mpsc;
 use tokio_stream::{wrappers::ReceiverStream, StreamExt};
 
+/// How long the Docker Engine API is waited for when checking docker
+#[cfg(unix)]
+const PING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 pub struct AstroRunRemoteRunnerServer {
   id: String,
   max_runs: i32,
@@ -59,6 +63,15 @@ impl AstroRunRemoteRunnerServer {
         "Cannot get result from runner".to_string(),
       ))?;
 
+      if let Some(summary) = stream.summary() {
+        if let Err(err) = sender
+          .send(Ok(RunResponse::summary(id.clone(), summary)))
+          .await
+        {
+          log::error!("Cannot send summary to client: {}", err);
+        }
+      }
+
       if let Err(err) = sender
         .send(
           Ok(RunResponse::result(id, result))
@@ -146,21 +159,24 @@ impl RemoteRunnerExt for AstroRunRemoteRunnerServer {
         }
       }
       ProtocolEvent::RunStep(event) => {
-        self.plugin_driver.on_run_step(event.clone()).await;
+        // Already logged by the plugin driver
+        self.plugin_driver.on_run_step(event.clone()).await.ok();
 
         if let Err(err) = self.runner.on_run_step(event).await {
           log::error!("Failed to handle run step event: {}", err);
         }
       }
       ProtocolEvent::RunJob(event) => {
-        self.plugin_driver.on_run_job(event.clone()).await;
+        // Already logged by the plugin driver
+        self.plugin_driver.on_run_job(event.clone()).await.ok();
 
         if let Err(err) = self.runner.on_run_job(event).await {
           log::error!("Failed to handle run job event: {}", err);
         }
       }
       ProtocolEvent::RunWorkflow(event) => {
-        self.plugin_driver.on_run_workflow(event.clone()).await;
+        // Already logged by the plugin driver
+        self.plugin_driver.on_run_workflow(event.clone()).await.ok();
 
         if let Err(err) = self.runner.on_run_workflow(event).await {
           log::error!("Failed to handle run workflow event: {}", err);
@@ -208,16 +224,25 @@ impl RemoteRunnerExt for AstroRunRemoteRunnerServer {
   async fn call_before_run_step_hook(
     &self,
     req: tonic::Request<astro_run::Command>,
-  ) -> Result<tonic::Response<astro_run_protocol::Command>, tonic::Status> {
+  ) -> Result<tonic::Response<BeforeRunStepResponse>, tonic::Status> {
     let command = req.into_inner();
     let step: astro_run::Step = command.into();
 
     // Call before run step hook
-    let step = self.plugin_driver.on_before_run_step(step).await;
+    let step = match self.plugin_driver.on_before_run_step(step).await {
+      Ok(astro_run::BeforeRun::Continue(step)) => step,
+      Ok(decision) => return Ok(tonic::Response::new(decision.into())),
+      Err(err) => {
+        return Err(tonic::Status::internal(format!(
+          "Failed to call before run step hook: {}",
+          err
+        )))
+      }
+    };
 
     // Call runner before run step hook
-    let step = match self.runner.on_before_run_step(step).await {
-      Ok(step) => step,
+    let decision = match self.runner.on_before_run_step(step).await {
+      Ok(decision) => decision,
       Err(err) => {
         return Err(tonic::Status::internal(format!(
           "Failed to call before run step hook: {}",
@@ -226,7 +251,7 @@ impl RemoteRunnerExt for AstroRunRemoteRunnerServer {
       }
     };
 
-    Ok(tonic::Response::new(step.into()))
+    Ok(tonic::Response::new(decision.into()))
   }
 }
 
@@ -235,6 +260,8 @@ pub struct AstroRunRemoteRunnerServerBuilder {
   runner: Option<Arc<Box<dyn Runner>>>,
   max_runs: i32,
   support_docker: Option<bool>,
+  #[cfg(unix)]
+  docker_engine: Option<std::path::PathBuf>,
   support_host: bool,
   plugins: Vec<Box<dyn Plugin>>,
 }
@@ -246,6 +273,8 @@ impl AstroRunRemoteRunnerServerBuilder {
       runner: None,
       max_runs: 5,
       support_docker: None,
+      #[cfg(unix)]
+      docker_engine: None,
       support_host: true,
       plugins: vec![],
     }
@@ -266,6 +295,15 @@ impl AstroRunRemoteRunnerServerBuilder {
     self
   }
 
+  /// Checks docker by pinging the Docker Engine API on the Unix socket instead of running
+  /// the docker CLI, for runners built with `AstroRunnerBuilder::docker_engine`.
+  /// The engine is pinged once by `build`. Ignored if `support_docker` is set
+  #[cfg(unix)]
+  pub fn docker_engine(mut self, socket: impl Into<std::path::PathBuf>) -> Self {
+    self.docker_engine = Some(socket.into());
+    self
+  }
+
   pub fn support_host(mut self, support_host: bool) -> Self {
     self.support_host = support_host;
     self
@@ -296,13 +334,20 @@ impl AstroRunRemoteRunnerServerBuilder {
     let id = self.id.ok_or_else(|| Error::init_error("Id is not set"))?;
 
     let support_docker = self.support_docker.unwrap_or_else(|| {
+      #[cfg(unix)]
+      if let Some(socket) = &self.docker_engine {
+        log::trace!("Support docker is not set, Pinging the docker engine");
+
+        return ping_docker_engine(socket);
+      }
+
       log::trace!("Support docker is not set, Checking if docker is installed and running");
 
       // Check if docker is installed and running
       std::process::Command::new("docker")
         .arg("ps")
         .status()
-        .map_or(false, |status| status.success())
+        .is_ok_and(|status| status.success())
     });
 
     Ok(AstroRunRemoteRunnerServer {
@@ -316,3 +361,28 @@ impl AstroRunRemoteRunnerServerBuilder {
     })
   }
 }
+
+/// Whether the Docker Engine API answers `/_ping` on the Unix socket
+#[cfg(unix)]
+fn ping_docker_engine(socket: &std::path::Path) -> bool {
+  use std::io::{Read, Write};
+
+  let ping = || -> std::io::Result<bool> {
+    let mut stream = std::os::unix::net::UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(PING_TIMEOUT))?;
+    stream.set_write_timeout(Some(PING_TIMEOUT))?;
+    stream.write_all(b"GET /_ping HTTP/1.0\r\nHost: docker\r\n\r\n")?;
+
+    // Only the status line is needed, such as `HTTP/1.1 200 OK`
+    let mut response = vec![];
+    stream.take(64).read_to_end(&mut response)?;
+    let response = String::from_utf8_lossy(&response);
+
+    Ok(response.split_whitespace().nth(1) == Some("200"))
+  };
+
+  ping().unwrap_or_else(|err| {
+    log::trace!("Failed to ping the docker engine: {}", err);
+    false
+  })
+}
@@ -219,7 +219,7 @@ async fn test_protocol() -> Result<()> {
 
             step.run = format!("echo {}", step.run);
 
-            Ok(step)
+            Ok(step.into())
           })
           .build(),
       )
@@ -1,7 +1,7 @@
 use astro_run::typetag;
 use astro_run::{
-  stream, AstroRun, AstroRunPlugin, Context, Error, HookNoopResult, JobRunResult, Result,
-  RunJobEvent, RunResult, RunStepEvent, RunWorkflowEvent, Runner, StepRunResult, Workflow,
+  stream, AstroRun, AstroRunPlugin, BeforeRun, Context, Error, HookNoopResult, JobRunResult,
+  Result, RunJobEvent, RunResult, RunStepEvent, RunWorkflowEvent, Runner, StepRunResult, Workflow,
   WorkflowLog, WorkflowRunResult, WorkflowState, WorkflowStateEvent,
 };
 use astro_run_remote_runner::{
@@ -415,6 +415,121 @@ async fn test_remote_runner_workflow_payload() -> Result<()> {
   Ok(())
 }
 
+#[astro_run_test::test]
+async fn test_remote_before_run_step_decisions() -> Result<()> {
+  let (oneshot_tx, rx) = tokio::sync::oneshot::channel();
+
+  let client_thread_handle = tokio::spawn(async {
+    let client_runner = AstroRunRemoteRunnerClient::builder()
+      .scheduler(DefaultScheduler::new())
+      .build()
+      .unwrap();
+
+    let handle = tokio::task::spawn({
+      let mut client_runner = client_runner.clone();
+
+      async move {
+        rx.await.unwrap();
+
+        client_runner
+          .start(vec!["http://127.0.0.1:5338"])
+          .await
+          .unwrap();
+      }
+    });
+
+    // Wait for server to start and listen for connections
+    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+
+    let completed_steps = std::sync::Arc::new(Mutex::new(vec![]));
+    let astro_run = AstroRun::builder()
+      .plugin(assert_logs_plugin(vec!["Not allowed"]))
+      .plugin(
+        AstroRunPlugin::builder("completed-steps")
+          .on_step_completed({
+            let completed_steps = completed_steps.clone();
+            move |result| {
+              completed_steps.lock().push(result.state);
+              Ok(())
+            }
+          })
+          .build(),
+      )
+      .runner(client_runner)
+      .build();
+
+    let workflow = format!(
+      r#"
+    jobs:
+      test:
+        steps:
+          - container: host/{os}
+            run: Skip me
+          - container: host/{os}
+            run: Fail me
+      "#,
+      os = std::env::consts::OS,
+    );
+
+    let workflow = Workflow::builder()
+      .config(workflow)
+      .build(&astro_run)
+      .await
+      .unwrap();
+
+    let res = workflow.run(astro_run.execution_context().build()).await;
+
+    let steps = &res.jobs.get("test").unwrap().steps;
+    assert_eq!(steps[0].state, WorkflowState::Skipped);
+    assert_eq!(steps[1].state, WorkflowState::Failed);
+    assert_eq!(res.state, WorkflowState::Failed);
+    assert_eq!(
+      *completed_steps.lock(),
+      vec![WorkflowState::Skipped, WorkflowState::Failed]
+    );
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    handle.abort();
+  });
+
+  let server_thread_handle = tokio::spawn(async {
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+
+    let runner_server = AstroRunRemoteRunnerServer::builder()
+      .id("test-runner")
+      .runner(TestRunner::new())
+      .plugin(
+        AstroRunPlugin::builder("policy")
+          .on_before_run_step(|step| {
+            if step.run == "Skip me" {
+              Ok(BeforeRun::Skip("Not needed".to_string()))
+            } else {
+              Ok(BeforeRun::Fail("Not allowed".to_string()))
+            }
+          })
+          .on_workflow_completed(move |_| {
+            tx.try_send(()).unwrap();
+            Ok(())
+          })
+          .build(),
+      )
+      .build()
+      .unwrap();
+
+    oneshot_tx.send(()).unwrap();
+
+    tokio::select! {
+      _ = rx.recv() => {}
+      _ = runner_server.serve("127.0.0.1:5338") => {}
+    }
+  });
+
+  tokio::try_join!(server_thread_handle, client_thread_handle).unwrap();
+
+  Ok(())
+}
+
 #[astro_run_test::test]
 fn test_build_server_error() {
   let error = AstroRunRemoteRunnerServer::builder().build().err();
@@ -429,3 +544,57 @@ fn test_build_server_error() {
 
   assert_eq!(error.unwrap(), Error::init_error("Id is not set"));
 }
+
+#[cfg(unix)]
+#[astro_run_test::test]
+async fn test_docker_engine_support() {
+  use astro_run_protocol::{remote_runner::RemoteRunnerExt, tonic, Empty};
+
+  let server = AstroRunRemoteRunnerServer::builder()
+    .id("test-runner")
+    .runner(TestRunner::new())
+    .docker_engine("/tmp/astro-run-missing-docker.sock")
+    .build()
+    .unwrap();
+
+  // Pinged through the engine instead of the docker CLI
+  let metadata = server
+    .get_runner_metadata(tonic::Request::new(Empty {}))
+    .await
+    .unwrap()
+    .into_inner();
+  assert!(!metadata.support_docker);
+
+  let socket = std::env::temp_dir().join(format!("astro-run-docker-{}.sock", std::process::id()));
+  std::fs::remove_file(&socket).ok();
+  let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+  let engine = std::thread::spawn(move || {
+    use std::io::{Read, Write};
+
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut request = [0; 1024];
+    let len = stream.read(&mut request).unwrap();
+    assert!(String::from_utf8_lossy(&request[..len]).starts_with("GET /_ping "));
+    stream
+      .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nOK")
+      .unwrap();
+  });
+
+  let server = AstroRunRemoteRunnerServer::builder()
+    .id("test-runner")
+    .runner(TestRunner::new())
+    .docker_engine(&socket)
+    .build()
+    .unwrap();
+  engine.join().unwrap();
+
+  // Answered from the result of the ping in `build`
+  let metadata = server
+    .get_runner_metadata(tonic::Request::new(Empty {}))
+    .await
+    .unwrap()
+    .into_inner();
+  assert!(metadata.support_docker);
+
+  std::fs::remove_file(&socket).ok();
+}
@@ -1,16 +1,22 @@
+#[cfg(unix)]
+use crate::{engine::EngineClient, executors::EngineExecutor};
 use crate::{
-  executors::{DockerExecutor, Executor, HostExecutor},
-  Plugin, PluginDriver, SharedPluginDriver,
+  executors::{DockerExecutor, Executor, HostExecutor, JobContainer, JobContainerExecutor},
+  registry::{ImagePuller, RegistryCredentials, DEFAULT_PULL_RETRIES},
+  services::JobServices,
+  OutputOptions, Plugin, PluginDriver, SharedPluginDriver,
 };
 use astro_run::{
-  stream, Context, Error, HookNoopResult, Result, RunResponse, RunResult, Runner, TriggerEvent,
-  WorkflowId,
+  bounded_stream, Context, Error, HookNoopResult, JobId, Log, OverflowPolicy, Result, RunResponse,
+  RunResult, Runner, TriggerEvent, WorkflowId, DEFAULT_STREAM_CAPACITY,
 };
 use parking_lot::Mutex;
 use std::{collections::HashMap, env, fs, path::PathBuf, sync::Arc};
 
 struct RunnerState {
   workflow_events: HashMap<WorkflowId, TriggerEvent>,
+  job_containers: HashMap<JobId, JobContainer>,
+  job_services: HashMap<JobId, JobServices>,
 }
 
 #[derive(Clone)]
@@ -18,6 +24,13 @@ pub struct AstroRunner {
   working_directory: PathBuf,
   state: Arc<Mutex<RunnerState>>,
   plugin_driver: SharedPluginDriver,
+  log_capacity: usize,
+  log_overflow: OverflowPolicy,
+  output: OutputOptions,
+  job_container: bool,
+  puller: ImagePuller,
+  #[cfg(unix)]
+  engine: Option<EngineClient>,
 }
 
 impl AstroRunner {
@@ -28,6 +41,46 @@ impl AstroRunner {
 
 #[astro_run::async_trait]
 impl Runner for AstroRunner {
+  async fn on_run_job(&self, event: astro_run::RunJobEvent) -> HookNoopResult {
+    let job = event.source;
+    let mut state = self.state.lock();
+
+    if !job.services.is_empty() {
+      state
+        .job_services
+        .insert(job.id.clone(), JobServices::new(&job.id, job.services));
+    }
+
+    if self.job_container {
+      state
+        .job_containers
+        .insert(job.id, JobContainer::new(job.container));
+    }
+
+    Ok(())
+  }
+
+  async fn on_job_completed(&self, result: astro_run::JobRunResult) -> HookNoopResult {
+    let (container, services) = {
+      let mut state = self.state.lock();
+      (
+        state.job_containers.remove(&result.id),
+        state.job_services.remove(&result.id),
+      )
+    };
+
+    if let Some(container) = container {
+      container.stop().await;
+    }
+
+    // Removed after the job container, which is connected to their network
+    if let Some(services) = services {
+      services.stop().await;
+    }
+
+    Ok(())
+  }
+
   async fn on_workflow_completed(&self, result: astro_run::WorkflowRunResult) -> HookNoopResult {
     if let Err(err) = self.cleanup_workflow_working_directory(result) {
       log::error!("AstroRunner: cleanup error: {}", err);
@@ -37,11 +90,17 @@ impl Runner for AstroRunner {
   }
 
   async fn run(&self, ctx: Context) -> RunResponse {
-    let (sender, receiver) = stream();
+    let (sender, receiver) = bounded_stream(self.log_capacity, self.log_overflow);
 
     let ctx = self.plugin_driver.on_before_run(ctx).await;
 
     let executor = self.create_executor(&ctx);
+    let services = self
+      .state
+      .lock()
+      .job_services
+      .get(&ctx.command.id.job_id())
+      .cloned();
 
     let event = ctx.event.clone();
     if let Some(event) = &ctx.event {
@@ -53,9 +112,41 @@ impl Runner for AstroRunner {
     }
 
     let plugins = Arc::clone(&self.plugin_driver);
+    let puller = self.puller.clone();
 
     tokio::spawn(async move {
-      if let Err(err) = executor.execute(ctx.clone(), sender.clone(), event).await {
+      let res = match &services {
+        Some(services) => tokio::select! {
+          res = services.start(&puller, &sender) => res,
+          signal = ctx.signal.recv() => {
+            log::trace!("Step received signal while starting the job services: {:?}", signal);
+            // The job may not complete to remove them
+            services.stop().await;
+
+            if let astro_run::Signal::Cancel = signal {
+              sender.cancelled();
+            } else {
+              sender.timeout();
+            }
+
+            plugins.on_after_run(ctx).await;
+            return;
+          }
+        },
+        None => Ok(()),
+      };
+      if let Err(err) = &res {
+        sender
+          .send_async(Log::error(format!(
+            "Failed to start the job services: {}",
+            err
+          )))
+          .await;
+      }
+
+      if let Err(err) = res {
+        log::error!("AstroRunner: start services error: {}", err);
+      } else if let Err(err) = executor.execute(ctx.clone(), sender.clone(), event).await {
         log::error!("AstroRunner: execute error: {}", err);
       }
 
@@ -84,14 +175,59 @@ impl AstroRunner {
       if container.name == host_name_with_arch || container.name == host_name {
         let executor = HostExecutor {
           working_directory: self.working_directory.clone(),
+          output: self.output.clone(),
+        };
+
+        return Box::new(executor);
+      }
+    }
+
+    let (job_container, network) = {
+      let state = self.state.lock();
+      let job_id = ctx.command.id.job_id();
+
+      (
+        state.job_containers.get(&job_id).cloned(),
+        state
+          .job_services
+          .get(&job_id)
+          .map(|services| services.network.clone()),
+      )
+    };
+
+    if let Some(container) = job_container {
+      // Steps with their own container don't run in the job container
+      if container.options == ctx.command.container {
+        let executor = JobContainerExecutor {
+          working_directory: self.working_directory.clone(),
+          output: self.output.clone(),
+          container,
+          network,
+          puller: self.puller.clone(),
         };
 
         return Box::new(executor);
       }
     }
 
+    #[cfg(unix)]
+    if let Some(engine) = &self.engine {
+      let executor = EngineExecutor {
+        working_directory: self.working_directory.clone(),
+        output: self.output.clone(),
+        network,
+        puller: self.puller.clone(),
+        engine: engine.clone(),
+      };
+
+      return Box::new(executor);
+    }
+
     let executor = DockerExecutor {
       working_directory: self.working_directory.clone(),
+      output: self.output.clone(),
+      network,
+      puller: self.puller.clone(),
     };
 
     Box::new(executor)
@@ -120,6 +256,14 @@ impl AstroRunner {
 pub struct AstroRunnerBuilder {
   working_directory: Option<PathBuf>,
   plugins: Vec<Box<dyn Plugin>>,
+  log_buffer: Option<(usize, OverflowPolicy)>,
+  output: OutputOptions,
+  job_container: bool,
+  docker_config: Option<PathBuf>,
+  registry_credentials: HashMap<String, RegistryCredentials>,
+  pull_retries: Option<u32>,
+  #[cfg(unix)]
+  docker_engine: Option<PathBuf>,
 }
 
 impl AstroRunnerBuilder {
@@ -138,6 +282,73 @@ impl AstroRunnerBuilder {
     self
   }
 
+  /// Number of log lines buffered for each step, and what happens when the buffer is full.
+  /// Defaults to 1024 lines with backpressure on the command output
+  pub fn log_buffer(mut self, capacity: usize, overflow: OverflowPolicy) -> Self {
+    self.log_buffer = Some((capacity, overflow));
+    self
+  }
+
+  /// Removes ANSI colors and other escape sequences from the step logs. They are kept by default
+  pub fn strip_ansi(mut self, strip_ansi: bool) -> Self {
+    self.output.strip_ansi = strip_ansi;
+    self
+  }
+
+  /// Longest log line in bytes, longer lines are truncated. Defaults to 64 KiB
+  pub fn max_line_length(mut self, max_line_length: usize) -> Self {
+    self.output.max_line_length = max_line_length;
+    self
+  }
+
+  /// Runs all the steps of a job in one container, which is started by the first step and
+  /// removed when the job completes. Steps with a container other than the job's still run
+  /// in their own container
+  pub fn job_container(mut self, job_container: bool) -> Self {
+    self.job_container = job_container;
+    self
+  }
+
+  /// Directory of the docker config used to pull images, such as `~/.docker`
+  pub fn docker_config(mut self, docker_config: PathBuf) -> Self {
+    self.docker_config = Some(docker_config);
+    self
+  }
+
+  /// Logs in to the registry before the first pull. Without `docker_config`, the
+  /// credentials are kept in `{working_directory}/.docker`
+  pub fn registry_credentials(
+    mut self,
+    registry: impl Into<String>,
+    username: impl Into<String>,
+    password: impl Into<String>,
+  ) -> Self {
+    self.registry_credentials.insert(
+      registry.into(),
+      RegistryCredentials {
+        username: username.into(),
+        password: password.into(),
+      },
+    );
+    self
+  }
+
+  /// Number of times a failed image pull is attempted again. Defaults to 2
+  pub fn pull_retries(mut self, pull_retries: u32) -> Self {
+    self.pull_retries = Some(pull_retries);
+    self
+  }
+
+  /// Runs the step containers and pulls their images through the Docker Engine API on the
+  /// Unix socket, such as `/var/run/docker.sock`, instead of the docker CLI. Their stdout
+  /// and stderr are logged separately. Image builds, job containers and services still use
+  /// the docker CLI
+  #[cfg(unix)]
+  pub fn docker_engine(mut self, socket: impl Into<PathBuf>) -> Self {
+    self.docker_engine = Some(socket.into());
+    self
+  }
+
   pub fn build(self) -> Result<AstroRunner> {
     let working_directory = self.working_directory.map(Ok).unwrap_or_else(|| {
       #[allow(deprecated)]
@@ -146,12 +357,42 @@ impl AstroRunnerBuilder {
         .ok_or_else(|| Error::init_error("AstroRunnerBuilder: working_directory is required"))
     })?;
 
+    let (log_capacity, log_overflow) = self
+      .log_buffer
+      .unwrap_or((DEFAULT_STREAM_CAPACITY, OverflowPolicy::default()));
+
+    let docker_config = self.docker_config.or_else(|| {
+      (!self.registry_credentials.is_empty()).then(|| working_directory.join(".docker"))
+    });
+    let puller = ImagePuller::new(
+      docker_config,
+      self.registry_credentials,
+      self.pull_retries.unwrap_or(DEFAULT_PULL_RETRIES),
+    );
+
+    #[cfg(unix)]
+    let engine = self.docker_engine.map(EngineClient::new);
+    #[cfg(unix)]
+    let puller = match &engine {
+      Some(engine) => puller.engine(engine.clone()),
+      None => puller,
+    };
+
     let runner = AstroRunner {
       working_directory,
       state: Arc::new(Mutex::new(RunnerState {
         workflow_events: HashMap::new(),
+        job_containers: HashMap::new(),
+        job_services: HashMap::new(),
       })),
       plugin_driver: Arc::new(PluginDriver::new(self.plugins)),
+      log_capacity,
+      log_overflow,
+      output: self.output,
+      job_container: self.job_container,
+      puller,
+      #[cfg(unix)]
+      engine,
     };
 
     Ok(runner)
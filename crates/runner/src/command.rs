@@ -1,31 +1,59 @@
-use astro_run::{Error, Result, RunResult, StreamSender};
+use crate::output::{LineDecoder, OutputOptions};
+use astro_run::{Error, Log, Result, RunResult, StreamSender};
 use serde::{Deserialize, Serialize};
 use std::{
   path::{Path, PathBuf},
   process::Stdio,
 };
 use tokio::{
-  io::{AsyncBufReadExt, BufReader},
+  io::{AsyncReadExt, AsyncWriteExt},
   process::Command as Cmd,
 };
 
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
 /// A command to be executed by the runner.
+///
+/// Runs through the shell by default. In argv mode, see `Command::argv`, `command` is the
+/// program and the arguments are passed as they are, without any shell interpolation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Command {
   pub command: String,
+  #[serde(default)]
+  pub args: Option<Vec<String>>,
   pub current_dir: Option<PathBuf>,
   pub envs: Vec<(String, String)>,
+  #[serde(default)]
+  pub output: OutputOptions,
+  /// Written to the standard input of `exec`, such as a password
+  #[serde(default, skip_serializing)]
+  pub stdin: Option<String>,
 }
 
 impl Command {
   pub fn new(cmd: impl Into<String>) -> Self {
     Self {
       command: cmd.into(),
+      args: None,
       current_dir: None,
       envs: vec![],
+      output: OutputOptions::default(),
+      stdin: None,
     }
   }
 
+  /// Runs `program` with `args` without a shell
+  pub fn argv<I, S>(program: impl Into<String>, args: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    let mut command = Self::new(program);
+    command.args = Some(args.into_iter().map(Into::into).collect());
+
+    command
+  }
+
   pub fn env(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
     self.envs.push((key.into(), value.into()));
 
@@ -38,25 +66,65 @@ impl Command {
     self
   }
 
+  pub fn output(&mut self, options: OutputOptions) -> &mut Self {
+    self.output = options;
+
+    self
+  }
+
+  pub fn stdin(&mut self, input: impl Into<String>) -> &mut Self {
+    self.stdin = Some(input.into());
+
+    self
+  }
+
   pub async fn exec(&mut self) -> Result<String> {
     let mut command = self.build_command();
-    let output = command.output().await.map_err(|err| {
+    let output = match &self.stdin {
+      Some(input) => {
+        let mut child = command
+          .stdin(Stdio::piped())
+          .stdout(Stdio::piped())
+          .stderr(Stdio::piped())
+          .spawn()
+          .map_err(|err| {
+            Error::internal_runtime_error(format!("Failed to spawn child process: {}", err))
+          })?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+          stdin.write_all(input.as_bytes()).await?;
+          // Closes the input
+          drop(stdin);
+        }
+
+        child.wait_with_output().await
+      }
+      None => command.output().await,
+    }
+    .map_err(|err| {
       Error::internal_runtime_error(format!("Failed to spawn child process: {}", err))
     })?;
 
     if output.status.success() {
-      let stdout = String::from_utf8(output.stdout)
-        .map_err(|err| Error::internal_runtime_error(format!("Failed to parse stdout: {}", err)))?;
+      let stdout = String::from_utf8_lossy(&output.stdout);
       return Ok(stdout.trim().to_string());
     }
 
-    let stderr = String::from_utf8(output.stderr)
-      .map_err(|err| Error::internal_runtime_error(format!("Failed to parse stderr: {}", err)))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
 
-    Err(Error::internal_runtime_error(stderr))
+    Err(Error::internal_runtime_error(stderr.to_string()))
   }
 
   pub async fn run(&mut self, sender: StreamSender) -> Result<()> {
+    let res = self.execute(&sender).await?;
+
+    sender.end(res);
+
+    Ok(())
+  }
+
+  /// Runs the command, sending its output to `sender` without ending the stream
+  pub async fn execute(&mut self, sender: &StreamSender) -> Result<RunResult> {
     let mut command = self.build_command();
     let mut child = command
       .stdout(Stdio::piped())
@@ -66,47 +134,60 @@ impl Command {
         Error::internal_runtime_error(format!("Failed to spawn child process: {}", err))
       })?;
 
-    let out = child.stdout.take().ok_or(Error::internal_runtime_error(
+    let mut out = child.stdout.take().ok_or(Error::internal_runtime_error(
       "Failed to get stdout from child process".to_string(),
     ))?;
-    let err = child.stderr.take().ok_or(Error::internal_runtime_error(
+    let mut err = child.stderr.take().ok_or(Error::internal_runtime_error(
       "Failed to get stderr from child process".to_string(),
     ))?;
 
-    let out = BufReader::new(out);
-    let err = BufReader::new(err);
-
-    let mut lines = out.lines();
-    let mut errors = err.lines();
+    let mut out_lines = LineDecoder::new(self.output.clone());
+    let mut err_lines = LineDecoder::new(self.output.clone());
+    let mut out_buf = vec![0; READ_BUFFER_SIZE];
+    let mut err_buf = vec![0; READ_BUFFER_SIZE];
+    let mut out_closed = false;
+    let mut err_closed = false;
 
-    loop {
+    // Both pipes are read until they are closed, a process may keep writing
+    // to one of them after closing the other
+    while !out_closed || !err_closed {
       tokio::select! {
-        line = lines.next_line() => {
-          match line {
-            Ok(Some(line)) => {
-              sender.log(line);
-            }
-            Ok(None) => {
-                break;
+        res = out.read(&mut out_buf), if !out_closed => {
+          let lines = match res {
+            Ok(0) => {
+              out_closed = true;
+              out_lines.finish().into_iter().collect()
             }
+            Ok(n) => out_lines.feed(&out_buf[..n]),
             Err(err) => {
-              sender.error(err.to_string());
-              break;
+              out_closed = true;
+              sender.send_async(Log::error(err.to_string())).await;
+              vec![]
             }
+          };
+
+          let now = chrono::Utc::now();
+          for line in lines {
+            sender.send_async(Log::log(line).time(now)).await;
           }
         }
-        error = errors.next_line() => {
-          match error {
-            Ok(Some(error)) => {
-              sender.error(error);
-            }
-            Ok(None) => {
-              break;
+        res = err.read(&mut err_buf), if !err_closed => {
+          let lines = match res {
+            Ok(0) => {
+              err_closed = true;
+              err_lines.finish().into_iter().collect()
             }
+            Ok(n) => err_lines.feed(&err_buf[..n]),
             Err(err) => {
-              sender.error(err.to_string());
-              break;
+              err_closed = true;
+              sender.send_async(Log::error(err.to_string())).await;
+              vec![]
             }
+          };
+
+          let now = chrono::Utc::now();
+          for line in lines {
+            sender.send_async(Log::error(line).time(now)).await;
           }
         }
       }
@@ -127,14 +208,33 @@ impl Command {
       })
       .unwrap_or_else(|| RunResult::Failed { exit_code: 1 });
 
-    sender.end(res);
-
-    Ok(())
+    Ok(res)
   }
 
   fn build_command(&self) -> Cmd {
     let mut command;
 
+    if let Some(args) = &self.args {
+      command = Cmd::new(&self.command);
+      command.args(args);
+    } else {
+      command = Self::shell_command(&self.command);
+    }
+
+    if let Some(dir) = &self.current_dir {
+      command.current_dir(dir);
+    }
+
+    for (key, value) in &self.envs {
+      command.env(key, value);
+    }
+
+    command
+  }
+
+  fn shell_command(script: &str) -> Cmd {
+    let mut command;
+
     #[cfg(target_os = "windows")]
     {
       command = Cmd::new("powershell.exe");
@@ -143,21 +243,13 @@ impl Command {
         .arg("-NoProfile")
         .arg("-NonInteractive")
         .arg("-Command")
-        .arg(self.command.clone());
+        .arg(script);
     }
     #[cfg(not(target_os = "windows"))]
     {
       command = Cmd::new("sh");
 
-      command.arg("-c").arg(self.command.clone());
-    }
-
-    if let Some(dir) = &self.current_dir {
-      command.current_dir(dir);
-    }
-
-    for (key, value) in &self.envs {
-      command.env(key, value);
+      command.arg("-c").arg(script);
     }
 
     command
@@ -222,6 +314,35 @@ mod tests {
     assert_eq!(logs[0].message, "world");
   }
 
+  #[cfg(not(target_os = "windows"))]
+  #[astro_run_test::test]
+  async fn test_command_output() {
+    // Keeps writing to stdout after stderr is closed
+    let mut cmd =
+      Command::new("printf 'a\\rb\\n\\377'; echo >&2 error; exec 2>&-; sleep 0.1; echo c");
+    let (sender, mut receiver) = stream();
+
+    let mut logs = vec![];
+
+    tokio::join!(
+      async {
+        while let Some(log) = receiver.next().await {
+          logs.push(log);
+        }
+      },
+      async {
+        cmd.run(sender).await.unwrap();
+      }
+    );
+
+    let (errors, lines): (Vec<_>, Vec<_>) = logs.into_iter().partition(|log| log.is_error());
+    let lines: Vec<_> = lines.into_iter().map(|log| log.message).collect();
+
+    assert_eq!(lines, vec!["b", "\u{fffd}c"]);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "error");
+  }
+
   #[tokio::test]
   async fn test_exec_command() {
     let mut cmd = Command::new("echo hello");
@@ -256,6 +377,24 @@ mod tests {
     ));
   }
 
+  #[cfg(not(target_os = "windows"))]
+  #[astro_run_test::test]
+  async fn test_argv_command() {
+    let mut cmd = Command::argv("echo", ["$HOME `id`;", "\"quoted\""]);
+    let stdout = cmd.exec().await.unwrap();
+
+    assert_eq!(stdout, "$HOME `id`; \"quoted\"");
+  }
+
+  #[cfg(not(target_os = "windows"))]
+  #[astro_run_test::test]
+  async fn test_exec_stdin() {
+    let mut cmd = Command::argv("cat", Vec::<String>::new());
+    cmd.stdin("secret");
+
+    assert_eq!(cmd.exec().await.unwrap(), "secret");
+  }
+
   #[astro_run_test::test]
   async fn test_exec_stderr_command() {
     let mut cmd = Command::new("cd /not/exist");
@@ -1,4 +1,5 @@
 use crate::command::Command;
+use astro_run::{ContainerOptions, HealthCheck};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
@@ -11,6 +12,21 @@ pub struct Docker {
   pub volumes: Vec<String>,
   pub auto_remove: bool,
   pub security_opts: Vec<String>,
+  pub detach: bool,
+  pub network: Option<String>,
+  pub network_aliases: Vec<String>,
+  pub ports: Vec<String>,
+  pub health_check: Option<HealthCheck>,
+  pub cpus: Option<f64>,
+  pub memory: Option<String>,
+  pub user: Option<String>,
+  pub privileged: bool,
+  pub tmpfs: Vec<String>,
+  pub env_files: Vec<String>,
+  /// Extra options, added as is before the image
+  pub options: Vec<String>,
+  /// Arguments passed to the entrypoint
+  pub args: Vec<String>,
 }
 
 impl Docker {
@@ -24,6 +40,19 @@ impl Docker {
       volumes: Vec::new(),
       security_opts: Vec::new(),
       auto_remove: true,
+      detach: false,
+      network: None,
+      network_aliases: Vec::new(),
+      ports: Vec::new(),
+      health_check: None,
+      cpus: None,
+      memory: None,
+      user: None,
+      privileged: false,
+      tmpfs: Vec::new(),
+      env_files: Vec::new(),
+      options: Vec::new(),
+      args: Vec::new(),
     }
   }
 
@@ -57,15 +86,132 @@ impl Docker {
     self
   }
 
-  pub fn volume(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+  pub fn detach(mut self, detach: bool) -> Self {
+    self.detach = detach;
+    self
+  }
+
+  pub fn network(mut self, network: impl Into<String>) -> Self {
+    self.network = Some(network.into());
+    self
+  }
+
+  /// Host name of the container on its network
+  pub fn network_alias(mut self, alias: impl Into<String>) -> Self {
+    self.network_aliases.push(alias.into());
+    self
+  }
+
+  pub fn port(mut self, port: impl Into<String>) -> Self {
+    self.ports.push(port.into());
+    self
+  }
+
+  pub fn health_check(mut self, health_check: HealthCheck) -> Self {
+    self.health_check = Some(health_check);
+    self
+  }
+
+  pub fn cpus(mut self, cpus: f64) -> Self {
+    self.cpus = Some(cpus);
+    self
+  }
+
+  pub fn memory(mut self, memory: impl Into<String>) -> Self {
+    self.memory = Some(memory.into());
+    self
+  }
+
+  pub fn user(mut self, user: impl Into<String>) -> Self {
+    self.user = Some(user.into());
     self
-      .volumes
-      .push(format!("\"{}:{}\"", from.into(), to.into()));
+  }
+
+  pub fn privileged(mut self, privileged: bool) -> Self {
+    self.privileged = privileged;
+    self
+  }
+
+  pub fn tmpfs(mut self, tmpfs: impl Into<String>) -> Self {
+    self.tmpfs.push(tmpfs.into());
+    self
+  }
+
+  pub fn env_file(mut self, env_file: impl Into<String>) -> Self {
+    self.env_files.push(env_file.into());
+    self
+  }
+
+  pub fn option(mut self, option: impl Into<String>) -> Self {
+    self.options.push(option.into());
     self
   }
 
-  fn generate_docker_command(&self) -> String {
-    let mut docker_command: Vec<String> = ["docker", "run", "--tty"]
+  pub fn arg(mut self, arg: impl Into<String>) -> Self {
+    self.args.push(arg.into());
+    self
+  }
+
+  /// Applies the options of a workflow container, except the entrypoint which
+  /// depends on how the step script is run
+  pub fn container_options(mut self, options: &ContainerOptions) -> Self {
+    for volume in options.volumes.iter().flatten() {
+      if let [host_path, container_path] = volume.split(':').collect::<Vec<&str>>()[..] {
+        self = self.volume(host_path, container_path);
+      }
+    }
+
+    for security_opt in options.security_opts.iter().flatten() {
+      self = self.security_opt(security_opt.clone());
+    }
+
+    if let Some(cpus) = options.cpus {
+      self = self.cpus(cpus);
+    }
+
+    if let Some(memory) = &options.memory {
+      self = self.memory(memory);
+    }
+
+    if let Some(user) = &options.user {
+      self = self.user(user);
+    }
+
+    if let Some(network) = &options.network {
+      self = self.network(network);
+    }
+
+    if let Some(privileged) = options.privileged {
+      self = self.privileged(privileged);
+    }
+
+    for port in options.ports.iter().flatten() {
+      self = self.port(port);
+    }
+
+    for tmpfs in options.tmpfs.iter().flatten() {
+      self = self.tmpfs(tmpfs);
+    }
+
+    if let Some(env_file) = &options.env_file {
+      self = self.env_file(env_file);
+    }
+
+    if let Some(extra) = &options.options {
+      self = self.option(extra);
+    }
+
+    self
+  }
+
+  pub fn volume(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+    self.volumes.push(format!("{}:{}", from.into(), to.into()));
+    self
+  }
+
+  /// Arguments of the `docker` program, passed without a shell
+  fn generate_docker_args(&self) -> Vec<String> {
+    let mut docker_command: Vec<String> = ["run", "--tty"]
       .iter()
       .map(|item| item.to_string())
       .collect();
@@ -74,11 +220,70 @@ impl Docker {
       docker_command.push("--rm".to_string());
     }
 
+    if self.detach {
+      docker_command.push("--detach".to_string());
+    }
+
     for security_opt in &self.security_opts {
       docker_command.push("--security-opt".to_string());
       docker_command.push(security_opt.to_string());
     }
 
+    if let Some(network) = &self.network {
+      docker_command.push("--network".to_string());
+      docker_command.push(network.to_string());
+    }
+
+    for alias in &self.network_aliases {
+      docker_command.push("--network-alias".to_string());
+      docker_command.push(alias.to_string());
+    }
+
+    for port in &self.ports {
+      docker_command.push("-p".to_string());
+      docker_command.push(port.to_string());
+    }
+
+    if let Some(health_check) = &self.health_check {
+      docker_command.push("--health-cmd".to_string());
+      docker_command.push(health_check.command.clone());
+      docker_command.push("--health-interval".to_string());
+      docker_command.push(format!("{}ms", health_check.interval.as_millis()));
+      docker_command.push("--health-timeout".to_string());
+      docker_command.push(format!("{}ms", health_check.timeout.as_millis()));
+      docker_command.push("--health-retries".to_string());
+      docker_command.push(health_check.retries.to_string());
+    }
+
+    if let Some(cpus) = self.cpus {
+      docker_command.push("--cpus".to_string());
+      docker_command.push(cpus.to_string());
+    }
+
+    if let Some(memory) = &self.memory {
+      docker_command.push("--memory".to_string());
+      docker_command.push(memory.to_string());
+    }
+
+    if let Some(user) = &self.user {
+      docker_command.push("--user".to_string());
+      docker_command.push(user.to_string());
+    }
+
+    if self.privileged {
+      docker_command.push("--privileged".to_string());
+    }
+
+    for tmpfs in &self.tmpfs {
+      docker_command.push("--tmpfs".to_string());
+      docker_command.push(tmpfs.to_string());
+    }
+
+    for env_file in &self.env_files {
+      docker_command.push("--env-file".to_string());
+      docker_command.push(env_file.to_string());
+    }
+
     for volume in &self.volumes {
       docker_command.push("-v".to_string());
       docker_command.push(volume.to_string());
@@ -86,7 +291,7 @@ impl Docker {
 
     for (key, value) in &self.environments {
       docker_command.push("-e".to_string());
-      docker_command.push(format!("{}=\"{}\"", key, value));
+      docker_command.push(format!("{}={}", key, value));
     }
 
     if let Some(working_dir) = &self.working_dir {
@@ -104,23 +309,114 @@ impl Docker {
       docker_command.push(name.to_string());
     }
 
+    for options in &self.options {
+      docker_command.extend(split_options(options));
+    }
     docker_command.push(self.image.clone());
+    docker_command.extend(self.args.iter().cloned());
 
-    docker_command.join(" ")
+    docker_command
   }
 }
 
 impl From<Docker> for Command {
   fn from(docker: Docker) -> Self {
-    let command = docker.generate_docker_command();
+    Command::argv("docker", docker.generate_docker_args())
+  }
+}
+
+/// Runs a command in a running container
+#[derive(Debug, Clone)]
+pub struct DockerExec {
+  pub container: String,
+  pub environments: HashMap<String, String>,
+  pub working_dir: Option<String>,
+  /// Program and its arguments
+  pub command: Vec<String>,
+}
+
+impl DockerExec {
+  pub fn new(container: impl Into<String>, program: impl Into<String>) -> Self {
+    Self {
+      container: container.into(),
+      environments: HashMap::new(),
+      working_dir: None,
+      command: vec![program.into()],
+    }
+  }
+
+  pub fn arg(mut self, arg: impl Into<String>) -> Self {
+    self.command.push(arg.into());
+    self
+  }
+
+  pub fn environment(mut self, key: String, value: String) -> Self {
+    self.environments.insert(key, value);
+    self
+  }
+
+  pub fn working_dir(mut self, working_dir: impl Into<String>) -> Self {
+    self.working_dir = Some(working_dir.into());
+    self
+  }
+
+  fn generate_docker_args(&self) -> Vec<String> {
+    let mut docker_command: Vec<String> = ["exec", "--tty"]
+      .iter()
+      .map(|item| item.to_string())
+      .collect();
+
+    for (key, value) in &self.environments {
+      docker_command.push("-e".to_string());
+      docker_command.push(format!("{}={}", key, value));
+    }
+
+    if let Some(working_dir) = &self.working_dir {
+      docker_command.push("-w".to_string());
+      docker_command.push(working_dir.to_string());
+    }
+
+    docker_command.push(self.container.clone());
+    docker_command.extend(self.command.iter().cloned());
+
+    docker_command
+  }
+}
 
-    Command::new(command)
+impl From<DockerExec> for Command {
+  fn from(exec: DockerExec) -> Self {
+    Command::argv("docker", exec.generate_docker_args())
   }
 }
 
+/// Splits raw options into arguments on whitespace, keeping quoted parts together
+fn split_options(options: &str) -> Vec<String> {
+  let mut args = vec![];
+  let mut arg: Option<String> = None;
+  let mut quote = None;
+
+  for c in options.chars() {
+    match (quote, c) {
+      (Some(q), c) if c == q => quote = None,
+      (Some(_), c) => arg.get_or_insert_with(String::new).push(c),
+      (None, '"' | '\'') => {
+        quote = Some(c);
+        arg.get_or_insert_with(String::new);
+      }
+      (None, c) if c.is_whitespace() => args.extend(arg.take()),
+      (None, c) => arg.get_or_insert_with(String::new).push(c),
+    }
+  }
+  args.extend(arg);
+
+  args
+}
+
 #[cfg(test)]
 mod tests {
-  use super::Docker;
+  use super::{split_options, Docker, DockerExec};
+  use astro_run::{ContainerOptions, HealthCheck};
+  use std::time::Duration;
 
   #[test]
   fn test_generate_docker_command() {
@@ -131,11 +427,120 @@ mod tests {
       .entrypoint("entrypoint".to_string())
       .volume("/app".to_string(), "/home/runner/work".to_string())
       .security_opt("seccomp=unconfined".to_string())
-      .generate_docker_command();
+      .generate_docker_args();
+
+    assert_eq!(
+      common.join(" "),
+      "run --tty --rm --security-opt seccomp=unconfined -v /app:/home/runner/work -e key=value -w /home/runner/work --entrypoint entrypoint --name test ubuntu"
+    );
+  }
+
+  #[test]
+  fn test_no_shell_interpolation() {
+    let args = Docker::new("ubuntu")
+      .environment("KEY".to_string(), "\"$(id)\" `id`; $HOME".to_string())
+      .volume("/my app", "/home/runner/work")
+      .generate_docker_args();
+
+    assert_eq!(
+      args,
+      vec![
+        "run",
+        "--tty",
+        "--rm",
+        "-v",
+        "/my app:/home/runner/work",
+        "-e",
+        "KEY=\"$(id)\" `id`; $HOME",
+        "ubuntu"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_generate_detached_docker_command() {
+    let command = Docker::new("ubuntu")
+      .name("job")
+      .detach(true)
+      .entrypoint("tail")
+      .arg("-f")
+      .arg("/dev/null")
+      .generate_docker_args();
+
+    assert_eq!(
+      command.join(" "),
+      "run --tty --rm --detach --entrypoint tail --name job ubuntu -f /dev/null"
+    );
+  }
+
+  #[test]
+  fn test_generate_service_command() {
+    let command = Docker::new("postgres")
+      .name("job-postgres")
+      .detach(true)
+      .network("job")
+      .network_alias("postgres")
+      .port("5432:5432")
+      .health_check(HealthCheck {
+        command: "pg_isready -U \"postgres\"".to_string(),
+        interval: Duration::from_secs(2),
+        timeout: Duration::from_millis(500),
+        retries: 3,
+      })
+      .generate_docker_args();
+
+    assert_eq!(command[11], "pg_isready -U \"postgres\"");
+    assert_eq!(
+      command.join(" "),
+      "run --tty --rm --detach --network job --network-alias postgres -p 5432:5432 --health-cmd pg_isready -U \"postgres\" --health-interval 2000ms --health-timeout 500ms --health-retries 3 --name job-postgres postgres"
+    );
+  }
+
+  #[test]
+  fn test_generate_container_options_command() {
+    let options = ContainerOptions {
+      name: "ubuntu".to_string(),
+      cpus: Some(1.5),
+      memory: Some("512m".to_string()),
+      user: Some("1000:1000".to_string()),
+      network: Some("host".to_string()),
+      privileged: Some(true),
+      ports: Some(vec!["8080:80".to_string()]),
+      tmpfs: Some(vec!["/tmp:size=64m".to_string()]),
+      env_file: Some(".env".to_string()),
+      options: Some("--pids-limit 100".to_string()),
+      ..Default::default()
+    };
+
+    let command = Docker::new("ubuntu")
+      .container_options(&options)
+      .generate_docker_args();
+
+    assert_eq!(
+      command.join(" "),
+      "run --tty --rm --network host -p 8080:80 --cpus 1.5 --memory 512m --user 1000:1000 --privileged --tmpfs /tmp:size=64m --env-file .env --pids-limit 100 ubuntu"
+    );
+  }
+
+  #[test]
+  fn test_split_options() {
+    assert_eq!(
+      split_options(r#" --label "a b"  --add-host='db:10.0.0.1' --init "" "#),
+      vec!["--label", "a b", "--add-host=db:10.0.0.1", "--init", ""]
+    );
+  }
+
+  #[test]
+  fn test_generate_docker_exec_command() {
+    let command = DockerExec::new("job", "/bin/bash")
+      .arg("/home/work/runner/1/entrypoint")
+      .environment("key".to_string(), "value".to_string())
+      .working_dir("/home/runner/work")
+      .generate_docker_args();
 
     assert_eq!(
-      common,
-      "docker run --tty --rm --security-opt seccomp=unconfined -v \"/app:/home/runner/work\" -e key=\"value\" -w /home/runner/work --entrypoint entrypoint --name test ubuntu"
+      command.join(" "),
+      "exec --tty -e key=value -w /home/runner/work job /bin/bash /home/work/runner/1/entrypoint"
     );
   }
 }
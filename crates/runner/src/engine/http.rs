@@ -0,0 +1,154 @@
+use astro_run::{Error, Result};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Incoming, client::conn::http1, upgrade::Upgraded, Request};
+use hyper_util::rt::TokioIo;
+use serde::de::DeserializeOwned;
+use std::path::Path;
+use tokio::{io::AsyncReadExt, net::UnixStream};
+
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+enum Body {
+  Incoming(Incoming),
+  /// Raw stream of a connection upgraded by the daemon, such as an attached container
+  Upgraded(TokioIo<Upgraded>),
+}
+
+/// Response of the daemon, the body is read as it arrives
+pub struct Response {
+  pub status: u16,
+  body: Body,
+}
+
+/// Sends a request on a new connection to the socket, which is closed once the response is read
+pub async fn send(
+  socket: &Path,
+  method: &str,
+  path: &str,
+  headers: &[(&str, &str)],
+  body: Option<Vec<u8>>,
+) -> Result<Response> {
+  let stream = UnixStream::connect(socket).await.map_err(|err| {
+    Error::internal_runtime_error(format!(
+      "Failed to connect to the docker daemon at {}: {}",
+      socket.display(),
+      err
+    ))
+  })?;
+
+  let (mut sender, connection) = http1::handshake(TokioIo::new(stream))
+    .await
+    .map_err(docker_error)?;
+  tokio::spawn(async move {
+    if let Err(err) = connection.with_upgrades().await {
+      log::trace!("Docker connection error: {}", err);
+    }
+  });
+
+  let mut request = Request::builder()
+    .method(method)
+    .uri(path)
+    .header("Host", "docker");
+  for (name, value) in headers {
+    request = request.header(*name, *value);
+  }
+  if body.is_some() {
+    request = request.header("Content-Type", "application/json");
+  }
+
+  let request = request
+    .body(Full::new(Bytes::from(body.unwrap_or_default())))
+    .map_err(docker_error)?;
+
+  let mut response = sender.send_request(request).await.map_err(docker_error)?;
+  let status = response.status().as_u16();
+
+  let body = if status == 101 {
+    let upgraded = hyper::upgrade::on(&mut response)
+      .await
+      .map_err(docker_error)?;
+
+    Body::Upgraded(TokioIo::new(upgraded))
+  } else {
+    Body::Incoming(response.into_body())
+  };
+
+  Ok(Response { status, body })
+}
+
+impl Response {
+  /// A 2xx status, or 101 when the connection is upgraded to a raw stream
+  pub fn is_success(&self) -> bool {
+    (200..300).contains(&self.status) || self.status == 101
+  }
+
+  /// Returns the next part of the body, `None` once it is read
+  pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>> {
+    match &mut self.body {
+      Body::Incoming(body) => {
+        while let Some(frame) = body.frame().await {
+          // Trailers are skipped
+          if let Ok(data) = frame.map_err(docker_error)?.into_data() {
+            return Ok(Some(data.to_vec()));
+          }
+        }
+
+        Ok(None)
+      }
+      Body::Upgraded(stream) => {
+        let mut buf = vec![0; READ_BUFFER_SIZE];
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+          return Ok(None);
+        }
+
+        buf.truncate(n);
+
+        Ok(Some(buf))
+      }
+    }
+  }
+
+  pub async fn bytes(mut self) -> Result<Vec<u8>> {
+    let mut body = vec![];
+
+    while let Some(chunk) = self.chunk().await? {
+      body.extend(chunk);
+    }
+
+    Ok(body)
+  }
+
+  pub async fn json<T: DeserializeOwned>(self) -> Result<T> {
+    let body = self.bytes().await?;
+
+    serde_json::from_slice(&body).map_err(|err| {
+      Error::internal_runtime_error(format!("Invalid response from docker: {}", err))
+    })
+  }
+
+  /// Returns the response if it succeeded, otherwise an error with the message of the daemon
+  pub async fn error_for_status(self) -> Result<Self> {
+    if self.is_success() {
+      return Ok(self);
+    }
+
+    let status = self.status;
+    let body = self.bytes().await?;
+    // Errors are sent as `{"message": "..."}`
+    let message = serde_json::from_slice::<serde_json::Value>(&body)
+      .ok()
+      .and_then(|body| body["message"].as_str().map(|message| message.to_string()))
+      .unwrap_or_else(|| String::from_utf8_lossy(&body).trim().to_string());
+
+    Err(Error::internal_runtime_error(format!(
+      "Docker responded with {}: {}",
+      status, message
+    )))
+  }
+}
+
+fn docker_error(err: impl std::fmt::Display) -> Error {
+  Error::internal_runtime_error(format!("Failed to talk to docker: {}", err))
+}
@@ -0,0 +1,917 @@
+mod http;
+
+use crate::{
+  docker::Docker,
+  output::{LineDecoder, OutputOptions},
+};
+use astro_run::{Error, Log, Result, RunResult, StreamSender};
+use base64::{engine::general_purpose::URL_SAFE, Engine};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::{
+  env,
+  path::{Path, PathBuf},
+};
+
+/// Socket of the docker daemon when `DOCKER_HOST` is not a Unix socket
+pub const DEFAULT_DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+/// How long `EngineClient::ping` waits for the daemon
+const PING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Stream types in the header of an attached stream frame
+const STDOUT_STREAM: u8 = 1;
+const STDERR_STREAM: u8 = 2;
+
+/// Client of the Docker Engine API on a Unix socket
+#[derive(Debug, Clone)]
+pub struct EngineClient {
+  socket: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ContainerState {
+  #[serde(rename = "ExitCode")]
+  pub exit_code: i32,
+  #[serde(rename = "OOMKilled", default)]
+  pub oom_killed: bool,
+}
+
+impl EngineClient {
+  pub fn new(socket: impl Into<PathBuf>) -> Self {
+    Self {
+      socket: socket.into(),
+    }
+  }
+
+  /// Uses the socket of `DOCKER_HOST` if it is a `unix://` address, otherwise the default socket
+  pub fn from_env() -> Self {
+    let socket = env::var("DOCKER_HOST")
+      .ok()
+      .and_then(|host| host.strip_prefix("unix://").map(PathBuf::from))
+      .unwrap_or_else(|| PathBuf::from(DEFAULT_DOCKER_SOCKET));
+
+    Self::new(socket)
+  }
+
+  pub fn socket(&self) -> &Path {
+    &self.socket
+  }
+
+  /// Checks that the daemon is running, failing if it doesn't respond in time
+  pub async fn ping(&self) -> Result<()> {
+    let ping = async { self.get("/_ping").await?.error_for_status().await };
+
+    tokio::time::timeout(PING_TIMEOUT, ping)
+      .await
+      .map_err(|_| Error::internal_runtime_error("Docker did not respond to the ping"))??;
+
+    Ok(())
+  }
+
+  pub async fn image_exists(&self, image: &str) -> Result<bool> {
+    let response = self.get(&format!("/images/{}/json", image)).await?;
+
+    if response.status == 404 {
+      return Ok(false);
+    }
+
+    response.error_for_status().await?;
+
+    Ok(true)
+  }
+
+  /// Pulls the image, streaming the progress to `sender`. `auth` is the encoded
+  /// `X-Registry-Auth` header. Returns the message of the failure
+  pub async fn pull_image(
+    &self,
+    image: &str,
+    auth: Option<&str>,
+    sender: &StreamSender,
+  ) -> std::result::Result<(), String> {
+    let (repository, tag) = split_image(image);
+    let path = format!(
+      "/images/create?fromImage={}&tag={}",
+      encode(repository),
+      encode(tag)
+    );
+    let headers: Vec<(&str, &str)> = auth
+      .map(|auth| vec![("X-Registry-Auth", auth)])
+      .unwrap_or_default();
+
+    let mut response = self
+      .request("POST", &path, &headers, None)
+      .await
+      .map_err(|err| err.to_string())?
+      .error_for_status()
+      .await
+      .map_err(|err| err.to_string())?;
+
+    // The progress is sent as JSON lines, failures are reported in the stream
+    let mut buffer = vec![];
+    loop {
+      let chunk = response.chunk().await.map_err(|err| err.to_string())?;
+      let is_end = chunk.is_none();
+      buffer.extend(chunk.unwrap_or_default());
+
+      let mut lines: Vec<Vec<u8>> = vec![];
+      while let Some(position) = buffer.iter().position(|byte| *byte == b'\n') {
+        lines.push(buffer.drain(..=position).collect());
+      }
+      if is_end && !buffer.is_empty() {
+        lines.push(std::mem::take(&mut buffer));
+      }
+
+      for line in lines {
+        let Ok(progress) = serde_json::from_slice::<Value>(&line) else {
+          continue;
+        };
+
+        if let Some(error) = progress["error"].as_str() {
+          return Err(error.to_string());
+        }
+
+        // Progress bars of the layers are skipped
+        if progress.get("progress").is_some() {
+          continue;
+        }
+
+        match (progress["id"].as_str(), progress["status"].as_str()) {
+          (Some(id), Some(status)) => {
+            sender
+              .send_async(Log::log(format!("{}: {}", id, status)))
+              .await
+          }
+          (None, Some(status)) => sender.send_async(Log::log(status)).await,
+          _ => {}
+        }
+      }
+
+      if is_end {
+        return Ok(());
+      }
+    }
+  }
+
+  /// Creates the container and returns its id
+  pub async fn create_container(&self, docker: &Docker) -> Result<String> {
+    #[derive(Deserialize)]
+    struct Created {
+      #[serde(rename = "Id")]
+      id: String,
+    }
+
+    let path = match &docker.name {
+      Some(name) => format!("/containers/create?name={}", encode(name)),
+      None => "/containers/create".to_string(),
+    };
+    let body = serde_json::to_vec(&container_config(docker)?)
+      .map_err(|err| Error::internal_runtime_error(err.to_string()))?;
+
+    let created: Created = self
+      .request("POST", &path, &[], Some(body))
+      .await?
+      .error_for_status()
+      .await?
+      .json()
+      .await?;
+
+    Ok(created.id)
+  }
+
+  /// Runs the container and streams its output until it exits. The container is removed
+  /// once it exits, even if it fails
+  pub async fn run(
+    &self,
+    docker: &Docker,
+    output: &OutputOptions,
+    sender: &StreamSender,
+  ) -> Result<RunResult> {
+    let id = self.create_container(docker).await?;
+    let res = self.run_container(&id, output, sender).await;
+
+    if let Err(err) = self.remove_container(&id).await {
+      log::error!("Failed to remove container {}: {}", id, err);
+    }
+
+    res
+  }
+
+  async fn run_container(
+    &self,
+    id: &str,
+    output: &OutputOptions,
+    sender: &StreamSender,
+  ) -> Result<RunResult> {
+    // Attached before the start, so the first lines of the output are not lost
+    let mut stream = self
+      .request(
+        "POST",
+        &format!("/containers/{}/attach?stream=1&stdout=1&stderr=1", id),
+        &[("Connection", "Upgrade"), ("Upgrade", "tcp")],
+        None,
+      )
+      .await?
+      .error_for_status()
+      .await?;
+
+    self
+      .request("POST", &format!("/containers/{}/start", id), &[], None)
+      .await?
+      .error_for_status()
+      .await?;
+
+    let mut frames = FrameDecoder::default();
+    let mut out_lines = LineDecoder::new(output.clone());
+    let mut err_lines = LineDecoder::new(output.clone());
+
+    while let Some(chunk) = stream.chunk().await? {
+      let now = chrono::Utc::now();
+
+      for (stream_type, payload) in frames.feed(&chunk) {
+        match stream_type {
+          STDOUT_STREAM => {
+            for line in out_lines.feed(&payload) {
+              sender.send_async(Log::log(line).time(now)).await;
+            }
+          }
+          STDERR_STREAM => {
+            for line in err_lines.feed(&payload) {
+              sender.send_async(Log::error(line).time(now)).await;
+            }
+          }
+          _ => {}
+        }
+      }
+    }
+
+    let now = chrono::Utc::now();
+    if let Some(line) = out_lines.finish() {
+      sender.send_async(Log::log(line).time(now)).await;
+    }
+    if let Some(line) = err_lines.finish() {
+      sender.send_async(Log::error(line).time(now)).await;
+    }
+
+    // The stream may close before the container exits
+    self
+      .request("POST", &format!("/containers/{}/wait", id), &[], None)
+      .await?
+      .error_for_status()
+      .await?
+      .bytes()
+      .await?;
+
+    let state = self.inspect_container(id).await?;
+
+    if state.oom_killed {
+      sender
+        .send_async(Log::error(
+          "The container was killed because it ran out of memory",
+        ))
+        .await;
+    }
+
+    let res = if state.exit_code == 0 && !state.oom_killed {
+      RunResult::Succeeded
+    } else {
+      RunResult::Failed {
+        exit_code: state.exit_code,
+      }
+    };
+
+    Ok(res)
+  }
+
+  pub async fn inspect_container(&self, id: &str) -> Result<ContainerState> {
+    #[derive(Deserialize)]
+    struct Inspect {
+      #[serde(rename = "State")]
+      state: ContainerState,
+    }
+
+    let inspect: Inspect = self
+      .get(&format!("/containers/{}/json", id))
+      .await?
+      .error_for_status()
+      .await?
+      .json()
+      .await?;
+
+    Ok(inspect.state)
+  }
+
+  pub async fn kill_container(&self, id: &str) -> Result<()> {
+    self
+      .request("POST", &format!("/containers/{}/kill", id), &[], None)
+      .await?
+      .error_for_status()
+      .await?;
+
+    Ok(())
+  }
+
+  pub async fn remove_container(&self, id: &str) -> Result<()> {
+    self
+      .request(
+        "DELETE",
+        &format!("/containers/{}?force=1&v=1", id),
+        &[],
+        None,
+      )
+      .await?
+      .error_for_status()
+      .await?;
+
+    Ok(())
+  }
+
+  async fn get(&self, path: &str) -> Result<http::Response> {
+    self.request("GET", path, &[], None).await
+  }
+
+  async fn request(
+    &self,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Option<Vec<u8>>,
+  ) -> Result<http::Response> {
+    http::send(&self.socket, method, path, headers, body).await
+  }
+}
+
+/// Splits the multiplexed stream of a container without a TTY into its stdout and stderr.
+///
+/// Each frame starts with a header of 8 bytes: the stream type, 3 empty bytes and the
+/// size of the payload as big-endian u32.
+#[derive(Default)]
+pub(crate) struct FrameDecoder {
+  buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+  /// Returns the frames completed by `bytes`
+  pub fn feed(&mut self, bytes: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    self.buffer.extend_from_slice(bytes);
+
+    let mut frames = vec![];
+    while self.buffer.len() >= 8 {
+      let size = u32::from_be_bytes([
+        self.buffer[4],
+        self.buffer[5],
+        self.buffer[6],
+        self.buffer[7],
+      ]) as usize;
+
+      if self.buffer.len() < 8 + size {
+        break;
+      }
+
+      let stream_type = self.buffer[0];
+      let payload = self.buffer[8..8 + size].to_vec();
+      self.buffer.drain(..8 + size);
+
+      frames.push((stream_type, payload));
+    }
+
+    frames
+  }
+}
+
+/// Body of `POST /containers/create`. The container runs without a TTY, so its stdout and
+/// stderr stay separated
+fn container_config(docker: &Docker) -> Result<Value> {
+  if !docker.options.is_empty() {
+    return Err(Error::unsupported_feature(format!(
+      "Docker options `{}` are not supported by the Docker Engine API",
+      docker.options.join(" ")
+    )));
+  }
+
+  let mut env = vec![];
+  // Read on the runner like the docker CLI does, the environments take precedence
+  for env_file in &docker.env_files {
+    env.extend(read_env_file(Path::new(env_file))?);
+  }
+  for (key, value) in &docker.environments {
+    env.push(format!("{}={}", key, value));
+  }
+
+  let mut exposed_ports = Map::new();
+  let mut port_bindings = Map::new();
+  for port in &docker.ports {
+    let (container_port, host_ip, host_port) = parse_port(port)?;
+    exposed_ports.insert(container_port.clone(), json!({}));
+    if let Some(host_port) = host_port {
+      port_bindings.insert(
+        container_port,
+        json!([{ "HostIp": host_ip.unwrap_or_default(), "HostPort": host_port }]),
+      );
+    }
+  }
+
+  let tmpfs: Map<String, Value> = docker
+    .tmpfs
+    .iter()
+    .map(|tmpfs| match tmpfs.split_once(':') {
+      Some((path, options)) => (path.to_string(), json!(options)),
+      None => (tmpfs.clone(), json!("")),
+    })
+    .collect();
+
+  let mut host_config = json!({
+    "AutoRemove": docker.auto_remove,
+    "Binds": docker.volumes,
+    "SecurityOpt": docker.security_opts,
+    "Privileged": docker.privileged,
+    "PortBindings": port_bindings,
+    "Tmpfs": tmpfs,
+  });
+
+  if let Some(network) = &docker.network {
+    host_config["NetworkMode"] = json!(network);
+  }
+
+  if let Some(cpus) = docker.cpus {
+    host_config["NanoCpus"] = json!((cpus * 1e9) as i64);
+  }
+
+  if let Some(memory) = &docker.memory {
+    host_config["Memory"] = json!(parse_memory(memory)?);
+  }
+
+  let mut config = json!({
+    "Image": docker.image,
+    "Env": env,
+    "Tty": false,
+    "AttachStdout": true,
+    "AttachStderr": true,
+    "ExposedPorts": exposed_ports,
+    "HostConfig": host_config,
+  });
+
+  if let Some(entrypoint) = &docker.entrypoint {
+    config["Entrypoint"] = json!([entrypoint]);
+  }
+
+  if !docker.args.is_empty() {
+    config["Cmd"] = json!(docker.args);
+  }
+
+  if let Some(working_dir) = &docker.working_dir {
+    config["WorkingDir"] = json!(working_dir);
+  }
+
+  if let Some(user) = &docker.user {
+    config["User"] = json!(user);
+  }
+
+  if let Some(health_check) = &docker.health_check {
+    config["Healthcheck"] = json!({
+      "Test": ["CMD-SHELL", health_check.command],
+      "Interval": health_check.interval.as_nanos() as u64,
+      "Timeout": health_check.timeout.as_nanos() as u64,
+      "Retries": health_check.retries,
+    });
+  }
+
+  if let (Some(network), false) = (&docker.network, docker.network_aliases.is_empty()) {
+    config["NetworkingConfig"] = json!({
+      "EndpointsConfig": { network: { "Aliases": docker.network_aliases } }
+    });
+  }
+
+  Ok(config)
+}
+
+/// Lines of an env file, a variable without a value is taken from the runner
+fn read_env_file(path: &Path) -> Result<Vec<String>> {
+  let content = std::fs::read_to_string(path).map_err(|err| {
+    Error::internal_runtime_error(format!(
+      "Failed to read env file {}: {}",
+      path.display(),
+      err
+    ))
+  })?;
+
+  let env = content
+    .lines()
+    .map(|line| line.trim_start())
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .filter_map(|line| {
+      if line.contains('=') {
+        Some(line.to_string())
+      } else {
+        env::var(line)
+          .ok()
+          .map(|value| format!("{}={}", line, value))
+      }
+    })
+    .collect();
+
+  Ok(env)
+}
+
+/// Parses a published port such as `80`, `8080:80`, `127.0.0.1:8080:80` or `53:53/udp`
+/// into the container port with its protocol, the host IP and the host port
+fn parse_port(port: &str) -> Result<(String, Option<String>, Option<String>)> {
+  let (mapping, protocol) = port.split_once('/').unwrap_or((port, "tcp"));
+  let parts: Vec<&str> = mapping.rsplitn(3, ':').collect();
+
+  let (container_port, host_port, host_ip) = match parts[..] {
+    [container_port] => (container_port, None, None),
+    [container_port, host_port] => (container_port, Some(host_port), None),
+    [container_port, host_port, host_ip] => (container_port, Some(host_port), Some(host_ip)),
+    _ => unreachable!(),
+  };
+
+  if container_port.is_empty() {
+    return Err(Error::internal_runtime_error(format!(
+      "Invalid port `{}`",
+      port
+    )));
+  }
+
+  Ok((
+    format!("{}/{}", container_port, protocol),
+    host_ip.map(|ip| ip.trim_matches(['[', ']']).to_string()),
+    host_port.map(|port| port.to_string()),
+  ))
+}
+
+/// Parses a memory limit such as `512m` or `2g` into bytes
+fn parse_memory(memory: &str) -> Result<i64> {
+  let memory = memory.trim().to_lowercase();
+  let digits = memory
+    .find(|c: char| !c.is_ascii_digit() && c != '.')
+    .unwrap_or(memory.len());
+  let (value, unit) = memory.split_at(digits);
+
+  let multiplier: f64 = match unit.trim_end_matches('b') {
+    "" => 1.0,
+    "k" => 1024.0,
+    "m" => 1024.0 * 1024.0,
+    "g" => 1024.0 * 1024.0 * 1024.0,
+    "t" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+    _ => f64::NAN,
+  };
+
+  match value.parse::<f64>() {
+    Ok(value) if multiplier.is_finite() && unit.len() <= 2 => Ok((value * multiplier) as i64),
+    _ => Err(Error::internal_runtime_error(format!(
+      "Invalid memory limit `{}`",
+      memory
+    ))),
+  }
+}
+
+/// Splits an image such as `ghcr.io/owner/image:tag` into its repository and tag.
+/// Images pinned by digest keep the digest in the repository
+fn split_image(image: &str) -> (&str, &str) {
+  if image.contains('@') {
+    return (image, "");
+  }
+
+  match image.rsplit_once(':') {
+    // The colon of a registry port, such as `localhost:5000/image`
+    Some((repository, tag)) if !tag.contains('/') => (repository, tag),
+    _ => (image, "latest"),
+  }
+}
+
+/// Registry of an image, images without a registry are on Docker Hub
+pub fn image_registry(image: &str) -> &str {
+  match image.split_once('/') {
+    Some((host, _)) if host.contains('.') || host.contains(':') || host == "localhost" => host,
+    _ => "docker.io",
+  }
+}
+
+/// Value of the `X-Registry-Auth` header
+pub fn registry_auth(registry: &str, username: &str, password: &str) -> String {
+  let auth = json!({
+    "username": username,
+    "password": password,
+    "serveraddress": registry,
+  });
+
+  // URL safe base64 with padding, as the daemon decodes the header
+  URL_SAFE.encode(auth.to_string())
+}
+
+fn encode(value: &str) -> String {
+  utf8_percent_encode(value, NON_ALPHANUMERIC).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use astro_run::{stream, WorkflowLogType};
+  use parking_lot::Mutex;
+  use std::sync::Arc;
+  use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixListener,
+  };
+  use tokio_stream::StreamExt;
+
+  type Handler = fn(&str, &str, &[u8]) -> Vec<u8>;
+
+  /// Serves the responses of `handler` on a socket, and records the requests
+  fn fake_daemon(name: &str, handler: Handler) -> (EngineClient, Arc<Mutex<Vec<String>>>) {
+    let socket = std::env::temp_dir().join(format!(
+      "astro-runner-engine-{}-{}.sock",
+      name,
+      std::process::id()
+    ));
+    std::fs::remove_file(&socket).ok();
+
+    let listener = UnixListener::bind(&socket).unwrap();
+    let requests = Arc::new(Mutex::new(vec![]));
+    let recorded = Arc::clone(&requests);
+
+    tokio::spawn(async move {
+      while let Ok((mut stream, _)) = listener.accept().await {
+        let mut request = vec![];
+        let mut buf = [0; 1024];
+        let head_end = loop {
+          let n = stream.read(&mut buf).await.unwrap();
+          request.extend_from_slice(&buf[..n]);
+          if let Some(position) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            break position + 4;
+          }
+        };
+
+        let head = String::from_utf8_lossy(&request[..head_end]).to_string();
+        let length = head
+          .lines()
+          .filter_map(|line| line.split_once(':'))
+          .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+          .and_then(|(_, length)| length.trim().parse::<usize>().ok())
+          .unwrap_or(0);
+        while request.len() < head_end + length {
+          let n = stream.read(&mut buf).await.unwrap();
+          request.extend_from_slice(&buf[..n]);
+        }
+
+        let mut request_line = head.lines().next().unwrap().split(' ');
+        let method = request_line.next().unwrap().to_string();
+        let path = request_line.next().unwrap().to_string();
+        recorded.lock().push(format!("{} {}", method, path));
+
+        let response = handler(&method, &path, &request[head_end..]);
+        stream.write_all(&response).await.unwrap();
+        stream.shutdown().await.ok();
+      }
+    });
+
+    (EngineClient::new(socket), requests)
+  }
+
+  fn response(status: &str, body: &str) -> Vec<u8> {
+    format!(
+      "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+      status,
+      body.len(),
+      body
+    )
+    .into_bytes()
+  }
+
+  fn frame(stream_type: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![stream_type, 0, 0, 0];
+    frame.extend((payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(payload);
+
+    frame
+  }
+
+  #[test]
+  fn test_frame_decoder() {
+    let mut bytes = frame(STDOUT_STREAM, b"hello\n");
+    bytes.extend(frame(STDERR_STREAM, b"oops"));
+
+    let mut decoder = FrameDecoder::default();
+    let mut frames = decoder.feed(&bytes[..10]);
+    frames.extend(decoder.feed(&bytes[10..]));
+
+    assert_eq!(
+      frames,
+      vec![
+        (STDOUT_STREAM, b"hello\n".to_vec()),
+        (STDERR_STREAM, b"oops".to_vec())
+      ]
+    );
+  }
+
+  #[test]
+  fn test_container_config() {
+    let docker = Docker::new("ubuntu")
+      .name("astro-run-step")
+      .entrypoint("/home/work/runner/entrypoint")
+      .environment("NAME".to_string(), "astro $(run)".to_string())
+      .volume("/tmp/data", "/home/work/data")
+      .network("astro-run-job")
+      .network_alias("step")
+      .port("127.0.0.1:8080:80")
+      .cpus(1.5)
+      .memory("512m")
+      .tmpfs("/tmp:size=64m")
+      .auto_remove(false);
+
+    let config = container_config(&docker).unwrap();
+
+    assert_eq!(config["Image"], "ubuntu");
+    assert_eq!(config["Env"], json!(["NAME=astro $(run)"]));
+    assert_eq!(
+      config["Entrypoint"],
+      json!(["/home/work/runner/entrypoint"])
+    );
+    assert_eq!(config["Tty"], false);
+    assert_eq!(config["ExposedPorts"], json!({ "80/tcp": {} }));
+
+    let host_config = &config["HostConfig"];
+    assert_eq!(host_config["Binds"], json!(["/tmp/data:/home/work/data"]));
+    assert_eq!(host_config["NetworkMode"], "astro-run-job");
+    assert_eq!(host_config["NanoCpus"], 1_500_000_000);
+    assert_eq!(host_config["Memory"], 512 * 1024 * 1024);
+    assert_eq!(host_config["Tmpfs"], json!({ "/tmp": "size=64m" }));
+    assert_eq!(host_config["AutoRemove"], false);
+    assert_eq!(
+      host_config["PortBindings"],
+      json!({ "80/tcp": [{ "HostIp": "127.0.0.1", "HostPort": "8080" }] })
+    );
+    assert_eq!(
+      config["NetworkingConfig"],
+      json!({ "EndpointsConfig": { "astro-run-job": { "Aliases": ["step"] } } })
+    );
+
+    let docker = Docker::new("ubuntu").option("--gpus all");
+    assert!(container_config(&docker).is_err());
+  }
+
+  #[test]
+  fn test_parse() {
+    assert_eq!(
+      parse_port("53:53/udp").unwrap(),
+      ("53/udp".to_string(), None, Some("53".to_string()))
+    );
+    assert_eq!(
+      parse_port("80").unwrap(),
+      ("80/tcp".to_string(), None, None)
+    );
+
+    assert_eq!(parse_memory("1024").unwrap(), 1024);
+    assert_eq!(parse_memory("2G").unwrap(), 2 * 1024 * 1024 * 1024);
+    assert_eq!(parse_memory("1.5kb").unwrap(), 1536);
+    assert!(parse_memory("2 gigabytes").is_err());
+
+    assert_eq!(split_image("ubuntu"), ("ubuntu", "latest"));
+    assert_eq!(
+      split_image("localhost:5000/app:1.0"),
+      ("localhost:5000/app", "1.0")
+    );
+    assert_eq!(
+      split_image("localhost:5000/app"),
+      ("localhost:5000/app", "latest")
+    );
+
+    assert_eq!(image_registry("ghcr.io/owner/app"), "ghcr.io");
+    assert_eq!(image_registry("owner/app"), "docker.io");
+
+    let auth = URL_SAFE
+      .decode(registry_auth("ghcr.io", "user", "pass?>"))
+      .unwrap();
+    assert_eq!(
+      serde_json::from_slice::<Value>(&auth).unwrap(),
+      json!({ "username": "user", "password": "pass?>", "serveraddress": "ghcr.io" })
+    );
+  }
+
+  #[astro_run_test::test]
+  async fn test_run() {
+    let (client, requests) = fake_daemon("run", |method, path, _| match (method, path) {
+      ("POST", "/containers/create?name=astro%2Drun%2Dstep") => {
+        response("201 Created", r#"{"Id":"abc"}"#)
+      }
+      ("POST", "/containers/abc/attach?stream=1&stdout=1&stderr=1") => {
+        let mut stream =
+          b"HTTP/1.1 101 UPGRADED\r\nConnection: Upgrade\r\nUpgrade: tcp\r\n\r\n".to_vec();
+        stream.extend(frame(STDOUT_STREAM, b"hel"));
+        stream.extend(frame(STDERR_STREAM, b"oops\n"));
+        stream.extend(frame(STDOUT_STREAM, b"lo\nlast"));
+        stream
+      }
+      ("POST", "/containers/abc/start") => response("204 No Content", ""),
+      ("POST", "/containers/abc/wait") => response("200 OK", r#"{"StatusCode":137}"#),
+      ("GET", "/containers/abc/json") => {
+        response("200 OK", r#"{"State":{"ExitCode":137,"OOMKilled":true}}"#)
+      }
+      ("DELETE", "/containers/abc?force=1&v=1") => response("204 No Content", ""),
+      _ => response("404 Not Found", r#"{"message":"not found"}"#),
+    });
+
+    let (sender, receiver) = stream();
+    let docker = Docker::new("ubuntu").name("astro-run-step");
+    let res = client
+      .run(&docker, &OutputOptions::default(), &sender)
+      .await
+      .unwrap();
+    sender.end(res.clone());
+
+    assert_eq!(res, RunResult::Failed { exit_code: 137 });
+
+    let logs: Vec<_> = receiver
+      .collect::<Vec<_>>()
+      .await
+      .into_iter()
+      .map(|log| (log.log_type, log.message))
+      .collect();
+    assert_eq!(
+      logs,
+      vec![
+        (WorkflowLogType::Error, "oops".to_string()),
+        (WorkflowLogType::Log, "hello".to_string()),
+        (WorkflowLogType::Log, "last".to_string()),
+        (
+          WorkflowLogType::Error,
+          "The container was killed because it ran out of memory".to_string()
+        ),
+      ]
+    );
+
+    assert_eq!(
+      *requests.lock(),
+      vec![
+        "POST /containers/create?name=astro%2Drun%2Dstep",
+        "POST /containers/abc/attach?stream=1&stdout=1&stderr=1",
+        "POST /containers/abc/start",
+        "POST /containers/abc/wait",
+        "GET /containers/abc/json",
+        "DELETE /containers/abc?force=1&v=1",
+      ]
+    );
+  }
+
+  #[astro_run_test::test]
+  async fn test_pull_image() {
+    let (client, _) = fake_daemon("pull", |_, path, _| {
+      let body = if path.contains("fromImage=ubuntu&tag=22%2E04") {
+        vec![
+          r#"{"status":"Pulling from library/ubuntu","id":"22.04"}"#,
+          r#"{"status":"Downloading","progress":"[=>  ]","id":"a1b2"}"#,
+          r#"{"status":"Status: Downloaded newer image for ubuntu:22.04"}"#,
+        ]
+      } else {
+        vec![r#"{"error":"pull access denied for private"}"#]
+      };
+
+      let mut response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+      for line in body {
+        let line = format!("{}\r\n", line);
+        response.extend(format!("{:x}\r\n{}\r\n", line.len(), line).into_bytes());
+      }
+      response.extend(b"0\r\n\r\n");
+
+      response
+    });
+
+    let (sender, receiver) = stream();
+    assert!(client
+      .pull_image("ubuntu:22.04", None, &sender)
+      .await
+      .is_ok());
+    assert_eq!(
+      client.pull_image("private", None, &sender).await,
+      Err("pull access denied for private".to_string())
+    );
+    sender.succeeded();
+
+    let logs: Vec<_> = receiver.map(|log| log.message).collect().await;
+    assert_eq!(
+      logs,
+      vec![
+        "22.04: Pulling from library/ubuntu",
+        "Status: Downloaded newer image for ubuntu:22.04"
+      ]
+    );
+  }
+
+  #[astro_run_test::test]
+  async fn test_error_message() {
+    let (client, _) = fake_daemon("error", |_, _, _| {
+      response(
+        "500 Internal Server Error",
+        r#"{"message":"daemon is broken"}"#,
+      )
+    });
+
+    let err = client.ping().await.unwrap_err();
+    assert_eq!(
+      err,
+      Error::internal_runtime_error("Docker responded with 500: daemon is broken")
+    );
+  }
+}
@@ -2,15 +2,23 @@ use crate::{
   command::Command,
   docker::Docker,
   executors::Executor,
+  image_builder,
   metadata::{Metadata, PathBufTryToString},
-  utils,
+  registry::ImagePuller,
+  utils, OutputOptions,
 };
-use astro_run::{Context, Result, StreamSender, TriggerEvent};
+use astro_run::{Context, Log, Result, StreamSender, TriggerEvent, STEP_SUMMARY_ENV};
 use std::path::PathBuf;
 use tokio::fs;
 
+const DOCKER_SUMMARY_PATH: &str = "/home/work/runner/summary.md";
+
 pub struct DockerExecutor {
   pub working_directory: PathBuf,
+  pub output: OutputOptions,
+  /// Network of the job services
+  pub network: Option<String>,
+  pub puller: ImagePuller,
 }
 
 #[astro_run::async_trait]
@@ -36,25 +44,51 @@ impl Executor for DockerExecutor {
 
     let metadata = builder.build();
 
-    // Generate docker command
-    let mut command = Self::into_command(ctx.clone(), metadata.clone())?;
-
     let is_completed = ctx.signal.is_cancelled() || ctx.signal.is_timeout();
 
     if !is_completed {
       // Create step working directory
       fs::create_dir_all(&metadata.step_host_working_directory).await?;
       utils::create_executable_file(&metadata.entrypoint_path, &ctx.command.run).await?;
+      // Created before mounting, otherwise docker creates a directory
+      utils::create_summary_file(&metadata.summary_path).await?;
 
-      // Run the command
+      // Build or pull the image, then run the command
       tokio::select! {
-        Err(err) = command.run(sender.clone()) => {
-          log::error!("Step run error: {}", err);
+        res = async {
+          let image = image_builder::prepare_image(
+            ctx.command.container.as_ref(),
+            &metadata.job_data_directory,
+            &self.puller,
+            &sender,
+          )
+          .await?;
+
+          let mut command = Self::into_command(ctx.clone(), metadata.clone(), image, self.network.clone())?;
+          command.output(self.output.clone());
+          let res = command.execute(&sender).await;
+          if let Err(err) = &res {
+            sender.send_async(Log::error(err.to_string())).await;
+          }
+
+          res
+        } => {
+          match res {
+            Ok(res) => {
+              if let Some(summary) = utils::read_summary_file(&metadata.summary_path).await {
+                sender.summary(summary);
+              }
+              sender.end(res);
+            }
+            Err(err) => {
+              log::error!("Step run error: {}", err);
+            }
+          }
         }
         signal = ctx.signal.recv() => {
           log::trace!("Killing running docker: {}", metadata.docker_name);
           // Kill the container on step run error
-          Command::new(format!("docker kill {}", metadata.docker_name))
+          Command::argv("docker", ["kill", &metadata.docker_name])
             .exec()
             .await
             .ok();
@@ -80,14 +114,22 @@ impl Executor for DockerExecutor {
 }
 
 impl DockerExecutor {
-  fn into_command(ctx: Context, metadata: Metadata) -> Result<Command> {
-    let image = ctx
-      .command
-      .container
-      .clone()
-      .map(|c| c.name)
-      .unwrap_or("ubuntu".to_string());
+  fn into_command(
+    ctx: Context,
+    metadata: Metadata,
+    image: String,
+    network: Option<String>,
+  ) -> Result<Command> {
+    Ok(Self::container(ctx, metadata, image, network)?.into())
+  }
 
+  /// Container of the step, which runs the step script
+  pub(crate) fn container(
+    ctx: Context,
+    metadata: Metadata,
+    image: String,
+    network: Option<String>,
+  ) -> Result<Docker> {
     let mut docker = Docker::new(image)
       .name(&metadata.docker_name)
       .working_dir(metadata.docker_working_directory.clone())
@@ -101,27 +143,32 @@ impl DockerExecutor {
         metadata.docker_working_directory,
       )
       .volume(metadata.cache_directory.to_string()?, "/home/work/caches")
-      .entrypoint("/home/work/runner/entrypoint")
+      .volume(metadata.summary_path.to_string()?, DOCKER_SUMMARY_PATH)
+      .environment(
+        STEP_SUMMARY_ENV.to_string(),
+        DOCKER_SUMMARY_PATH.to_string(),
+      )
       .auto_remove(true);
 
     for (key, env) in ctx.command.environments {
       docker = docker.environment(key, env.to_string());
     }
 
-    if let Some(Some(volumes)) = ctx.command.container.as_ref().map(|c| c.volumes.clone()) {
-      for volume in volumes {
-        if let [host_path, container_path] = volume.split(':').collect::<Vec<&str>>()[..] {
-          docker = docker.volume(host_path, container_path);
-        }
-      }
+    // The container options may connect it to another network
+    if let Some(network) = network {
+      docker = docker.network(network);
     }
 
-    if let Some(Some(security_opts)) = ctx.command.container.map(|c| c.security_opts) {
-      for security_opt in security_opts {
-        docker = docker.security_opt(security_opt);
-      }
-    }
+    let options = ctx.command.container.unwrap_or_default();
+    docker = docker.container_options(&options);
+
+    docker = match options.entrypoint {
+      Some(entrypoint) => docker
+        .entrypoint(entrypoint)
+        .arg("/home/work/runner/entrypoint"),
+      None => docker.entrypoint("/home/work/runner/entrypoint"),
+    };
 
-    Ok(docker.into())
+    Ok(docker)
   }
 }
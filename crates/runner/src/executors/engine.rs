@@ -0,0 +1,111 @@
+use crate::{
+  engine::EngineClient,
+  executors::{DockerExecutor, Executor},
+  image_builder,
+  metadata::Metadata,
+  registry::ImagePuller,
+  utils, OutputOptions,
+};
+use astro_run::{Context, Log, Result, StreamSender, TriggerEvent};
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Runs the step containers and pulls their images through the Docker Engine API,
+/// see `AstroRunnerBuilder::docker_engine`. Images are still built with the docker CLI
+pub struct EngineExecutor {
+  pub working_directory: PathBuf,
+  pub output: OutputOptions,
+  /// Network of the job services
+  pub network: Option<String>,
+  pub puller: ImagePuller,
+  pub engine: EngineClient,
+}
+
+#[astro_run::async_trait]
+impl Executor for EngineExecutor {
+  async fn execute(
+    &self,
+    ctx: Context,
+    sender: StreamSender,
+    event: Option<TriggerEvent>,
+  ) -> Result<()> {
+    // Runner working directory
+    let mut builder = Metadata::builder()
+      .runner_working_directory(self.working_directory.clone())
+      .step_id(ctx.command.id.clone());
+
+    if let Some(event) = event {
+      builder = builder.repository(event.repo_owner, event.repo_name);
+    }
+
+    let metadata = builder.build();
+
+    let is_completed = ctx.signal.is_cancelled() || ctx.signal.is_timeout();
+
+    if is_completed {
+      log::trace!("Step is already completed");
+      return Ok(());
+    }
+
+    // Create step working directory
+    fs::create_dir_all(&metadata.step_host_working_directory).await?;
+    utils::create_executable_file(&metadata.entrypoint_path, &ctx.command.run).await?;
+    // Created before mounting, otherwise docker creates a directory
+    utils::create_summary_file(&metadata.summary_path).await?;
+
+    // Build or pull the image, then run the container
+    tokio::select! {
+      res = async {
+        let image = image_builder::prepare_image(
+          ctx.command.container.as_ref(),
+          &metadata.job_data_directory,
+          &self.puller,
+          &sender,
+        )
+        .await?;
+
+        // Removed by the engine client once its exit code is read
+        let docker = DockerExecutor::container(ctx.clone(), metadata.clone(), image, self.network.clone())?
+          .auto_remove(false);
+
+        let res = self.engine.run(&docker, &self.output, &sender).await;
+        if let Err(err) = &res {
+          sender.send_async(Log::error(err.to_string())).await;
+        }
+
+        res
+      } => {
+        match res {
+          Ok(res) => {
+            if let Some(summary) = utils::read_summary_file(&metadata.summary_path).await {
+              sender.summary(summary);
+            }
+            sender.end(res);
+          }
+          Err(err) => {
+            log::error!("Step run error: {}", err);
+          }
+        }
+      }
+      signal = ctx.signal.recv() => {
+        log::trace!("Removing running container: {}", metadata.docker_name);
+        // The container may not be created yet
+        self.engine.kill_container(&metadata.docker_name).await.ok();
+        self.engine.remove_container(&metadata.docker_name).await.ok();
+
+        log::trace!("Step received signal: {:?}", signal);
+        if let astro_run::Signal::Cancel = signal {
+          sender.cancelled();
+        } else {
+          sender.timeout();
+        }
+      }
+    }
+
+    // Clean up working directory
+    fs::remove_dir_all(&metadata.step_host_working_directory).await?;
+    log::trace!("Step run finished");
+
+    Ok(())
+  }
+}
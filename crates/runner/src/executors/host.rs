@@ -1,10 +1,16 @@
-use crate::{command::Command, executors::Executor, metadata::Metadata};
-use astro_run::{Context, Result, StreamSender, TriggerEvent};
+use crate::{
+  command::Command,
+  executors::Executor,
+  metadata::{Metadata, PathBufTryToString},
+  utils, OutputOptions,
+};
+use astro_run::{Context, Result, StreamSender, TriggerEvent, STEP_SUMMARY_ENV};
 use std::path::PathBuf;
 use tokio::fs;
 
 pub struct HostExecutor {
   pub working_directory: PathBuf,
+  pub output: OutputOptions,
 }
 
 #[astro_run::async_trait]
@@ -27,18 +33,31 @@ impl Executor for HostExecutor {
     let metadata = builder.build();
 
     // Generate docker command
-    let mut command = Self::into_command(&ctx, &metadata);
+    let mut command = Self::into_command(&ctx, &metadata)?;
+    command.output(self.output.clone());
 
     let is_completed = ctx.signal.is_cancelled() || ctx.signal.is_timeout();
 
     if !is_completed {
       // Create step working directory
       fs::create_dir_all(&metadata.job_data_directory).await?;
+      fs::create_dir_all(&metadata.step_host_working_directory).await?;
+      utils::create_summary_file(&metadata.summary_path).await?;
 
       tokio::select! {
         // Run the command
-        Err(err) = command.run(sender.clone()) => {
-          log::error!("Step run error: {}", err);
+        res = command.execute(&sender) => {
+          match res {
+            Ok(res) => {
+              if let Some(summary) = utils::read_summary_file(&metadata.summary_path).await {
+                sender.summary(summary);
+              }
+              sender.end(res);
+            }
+            Err(err) => {
+              log::error!("Step run error: {}", err);
+            }
+          }
         }
         signal = ctx.signal.recv() => {
           // TODO: cancel the command
@@ -53,6 +72,7 @@ impl Executor for HostExecutor {
 
       // Clean up working directory
       fs::remove_dir_all(&metadata.job_data_directory).await?;
+      fs::remove_dir_all(&metadata.step_host_working_directory).await?;
 
       log::trace!("Step run finished");
     } else {
@@ -64,7 +84,7 @@ impl Executor for HostExecutor {
 }
 
 impl HostExecutor {
-  fn into_command(ctx: &Context, metadata: &Metadata) -> Command {
+  fn into_command(ctx: &Context, metadata: &Metadata) -> Result<Command> {
     let mut command = Command::new(ctx.command.run.clone());
 
     command.dir(&metadata.job_data_directory);
@@ -73,6 +93,8 @@ impl HostExecutor {
       command.env(key, env.to_string());
     }
 
-    command
+    command.env(STEP_SUMMARY_ENV, metadata.summary_path.to_string()?);
+
+    Ok(command)
   }
 }
@@ -0,0 +1,231 @@
+use crate::{
+  command::Command,
+  docker::{Docker, DockerExec},
+  executors::Executor,
+  image_builder,
+  metadata::{Metadata, PathBufTryToString},
+  registry::ImagePuller,
+  utils, OutputOptions,
+};
+use astro_run::{
+  ContainerOptions, Context, Error, Log, Result, StreamSender, TriggerEvent, STEP_SUMMARY_ENV,
+};
+use std::{path::PathBuf, sync::Arc};
+use tokio::{fs, sync::Mutex};
+
+/// Job directory in the container, step files are in `{DOCKER_JOB_DIRECTORY}/{step_number}`
+const DOCKER_JOB_DIRECTORY: &str = "/home/work/runner";
+
+/// A container shared by all the steps of a job, see `AstroRunnerBuilder::job_container`
+#[derive(Clone)]
+pub struct JobContainer {
+  /// Container of the job, steps with another container don't run in it
+  pub options: Option<ContainerOptions>,
+  /// Name of the running container, `None` until a step starts it
+  running: Arc<Mutex<Option<String>>>,
+}
+
+impl JobContainer {
+  pub fn new(options: Option<ContainerOptions>) -> Self {
+    Self {
+      options,
+      running: Arc::new(Mutex::new(None)),
+    }
+  }
+
+  /// Starts the container unless it is already running, and returns its name
+  async fn start(
+    &self,
+    metadata: &Metadata,
+    network: Option<String>,
+    puller: &ImagePuller,
+    sender: &StreamSender,
+  ) -> Result<String> {
+    let mut running = self.running.lock().await;
+
+    if let Some(name) = running.as_ref() {
+      return Ok(name.clone());
+    }
+
+    let image = image_builder::prepare_image(
+      self.options.as_ref(),
+      &metadata.job_data_directory,
+      puller,
+      sender,
+    )
+    .await?;
+
+    let mut docker = Docker::new(image)
+      .name(&metadata.job_docker_name)
+      .detach(true)
+      .working_dir(metadata.docker_working_directory.clone())
+      .volume(metadata.job_directory.to_string()?, DOCKER_JOB_DIRECTORY)
+      // Working directory, such as /home/work/{repo}
+      .volume(
+        metadata.job_data_directory.to_string()?,
+        metadata.docker_working_directory.clone(),
+      )
+      .volume(metadata.cache_directory.to_string()?, "/home/work/caches")
+      // Keeps the container alive until the job completes
+      .entrypoint("tail")
+      .arg("-f")
+      .arg("/dev/null");
+
+    // The container options may connect it to another network
+    if let Some(network) = network {
+      docker = docker.network(network);
+    }
+
+    if let Some(options) = &self.options {
+      docker = docker.container_options(options);
+    }
+
+    log::trace!("Starting job container {}", metadata.job_docker_name);
+    Command::from(docker).exec().await?;
+
+    *running = Some(metadata.job_docker_name.clone());
+
+    Ok(metadata.job_docker_name.clone())
+  }
+
+  /// Removes the container if it is running, the next step starts a new one
+  pub async fn stop(&self) {
+    if let Some(name) = self.running.lock().await.take() {
+      log::trace!("Removing job container {}", name);
+      Command::argv("docker", ["rm", "--force", &name])
+        .exec()
+        .await
+        .ok();
+    }
+  }
+}
+
+pub struct JobContainerExecutor {
+  pub working_directory: PathBuf,
+  pub output: OutputOptions,
+  pub container: JobContainer,
+  /// Network of the job services
+  pub network: Option<String>,
+  pub puller: ImagePuller,
+}
+
+#[astro_run::async_trait]
+impl Executor for JobContainerExecutor {
+  async fn execute(
+    &self,
+    ctx: Context,
+    sender: StreamSender,
+    event: Option<TriggerEvent>,
+  ) -> Result<()> {
+    // Runner working directory
+    let mut builder = Metadata::builder()
+      .runner_working_directory(self.working_directory.clone())
+      .step_id(ctx.command.id.clone());
+
+    if let Some(event) = event {
+      builder = builder.repository(event.repo_owner, event.repo_name);
+    }
+
+    let metadata = builder.build();
+
+    let is_completed = ctx.signal.is_cancelled() || ctx.signal.is_timeout();
+
+    if is_completed {
+      log::trace!("Step is already completed");
+      return Ok(());
+    }
+
+    // Create step working directory
+    fs::create_dir_all(&metadata.step_host_working_directory).await?;
+    utils::create_executable_file(&metadata.entrypoint_path, &ctx.command.run).await?;
+    utils::create_summary_file(&metadata.summary_path).await?;
+
+    let name = self
+      .container
+      .start(&metadata, self.network.clone(), &self.puller, &sender)
+      .await;
+
+    let name = match name {
+      Ok(name) => name,
+      Err(err) => {
+        sender
+          .send_async(Log::error(format!(
+            "Failed to start the job container: {}",
+            err
+          )))
+          .await;
+        return Err(err);
+      }
+    };
+
+    let mut command = Self::into_command(&ctx, &metadata, name)?;
+    command.output(self.output.clone());
+
+    tokio::select! {
+      res = command.execute(&sender) => {
+        match res {
+          Ok(res) => {
+            if let Some(summary) = utils::read_summary_file(&metadata.summary_path).await {
+              sender.summary(summary);
+            }
+            sender.end(res);
+          }
+          Err(err) => {
+            log::error!("Step run error: {}", err);
+          }
+        }
+      }
+      signal = ctx.signal.recv() => {
+        // Stops the running step along with the container
+        self.container.stop().await;
+
+        log::trace!("Step received signal: {:?}", signal);
+        if let astro_run::Signal::Cancel = signal {
+          sender.cancelled();
+        } else {
+          sender.timeout();
+        }
+      }
+    }
+
+    // Clean up working directory
+    fs::remove_dir_all(&metadata.step_host_working_directory).await?;
+    log::trace!("Step run finished");
+
+    Ok(())
+  }
+}
+
+impl JobContainerExecutor {
+  fn into_command(ctx: &Context, metadata: &Metadata, container: String) -> Result<Command> {
+    let step_directory = metadata
+      .step_host_working_directory
+      .file_name()
+      .and_then(|name| name.to_str())
+      .ok_or_else(|| Error::internal_runtime_error("Invalid step working directory"))?;
+    let step_directory = format!("{}/{}", DOCKER_JOB_DIRECTORY, step_directory);
+
+    let script = format!("{}/entrypoint", step_directory);
+    let entrypoint = ctx
+      .command
+      .container
+      .as_ref()
+      .and_then(|c| c.entrypoint.clone());
+
+    let mut exec = match entrypoint {
+      Some(entrypoint) => DockerExec::new(container, entrypoint).arg(script),
+      None => DockerExec::new(container, script),
+    }
+    .working_dir(metadata.docker_working_directory.clone())
+    .environment(
+      STEP_SUMMARY_ENV.to_string(),
+      format!("{}/summary.md", step_directory),
+    );
+
+    for (key, env) in &ctx.command.environments {
+      exec = exec.environment(key.clone(), env.to_string());
+    }
+
+    Ok(exec.into())
+  }
+}
@@ -1,9 +1,15 @@
 mod docker;
+#[cfg(unix)]
+mod engine;
 mod host;
+mod job_container;
 
 use astro_run::{Context, Result, StreamSender, TriggerEvent};
 pub use docker::DockerExecutor;
+#[cfg(unix)]
+pub use engine::EngineExecutor;
 pub use host::HostExecutor;
+pub use job_container::{JobContainer, JobContainerExecutor};
 
 #[astro_run::async_trait]
 pub trait Executor: Send + Sync {
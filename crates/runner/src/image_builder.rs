@@ -0,0 +1,348 @@
+use crate::{command::Command, metadata::PathBufTryToString, registry::ImagePuller};
+use astro_run::{ContainerBuild, ContainerOptions, Error, Log, Result, RunResult, StreamSender};
+use sha2::{Digest, Sha256};
+use std::{
+  collections::BTreeMap,
+  fs,
+  path::{Component, Path, PathBuf},
+};
+
+/// Repository of the built images, tagged with the hash of their inputs
+const BUILD_IMAGE_REPOSITORY: &str = "astro-run-build";
+/// Label of the built images
+const BUILD_IMAGE_LABEL: &str = "dev.astro-run.build";
+
+/// Builds the image of the container if it has a `build`, otherwise pulls it. Returns the
+/// image the container runs
+pub async fn prepare_image(
+  options: Option<&ContainerOptions>,
+  working_directory: &Path,
+  puller: &ImagePuller,
+  sender: &StreamSender,
+) -> Result<String> {
+  let Some(options) = options else {
+    let image = "ubuntu".to_string();
+    puller.pull(&image, Default::default(), sender).await?;
+    return Ok(image);
+  };
+
+  if let Some(build) = &options.build {
+    return build_image(build, working_directory, puller, sender).await;
+  }
+
+  puller
+    .pull(&options.name, options.pull.unwrap_or_default(), sender)
+    .await?;
+
+  Ok(options.name.clone())
+}
+
+/// Builds the image unless an image with the same inputs was already built on the runner,
+/// and returns its name. Paths are resolved in `working_directory`, base images are pulled with
+/// the docker config and registry credentials of `puller`.
+///
+/// A new `astro-run-build:<hash>` tag is created whenever the inputs change and old tags are
+/// not removed, since other jobs may still use them. The images are labeled with
+/// `dev.astro-run.build`, unused ones can be removed with
+/// `docker image prune --all --filter label=dev.astro-run.build`
+pub async fn build_image(
+  build: &ContainerBuild,
+  working_directory: &Path,
+  puller: &ImagePuller,
+  sender: &StreamSender,
+) -> Result<String> {
+  let context = resolve(working_directory, build.context.as_deref().unwrap_or("."))?;
+  let dockerfile = resolve(
+    &context,
+    build.dockerfile.as_deref().unwrap_or("Dockerfile"),
+  )?;
+  let args: BTreeMap<String, String> = build.args.clone().unwrap_or_default().into_iter().collect();
+
+  let hash = {
+    let (context, dockerfile, args) = (context.clone(), dockerfile.clone(), args.clone());
+    tokio::task::spawn_blocking(move || hash_inputs(&context, &dockerfile, &args))
+      .await
+      .map_err(|err| Error::internal_runtime_error(err.to_string()))?
+      .map_err(|err| {
+        Error::internal_runtime_error(format!("Failed to read the build context: {}", err))
+      })?
+  };
+  let image = format!("{}:{}", BUILD_IMAGE_REPOSITORY, &hash[..16]);
+
+  let exists = Command::argv("docker", ["image", "inspect", &image])
+    .exec()
+    .await
+    .is_ok();
+  if exists {
+    log::trace!("Reusing built image {}", image);
+    return Ok(image);
+  }
+
+  let mut docker_args = vec![
+    "build".to_string(),
+    "--tag".to_string(),
+    image.clone(),
+    "--label".to_string(),
+    BUILD_IMAGE_LABEL.to_string(),
+    "--file".to_string(),
+    dockerfile.to_string()?,
+  ];
+  for (key, value) in &args {
+    docker_args.push("--build-arg".to_string());
+    docker_args.push(format!("{}={}", key, value));
+  }
+  docker_args.push(context.to_string()?);
+
+  sender
+    .send_async(Log::group_start(format!("Build image {}", image)))
+    .await;
+  let res = puller.build(docker_args, sender).await;
+  sender.send_async(Log::group_end()).await;
+
+  match res? {
+    RunResult::Succeeded => Ok(image),
+    _ => {
+      let message = format!("Failed to build image {}", image);
+      sender.send_async(Log::error(message.clone())).await;
+      Err(Error::internal_runtime_error(message))
+    }
+  }
+}
+
+/// Joins a relative path, which must not leave `root`
+fn resolve(root: &Path, path: &str) -> Result<PathBuf> {
+  let is_inside = Path::new(path)
+    .components()
+    .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+  if !is_inside {
+    return Err(Error::internal_runtime_error(format!(
+      "Build path `{}` must be relative and stay in the working directory",
+      path
+    )));
+  }
+
+  Ok(root.join(path))
+}
+
+/// Hashes the files of the context which are not excluded by its `.dockerignore`,
+/// the Dockerfile and the build arguments
+fn hash_inputs(
+  context: &Path,
+  dockerfile: &Path,
+  args: &BTreeMap<String, String>,
+) -> std::io::Result<String> {
+  let ignore = DockerIgnore::load(context)?;
+  let mut files = vec![];
+  collect_files(context, context, &ignore, &mut files)?;
+  files.sort();
+
+  let mut hasher = Sha256::new();
+  let mut update = |bytes: &[u8]| {
+    // Prefixed with the length, so that moving bytes between fields changes the hash
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+  };
+
+  for file in files {
+    let relative = file.strip_prefix(context).unwrap_or(&file);
+    update(relative.to_string_lossy().as_bytes());
+
+    let metadata = fs::symlink_metadata(&file)?;
+    if metadata.file_type().is_symlink() {
+      update(fs::read_link(&file)?.to_string_lossy().as_bytes());
+    } else {
+      update(&fs::read(&file)?);
+    }
+  }
+
+  update(&fs::read(dockerfile)?);
+
+  for (key, value) in args {
+    update(key.as_bytes());
+    update(value.as_bytes());
+  }
+
+  Ok(hex::encode(hasher.finalize()))
+}
+
+/// Files and symbolic links in `dir`, without the ignored files
+fn collect_files(
+  context: &Path,
+  dir: &Path,
+  ignore: &DockerIgnore,
+  files: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+  for entry in fs::read_dir(dir)? {
+    let entry = entry?;
+    let path = entry.path();
+    let relative = path.strip_prefix(context).unwrap_or(&path);
+
+    if entry.file_type()?.is_dir() {
+      // Files of an ignored directory can be included again by an exception
+      if ignore.has_exceptions || !ignore.is_ignored(relative) {
+        collect_files(context, &path, ignore, files)?;
+      }
+    } else if !ignore.is_ignored(relative) {
+      files.push(path);
+    }
+  }
+
+  Ok(())
+}
+
+/// Patterns of the `.dockerignore` file in the root of the context
+#[derive(Default)]
+struct DockerIgnore {
+  /// Patterns and whether they are exceptions, starting with `!`
+  patterns: Vec<(glob::Pattern, bool)>,
+  has_exceptions: bool,
+}
+
+impl DockerIgnore {
+  fn load(context: &Path) -> std::io::Result<Self> {
+    match fs::read_to_string(context.join(".dockerignore")) {
+      Ok(content) => Ok(Self::parse(&content)),
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+      Err(err) => Err(err),
+    }
+  }
+
+  fn parse(content: &str) -> Self {
+    let mut ignore = Self::default();
+
+    for line in content.lines().map(str::trim) {
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let (pattern, is_exception) = match line.strip_prefix('!') {
+        Some(pattern) => (pattern.trim(), true),
+        None => (line, false),
+      };
+      let pattern = pattern.trim_start_matches('/').trim_end_matches('/');
+
+      match glob::Pattern::new(pattern) {
+        Ok(pattern) => {
+          ignore.has_exceptions |= is_exception;
+          ignore.patterns.push((pattern, is_exception));
+        }
+        Err(err) => log::warn!("Invalid .dockerignore pattern `{}`: {}", line, err),
+      }
+    }
+
+    ignore
+  }
+
+  /// A path is ignored if the last pattern matching it, or one of its parent directories,
+  /// is not an exception
+  fn is_ignored(&self, relative: &Path) -> bool {
+    let options = glob::MatchOptions {
+      case_sensitive: true,
+      require_literal_separator: true,
+      require_literal_leading_dot: false,
+    };
+
+    self
+      .patterns
+      .iter()
+      .rev()
+      .find(|(pattern, _)| {
+        relative
+          .ancestors()
+          .filter(|path| !path.as_os_str().is_empty())
+          .any(|path| pattern.matches_path_with(path, options))
+      })
+      .is_some_and(|(_, is_exception)| !is_exception)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+      "astro-runner-build-{}-{}",
+      name,
+      std::process::id()
+    ));
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    fs::write(dir.join("Dockerfile"), "FROM ubuntu").unwrap();
+    fs::write(dir.join("script.sh"), "echo 1").unwrap();
+    fs::write(dir.join(".git").join("HEAD"), "main").unwrap();
+
+    dir
+  }
+
+  #[test]
+  fn test_hash_inputs() {
+    let dir = temp_dir("hash");
+    let dockerfile = dir.join("Dockerfile");
+    let args = BTreeMap::new();
+
+    let hash = hash_inputs(&dir, &dockerfile, &args).unwrap();
+    assert_eq!(hash, hash_inputs(&dir, &dockerfile, &args).unwrap());
+
+    // Sent to the daemon unless `.dockerignore` excludes it
+    fs::write(dir.join(".git").join("HEAD"), "dev").unwrap();
+    assert_ne!(hash, hash_inputs(&dir, &dockerfile, &args).unwrap());
+
+    fs::write(dir.join(".dockerignore"), ".git\n").unwrap();
+    let hash = hash_inputs(&dir, &dockerfile, &args).unwrap();
+    fs::write(dir.join(".git").join("HEAD"), "main").unwrap();
+    assert_eq!(hash, hash_inputs(&dir, &dockerfile, &args).unwrap());
+
+    fs::write(dir.join("script.sh"), "echo 2").unwrap();
+    let changed = hash_inputs(&dir, &dockerfile, &args).unwrap();
+    assert_ne!(hash, changed);
+
+    let args = BTreeMap::from([("VERSION".to_string(), "1".to_string())]);
+    assert_ne!(changed, hash_inputs(&dir, &dockerfile, &args).unwrap());
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn test_dockerignore() {
+    let dir = temp_dir("dockerignore");
+    let dockerfile = dir.join("Dockerfile");
+    let args = BTreeMap::new();
+    fs::write(
+      dir.join(".dockerignore"),
+      "# Build artifacts\ntarget\n**/node_modules\n*.log\n!keep.log\n",
+    )
+    .unwrap();
+
+    let hash = hash_inputs(&dir, &dockerfile, &args).unwrap();
+
+    for file in [
+      "target/debug/app",
+      "web/node_modules/lib/index.js",
+      "build.log",
+    ] {
+      let path = dir.join(file);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, "artifact").unwrap();
+    }
+    assert_eq!(hash, hash_inputs(&dir, &dockerfile, &args).unwrap());
+
+    // Exception
+    fs::write(dir.join("keep.log"), "kept").unwrap();
+    assert_ne!(hash, hash_inputs(&dir, &dockerfile, &args).unwrap());
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn test_resolve() {
+    let root = Path::new("/home/runner/work");
+
+    assert_eq!(
+      resolve(root, "./ci").unwrap(),
+      PathBuf::from("/home/runner/work/./ci")
+    );
+    assert!(resolve(root, "../other").is_err());
+    assert!(resolve(root, "/etc").is_err());
+  }
+}
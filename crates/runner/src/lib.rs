@@ -2,12 +2,24 @@
 mod astro_runner;
 mod command;
 mod docker;
+#[cfg(unix)]
+mod engine;
 mod executors;
+mod image_builder;
 mod metadata;
+mod output;
 mod plugin;
+mod registry;
+mod services;
 mod utils;
 
 pub use crate::astro_runner::{AstroRunner, AstroRunnerBuilder};
 pub use command::Command;
-pub use executors::{DockerExecutor, HostExecutor};
+#[cfg(unix)]
+pub use engine::{EngineClient, DEFAULT_DOCKER_SOCKET};
+#[cfg(unix)]
+pub use executors::EngineExecutor;
+pub use executors::{DockerExecutor, HostExecutor, JobContainerExecutor};
+pub use output::{OutputOptions, DEFAULT_MAX_LINE_LENGTH};
 pub use plugin::*;
+pub use registry::DEFAULT_PULL_RETRIES;
@@ -16,6 +16,8 @@ impl PathBufTryToString for PathBuf {
 
 #[derive(Clone)]
 pub struct Metadata {
+  /// Job directory, parent of the step working directories
+  pub job_directory: PathBuf,
   /// Step working directory
   pub step_host_working_directory: PathBuf,
   /// Job data directory
@@ -24,8 +26,12 @@ pub struct Metadata {
   pub cache_directory: PathBuf,
   /// Entrypoint path
   pub entrypoint_path: PathBuf,
+  /// Step summary file, see `astro_run::STEP_SUMMARY_ENV`
+  pub summary_path: PathBuf,
   /// Docker name
   pub docker_name: String,
+  /// Name of the container shared by the steps of the job
+  pub job_docker_name: String,
   /// Working directory on docker container
   pub docker_working_directory: String,
 }
@@ -82,28 +88,28 @@ impl MetadataBuilder {
     let job_key = step_id.job_key();
     let step_number = step_id.step_number();
 
-    let job_data_directory = repo_working_directory
-      .join(&workflow_id)
-      .join(&job_key)
-      .join("data");
+    let job_directory = repo_working_directory.join(&workflow_id).join(&job_key);
+    let job_data_directory = job_directory.join("data");
 
     // Step working directory
-    let step_host_working_directory = repo_working_directory
-      .join(&workflow_id)
-      .join(&job_key)
-      .join(step_number.to_string());
+    let step_host_working_directory = job_directory.join(step_number.to_string());
 
     let entrypoint_path = step_host_working_directory.join("entrypoint");
+    let summary_path = step_host_working_directory.join("summary.md");
     let docker_name = format!("{}-{}-{}", workflow_id, job_key, step_number);
+    let job_docker_name = format!("{}-{}", workflow_id, job_key);
     let docker_working_directory = String::from("/home/runner/work");
 
     Metadata {
       docker_name,
+      job_docker_name,
+      job_directory,
       step_host_working_directory,
       job_data_directory,
       cache_directory,
       docker_working_directory,
       entrypoint_path,
+      summary_path,
     }
   }
 }
@@ -128,6 +134,10 @@ mod tests {
       directories.step_host_working_directory,
       PathBuf::from("/home/runner/work/panghu-huang/astro-run/workflow-id/job-key/1")
     );
+    assert_eq!(
+      directories.job_directory,
+      PathBuf::from("/home/runner/work/panghu-huang/astro-run/workflow-id/job-key")
+    );
     assert_eq!(
       directories.job_data_directory,
       PathBuf::from("/home/runner/work/panghu-huang/astro-run/workflow-id/job-key/data")
@@ -140,7 +150,12 @@ mod tests {
       directories.entrypoint_path,
       PathBuf::from("/home/runner/work/panghu-huang/astro-run/workflow-id/job-key/1/entrypoint")
     );
+    assert_eq!(
+      directories.summary_path,
+      PathBuf::from("/home/runner/work/panghu-huang/astro-run/workflow-id/job-key/1/summary.md")
+    );
     assert_eq!(directories.docker_name, "workflow-id-job-key-1");
+    assert_eq!(directories.job_docker_name, "workflow-id-job-key");
     assert_eq!(directories.docker_working_directory, "/home/runner/work");
   }
 
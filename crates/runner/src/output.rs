@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+
+/// Longest line kept in the logs, in bytes of raw output
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// How the output of a command is turned into log lines
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputOptions {
+  /// Removes ANSI escape sequences such as colors and cursor movements
+  pub strip_ansi: bool,
+  /// Longer lines are cut and end with a marker telling how many bytes were dropped
+  pub max_line_length: usize,
+}
+
+impl Default for OutputOptions {
+  fn default() -> Self {
+    Self {
+      strip_ansi: false,
+      max_line_length: DEFAULT_MAX_LINE_LENGTH,
+    }
+  }
+}
+
+/// Splits raw output into lines.
+///
+/// A carriage return which is not followed by a line feed rewinds the line, so progress bars
+/// only keep their last state. Invalid UTF-8 is replaced instead of failing the step.
+pub(crate) struct LineDecoder {
+  options: OutputOptions,
+  line: Vec<u8>,
+  truncated: usize,
+  carriage_return: bool,
+}
+
+impl LineDecoder {
+  pub fn new(options: OutputOptions) -> Self {
+    Self {
+      options,
+      line: vec![],
+      truncated: 0,
+      carriage_return: false,
+    }
+  }
+
+  /// Returns the lines completed by `bytes`
+  pub fn feed(&mut self, bytes: &[u8]) -> Vec<String> {
+    let mut lines = vec![];
+
+    for &byte in bytes {
+      match byte {
+        b'\n' => lines.push(self.take_line()),
+        b'\r' => self.carriage_return = true,
+        _ => {
+          if self.carriage_return {
+            self.line.clear();
+            self.truncated = 0;
+            self.carriage_return = false;
+          }
+
+          if self.line.len() < self.options.max_line_length {
+            self.line.push(byte);
+          } else {
+            self.truncated += 1;
+          }
+        }
+      }
+    }
+
+    lines
+  }
+
+  /// Returns the last line if the output does not end with a line feed
+  pub fn finish(&mut self) -> Option<String> {
+    if self.line.is_empty() && self.truncated == 0 {
+      return None;
+    }
+
+    Some(self.take_line())
+  }
+
+  fn take_line(&mut self) -> String {
+    let mut bytes = std::mem::take(&mut self.line);
+    let mut truncated = std::mem::take(&mut self.truncated);
+    self.carriage_return = false;
+
+    if truncated > 0 {
+      // Don't leave half of a character at the cut
+      let len = bytes.len();
+      bytes.truncate(len - partial_char_len(&bytes));
+      truncated += len - bytes.len();
+    }
+
+    let mut line = String::from_utf8_lossy(&bytes).into_owned();
+
+    if self.options.strip_ansi {
+      line = strip_ansi_codes(&line);
+    }
+
+    if truncated > 0 {
+      line.push_str(&format!(" ... [{} bytes truncated]", truncated));
+    }
+
+    line
+  }
+}
+
+/// Number of bytes at the end which belong to an incomplete UTF-8 character
+pub(crate) fn partial_char_len(bytes: &[u8]) -> usize {
+  for i in 1..=bytes.len().min(4) {
+    let byte = bytes[bytes.len() - i];
+    // Continuation bytes are 10xxxxxx
+    if byte & 0xC0 == 0x80 {
+      continue;
+    }
+
+    let width = match byte {
+      0xC0..=0xDF => 2,
+      0xE0..=0xEF => 3,
+      0xF0..=0xF7 => 4,
+      _ => 1,
+    };
+
+    return if width > i { i } else { 0 };
+  }
+
+  0
+}
+
+/// Removes CSI sequences (colors, cursor movements), OSC sequences (titles, hyperlinks)
+/// and other two byte escape sequences
+pub(crate) fn strip_ansi_codes(text: &str) -> String {
+  let mut output = String::with_capacity(text.len());
+  let mut chars = text.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    if c != '\x1b' {
+      output.push(c);
+      continue;
+    }
+
+    match chars.next() {
+      // ESC [ parameters, ended by a byte in the range 0x40-0x7E
+      Some('[') => {
+        for c in chars.by_ref() {
+          if ('\x40'..='\x7e').contains(&c) {
+            break;
+          }
+        }
+      }
+      // ESC ] text, ended by BEL or ESC \
+      Some(']') => {
+        while let Some(c) = chars.next() {
+          if c == '\x07' {
+            break;
+          }
+          if c == '\x1b' {
+            chars.next_if_eq(&'\\');
+            break;
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+
+  output
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn decode(options: OutputOptions, chunks: &[&[u8]]) -> Vec<String> {
+    let mut decoder = LineDecoder::new(options);
+    let mut lines = vec![];
+
+    for chunk in chunks {
+      lines.extend(decoder.feed(chunk));
+    }
+    lines.extend(decoder.finish());
+
+    lines
+  }
+
+  #[test]
+  fn test_lines() {
+    let lines = decode(OutputOptions::default(), &[b"hello\nwor", b"ld\r\n\nlast"]);
+
+    assert_eq!(lines, vec!["hello", "world", "", "last"]);
+  }
+
+  #[test]
+  fn test_carriage_return() {
+    let lines = decode(
+      OutputOptions::default(),
+      &[
+        b"Downloading 10%\rDownloading 50%\r",
+        b"Downloading 100%\ndone\r",
+      ],
+    );
+
+    assert_eq!(lines, vec!["Downloading 100%", "done"]);
+  }
+
+  #[test]
+  fn test_invalid_utf8() {
+    let lines = decode(OutputOptions::default(), &[b"caf\xc3", b"\xa9 \xff\n"]);
+
+    assert_eq!(lines, vec!["café \u{fffd}"]);
+  }
+
+  #[test]
+  fn test_max_line_length() {
+    let options = OutputOptions {
+      max_line_length: 5,
+      ..Default::default()
+    };
+    let lines = decode(options, &["abcdefgh\nabcdé\nok\n".as_bytes()]);
+
+    assert_eq!(
+      lines,
+      vec![
+        "abcde ... [3 bytes truncated]",
+        "abcd ... [2 bytes truncated]",
+        "ok"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_strip_ansi() {
+    let options = OutputOptions {
+      strip_ansi: true,
+      ..Default::default()
+    };
+    let lines = decode(
+      options,
+      &[b"\x1b[1;32mCompiling\x1b[0m crate\n\x1b]8;;https://a.b\x1b\\link\x1b]8;;\x07\n"],
+    );
+    assert_eq!(lines, vec!["Compiling crate", "link"]);
+
+    let lines = decode(OutputOptions::default(), &[b"\x1b[31mred\x1b[0m\n"]);
+    assert_eq!(lines, vec!["\x1b[31mred\x1b[0m"]);
+  }
+}
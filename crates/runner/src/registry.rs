@@ -0,0 +1,259 @@
+#[cfg(unix)]
+use crate::engine::{self, EngineClient};
+use crate::{command::Command, metadata::PathBufTryToString};
+use astro_run::{Error, Log, PullPolicy, Result, RunResult, StreamSender};
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+
+/// Number of times a failed pull is attempted again
+pub const DEFAULT_PULL_RETRIES: u32 = 2;
+
+#[derive(Debug, Clone)]
+pub struct RegistryCredentials {
+  pub username: String,
+  pub password: String,
+}
+
+/// Pulls the images of the steps before they run, see `AstroRunnerBuilder::registry_credentials`
+#[derive(Clone)]
+pub struct ImagePuller {
+  /// Passed as `docker --config`, the default docker config is used when `None`
+  config_dir: Option<PathBuf>,
+  credentials: HashMap<String, RegistryCredentials>,
+  retries: u32,
+  logged_in: Arc<Mutex<bool>>,
+  /// Pulls through the Docker Engine API instead of the docker CLI
+  #[cfg(unix)]
+  engine: Option<EngineClient>,
+}
+
+impl ImagePuller {
+  pub fn new(
+    config_dir: Option<PathBuf>,
+    credentials: HashMap<String, RegistryCredentials>,
+    retries: u32,
+  ) -> Self {
+    Self {
+      config_dir,
+      credentials,
+      retries,
+      logged_in: Arc::new(Mutex::new(false)),
+      #[cfg(unix)]
+      engine: None,
+    }
+  }
+
+  /// Pulls with the Docker Engine API, the credentials are sent with each pull
+  /// instead of being stored by `docker login`
+  #[cfg(unix)]
+  pub fn engine(mut self, engine: EngineClient) -> Self {
+    self.engine = Some(engine);
+    self
+  }
+
+  /// Makes sure the image is on the runner according to the policy. The pull runs in its
+  /// own log group, with its output streamed to the step logs
+  pub async fn pull(&self, image: &str, policy: PullPolicy, sender: &StreamSender) -> Result<()> {
+    match policy {
+      PullPolicy::Never => return Ok(()),
+      PullPolicy::IfNotPresent if self.exists(image).await => return Ok(()),
+      _ => {}
+    }
+
+    sender
+      .send_async(Log::group_start(format!("Pull image {}", image)))
+      .await;
+    let res = self.pull_with_retries(image, sender).await;
+    sender.send_async(Log::group_end()).await;
+
+    if let Err(err) = res {
+      sender.send_async(Log::error(err.clone())).await;
+      return Err(Error::internal_runtime_error(err));
+    }
+
+    Ok(())
+  }
+
+  /// Returns the message of the failure, which is also logged to the step
+  async fn pull_with_retries(
+    &self,
+    image: &str,
+    sender: &StreamSender,
+  ) -> std::result::Result<(), String> {
+    // The engine sends the credentials with each pull
+    #[cfg(unix)]
+    let uses_engine = self.engine.is_some();
+    #[cfg(not(unix))]
+    let uses_engine = false;
+
+    if !uses_engine {
+      self.login().await?;
+    }
+
+    let mut attempt = 0;
+    loop {
+      if self.pull_once(image, sender).await? {
+        return Ok(());
+      }
+
+      if attempt >= self.retries {
+        return Err(format!("Failed to pull image {}", image));
+      }
+
+      attempt += 1;
+      sender
+        .send_async(Log::warning(format!(
+          "Failed to pull image {}, retrying ({}/{})",
+          image, attempt, self.retries
+        )))
+        .await;
+      tokio::time::sleep(Duration::from_secs(2 * attempt as u64)).await;
+    }
+  }
+
+  /// Returns whether the pull succeeded, errors which are not worth a retry are returned
+  async fn pull_once(
+    &self,
+    image: &str,
+    sender: &StreamSender,
+  ) -> std::result::Result<bool, String> {
+    #[cfg(unix)]
+    if let Some(engine) = &self.engine {
+      let registry = engine::image_registry(image);
+      let auth = self.credentials.get(registry).map(|credentials| {
+        engine::registry_auth(registry, &credentials.username, &credentials.password)
+      });
+
+      return match engine.pull_image(image, auth.as_deref(), sender).await {
+        Ok(()) => Ok(true),
+        Err(err) => {
+          sender.send_async(Log::error(err)).await;
+          Ok(false)
+        }
+      };
+    }
+
+    let res = self
+      .docker(["pull", image])
+      .execute(sender)
+      .await
+      .map_err(|err| err.to_string())?;
+
+    Ok(res == RunResult::Succeeded)
+  }
+
+  async fn exists(&self, image: &str) -> bool {
+    #[cfg(unix)]
+    if let Some(engine) = &self.engine {
+      return engine.image_exists(image).await.unwrap_or(false);
+    }
+
+    Command::argv("docker", ["image", "inspect", image])
+      .exec()
+      .await
+      .is_ok()
+  }
+
+  /// Logs in to the configured registries once, the credentials are kept in the docker config
+  async fn login(&self) -> std::result::Result<(), String> {
+    let mut logged_in = self.logged_in.lock().await;
+
+    if *logged_in {
+      return Ok(());
+    }
+
+    for (registry, credentials) in &self.credentials {
+      log::trace!("Logging in to registry {}", registry);
+
+      self
+        .docker([
+          "login",
+          "--username",
+          &credentials.username,
+          "--password-stdin",
+          registry,
+        ])
+        .stdin(credentials.password.clone())
+        .exec()
+        .await
+        .map_err(|err| format!("Failed to log in to registry {}: {}", registry, err))?;
+    }
+
+    *logged_in = true;
+
+    Ok(())
+  }
+
+  /// Builds an image with the docker CLI, logged in to the configured registries so that
+  /// private base images can be pulled
+  pub async fn build(&self, args: Vec<String>, sender: &StreamSender) -> Result<RunResult> {
+    self.login().await.map_err(Error::internal_runtime_error)?;
+
+    self.docker(args).execute(sender).await
+  }
+
+  fn docker<I, S>(&self, args: I) -> Command
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    let mut docker_args = vec![];
+
+    if let Some(config_dir) = self
+      .config_dir
+      .as_ref()
+      .and_then(|dir| dir.to_string().ok())
+    {
+      docker_args.push("--config".to_string());
+      docker_args.push(config_dir);
+    }
+
+    docker_args.extend(args.into_iter().map(Into::into));
+
+    Command::argv("docker", docker_args)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use astro_run::{stream, WorkflowLogType};
+  use tokio_stream::StreamExt;
+
+  #[astro_run_test::test]
+  async fn test_never_pull() {
+    let puller = ImagePuller::new(None, HashMap::new(), 0);
+    let (sender, receiver) = stream();
+
+    let res = puller
+      .pull("astro-run/not-exists", PullPolicy::Never, &sender)
+      .await;
+    sender.succeeded();
+
+    assert!(res.is_ok());
+    assert_eq!(receiver.collect::<Vec<_>>().await.len(), 0);
+  }
+
+  #[astro_run_test::test]
+  async fn test_pull_failed() {
+    let puller = ImagePuller::new(None, HashMap::new(), 0);
+    let (sender, receiver) = stream();
+
+    let res = puller
+      .pull("astro-run/not-exists:invalid", PullPolicy::Always, &sender)
+      .await;
+    sender.failed(1);
+
+    assert!(res.is_err());
+
+    let logs: Vec<_> = receiver.collect().await;
+    assert_eq!(logs[0].log_type, WorkflowLogType::GroupStart);
+    assert_eq!(logs[0].message, "Pull image astro-run/not-exists:invalid");
+    let last = logs.last().unwrap();
+    assert!(last.is_error());
+    assert_eq!(
+      last.message,
+      "Failed to pull image astro-run/not-exists:invalid"
+    );
+  }
+}
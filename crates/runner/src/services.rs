@@ -0,0 +1,165 @@
+use crate::{command::Command, docker::Docker, registry::ImagePuller};
+use astro_run::{Error, HealthCheck, Id, JobId, PullPolicy, Result, Service, StreamSender};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+
+/// How often the health of a starting service is checked
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Time given to docker to report the health of a service, on top of the time its
+/// health check may take
+const HEALTH_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// The services of a job and the network they share with the step containers.
+///
+/// Started before the first step, and removed when the job completes.
+#[derive(Clone)]
+pub struct JobServices {
+  /// Network of the job, services are reachable by their name on it
+  pub network: String,
+  services: HashMap<Id, Service>,
+  /// Result of the start, `None` until the first step starts the services
+  started: Arc<Mutex<Option<std::result::Result<(), String>>>>,
+}
+
+impl JobServices {
+  pub fn new(job_id: &JobId, services: HashMap<Id, Service>) -> Self {
+    Self {
+      network: format!("{}-{}", job_id.workflow_id().inner(), job_id.job_key()),
+      services,
+      started: Arc::new(Mutex::new(None)),
+    }
+  }
+
+  /// Starts the services and waits for their health checks. Only the first call starts them,
+  /// the later ones return the same result
+  pub async fn start(&self, puller: &ImagePuller, sender: &StreamSender) -> Result<()> {
+    let mut started = self.started.lock().await;
+
+    if started.is_none() {
+      // Kept if the start is dropped halfway, so that `stop` removes what was started
+      *started = Some(Err(
+        "The services were interrupted while starting".to_string(),
+      ));
+
+      let res = self
+        .start_services(puller, sender)
+        .await
+        .map_err(|err| err.to_string());
+      *started = Some(res);
+    }
+
+    started
+      .clone()
+      .unwrap_or(Ok(()))
+      .map_err(Error::internal_runtime_error)
+  }
+
+  /// Removes the services and the network, if they were started
+  pub async fn stop(&self) {
+    if self.started.lock().await.take().is_none() {
+      return;
+    }
+
+    for name in self.services.keys() {
+      log::trace!("Removing service {}", name);
+      Command::argv("docker", ["rm", "--force", &self.container_name(name)])
+        .exec()
+        .await
+        .ok();
+    }
+
+    Command::argv("docker", ["network", "rm", &self.network])
+      .exec()
+      .await
+      .ok();
+  }
+
+  async fn start_services(&self, puller: &ImagePuller, sender: &StreamSender) -> Result<()> {
+    log::trace!("Creating network {}", self.network);
+    Command::argv("docker", ["network", "create", &self.network])
+      .exec()
+      .await?;
+
+    for (name, service) in &self.services {
+      log::trace!("Starting service {}", name);
+      puller
+        .pull(&service.image, PullPolicy::IfNotPresent, sender)
+        .await?;
+
+      let mut docker = Docker::new(service.image.clone())
+        .name(self.container_name(name))
+        .detach(true)
+        .network(self.network.clone())
+        .network_alias(name.clone());
+
+      for (key, env) in &service.environments {
+        docker = docker.environment(key.clone(), env.to_string());
+      }
+
+      for port in &service.ports {
+        docker = docker.port(port.clone());
+      }
+
+      if let Some(health_check) = &service.health_check {
+        docker = docker.health_check(health_check.clone());
+      }
+
+      Command::from(docker).exec().await.map_err(|err| {
+        Error::internal_runtime_error(format!("Failed to start service `{}`: {}", name, err))
+      })?;
+    }
+
+    for (name, service) in &self.services {
+      if let Some(health_check) = &service.health_check {
+        self.wait_until_healthy(name, health_check).await?;
+      }
+    }
+
+    Ok(())
+  }
+
+  async fn wait_until_healthy(&self, name: &str, health_check: &HealthCheck) -> Result<()> {
+    let timeout = (health_check.interval + health_check.timeout) * (health_check.retries + 1)
+      + HEALTH_GRACE_PERIOD;
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    loop {
+      // Docker marks the service unhealthy once the retries are used up
+      let status = Command::argv(
+        "docker",
+        [
+          "inspect",
+          "--format",
+          "{{.State.Health.Status}}",
+          &self.container_name(name),
+        ],
+      )
+      .exec()
+      .await
+      .map_err(|err| {
+        Error::internal_runtime_error(format!("Service `{}` is not running: {}", name, err))
+      })?;
+
+      match status.as_str() {
+        "healthy" => return Ok(()),
+        "unhealthy" => {
+          return Err(Error::internal_runtime_error(format!(
+            "Service `{}` is unhealthy",
+            name
+          )))
+        }
+        _ if tokio::time::Instant::now() >= deadline => {
+          return Err(Error::internal_runtime_error(format!(
+            "Service `{}` is not healthy after {:?}",
+            name, timeout
+          )))
+        }
+        _ => tokio::time::sleep(HEALTH_POLL_INTERVAL).await,
+      }
+    }
+  }
+
+  fn container_name(&self, service: &str) -> String {
+    format!("{}-{}", self.network, service)
+  }
+}
@@ -1,6 +1,13 @@
+use crate::output::partial_char_len;
 use astro_run::Result;
 use std::path::PathBuf;
-use tokio::{fs, io::AsyncWriteExt};
+use tokio::{
+  fs,
+  io::{AsyncReadExt, AsyncWriteExt},
+};
+
+/// Largest summary kept, in bytes
+const MAX_SUMMARY_SIZE: u64 = 1024 * 1024;
 
 pub async fn create_executable_file(file_path: &PathBuf, content: &String) -> Result<()> {
   let mut file;
@@ -31,3 +38,82 @@ pub async fn create_executable_file(file_path: &PathBuf, content: &String) -> Re
 
   Ok(())
 }
+
+/// Creates an empty summary file for the step to append to
+pub async fn create_summary_file(file_path: &PathBuf) -> Result<()> {
+  fs::write(file_path, "").await?;
+
+  Ok(())
+}
+
+/// Reads the summary written by the step, `None` if it's empty.
+///
+/// Only the first `MAX_SUMMARY_SIZE` bytes are kept, followed by a marker telling how many
+/// bytes were dropped
+pub async fn read_summary_file(file_path: &PathBuf) -> Option<String> {
+  let file = fs::File::open(file_path).await.ok()?;
+  let size = file.metadata().await.ok()?.len();
+
+  let mut bytes = vec![];
+  file
+    .take(MAX_SUMMARY_SIZE)
+    .read_to_end(&mut bytes)
+    .await
+    .ok()?;
+
+  let mut truncated = size.saturating_sub(bytes.len() as u64);
+  if truncated > 0 {
+    // Don't leave half of a character at the cut
+    let len = bytes.len();
+    bytes.truncate(len - partial_char_len(&bytes));
+    truncated += (len - bytes.len()) as u64;
+  }
+
+  let mut summary = String::from_utf8_lossy(&bytes).into_owned();
+  if summary.trim().is_empty() {
+    return None;
+  }
+
+  if truncated > 0 {
+    summary.push_str(&format!("\n\n... [{} bytes truncated]", truncated));
+  }
+
+  Some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[astro_run_test::test]
+  async fn test_read_summary_file() {
+    let path = std::env::temp_dir().join(format!("astro-runner-summary-{}.md", std::process::id()));
+
+    create_summary_file(&path).await.unwrap();
+    assert_eq!(read_summary_file(&path).await, None);
+
+    fs::write(&path, "# Summary\n").await.unwrap();
+    assert_eq!(
+      read_summary_file(&path).await,
+      Some("# Summary\n".to_string())
+    );
+
+    // The cut falls inside the last `é`
+    let content = format!(
+      "{}é{}",
+      "a".repeat(MAX_SUMMARY_SIZE as usize - 1),
+      "b".repeat(9)
+    );
+    fs::write(&path, &content).await.unwrap();
+    let summary = read_summary_file(&path).await.unwrap();
+    assert_eq!(
+      summary,
+      format!(
+        "{}\n\n... [11 bytes truncated]",
+        "a".repeat(MAX_SUMMARY_SIZE as usize - 1)
+      )
+    );
+
+    fs::remove_file(&path).await.unwrap();
+  }
+}
@@ -75,6 +75,161 @@ jobs:
   assert_eq!(job_result.steps[1].state, WorkflowState::Succeeded);
 }
 
+#[astro_run_test::test(docker)]
+async fn test_job_container() {
+  // Pull the images before running the test
+  Command::new("docker pull ubuntu").exec().await.unwrap();
+  Command::new("docker pull ubuntu:22.04")
+    .exec()
+    .await
+    .unwrap();
+
+  let workflow = r#"
+jobs:
+  test:
+    name: Test Job
+    container: ubuntu
+    steps:
+      - run: echo "Hello World" > /tmp/state
+      - run: cat /tmp/state
+      - container: ubuntu:22.04
+        run: test -f /tmp/state || echo "Not found"
+  "#;
+
+  let runner = AstroRunner::builder().job_container(true).build().unwrap();
+
+  let astro_run = AstroRun::builder()
+    .runner(runner)
+    .plugin(assert_logs_plugin(vec!["Hello World", "Not found"]))
+    .build();
+
+  let workflow = Workflow::builder()
+    .config(workflow)
+    .build(&astro_run)
+    .await
+    .unwrap();
+
+  let ctx = astro_run.execution_context().build();
+
+  let res = workflow.run(ctx).await;
+
+  assert_eq!(res.state, WorkflowState::Succeeded);
+  let job_result = res.jobs.get("test").unwrap();
+  assert_eq!(job_result.steps.len(), 3);
+
+  // Removed when the job completes
+  let containers = Command::new(format!(
+    "docker ps --all --quiet --filter name={}-test",
+    workflow.id.inner()
+  ))
+  .exec()
+  .await
+  .unwrap();
+  assert!(containers.is_empty());
+}
+
+#[astro_run_test::test(docker)]
+async fn test_services() {
+  // Pull the images before running the test
+  Command::new("docker pull ubuntu").exec().await.unwrap();
+  Command::new("docker pull redis").exec().await.unwrap();
+
+  let workflow = r#"
+jobs:
+  test:
+    name: Test Job
+    services:
+      redis:
+        image: redis
+        health-check:
+          command: redis-cli ping
+          interval: 1s
+    steps:
+      - container: ubuntu
+        run: getent hosts redis
+  "#;
+
+  let runner = AstroRunner::builder().build().unwrap();
+
+  let astro_run = AstroRun::builder().runner(runner).build();
+
+  let workflow = Workflow::builder()
+    .config(workflow)
+    .build(&astro_run)
+    .await
+    .unwrap();
+
+  let ctx = astro_run.execution_context().build();
+
+  let res = workflow.run(ctx).await;
+
+  assert_eq!(res.state, WorkflowState::Succeeded);
+
+  // Removed when the job completes
+  let containers = Command::new(format!(
+    "docker ps --all --quiet --filter name={}-test-redis",
+    workflow.id.inner()
+  ))
+  .exec()
+  .await
+  .unwrap();
+  assert!(containers.is_empty());
+}
+
+#[astro_run_test::test(docker)]
+async fn test_cancel_services_start() {
+  Command::new("docker pull redis").exec().await.unwrap();
+
+  // Never healthy within the test
+  let workflow = r#"
+jobs:
+  test:
+    services:
+      redis:
+        image: redis
+        health-check:
+          command: "false"
+          interval: 30s
+    steps:
+      - container: ubuntu
+        run: echo "Hello world"
+  "#;
+
+  let runner = AstroRunner::builder().build().unwrap();
+
+  let astro_run = AstroRun::builder().runner(runner).build();
+
+  let workflow = Workflow::builder()
+    .config(workflow)
+    .build(&astro_run)
+    .await
+    .unwrap();
+
+  tokio::task::spawn({
+    let astro_run = astro_run.clone();
+    let job_id = workflow.jobs.get("test").unwrap().id.clone();
+    async move {
+      tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+      astro_run.cancel_job(&job_id).unwrap();
+    }
+  });
+
+  let res = workflow.run(astro_run.execution_context().build()).await;
+
+  let job_result = res.jobs.get("test").unwrap();
+  assert_eq!(job_result.steps[0].state, WorkflowState::Cancelled);
+
+  // Removed once the start is interrupted
+  let containers = Command::new(format!(
+    "docker ps --all --quiet --filter name={}-test-redis",
+    workflow.id.inner()
+  ))
+  .exec()
+  .await
+  .unwrap();
+  assert!(containers.is_empty());
+}
+
 #[astro_run_test::test(docker)]
 async fn test_docker_volume() {
   // Pull the image before running the test
@@ -181,6 +336,57 @@ async fn test_host() {
   assert_eq!(job_result.steps[0].state, WorkflowState::Succeeded);
 }
 
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[astro_run_test::test]
+async fn test_host_summary() {
+  let workflow = format!(
+    r###"
+  jobs:
+    test:
+      name: Test Job
+      steps:
+        - container: host/{os}
+          run: echo "## Tests passed" >> $ASTRO_RUN_STEP_SUMMARY
+        - container: host/{os}
+          run: echo "No summary"
+        - container: host/{os}
+          run: echo "| Benchmark | 1ms |" >> $ASTRO_RUN_STEP_SUMMARY
+    "###,
+    os = std::env::consts::OS
+  );
+  let working_dir = std::env::temp_dir().join("astro-run-summary");
+
+  let runner = AstroRunner::builder()
+    .working_directory(working_dir)
+    .build()
+    .unwrap();
+
+  let astro_run = AstroRun::builder().runner(runner).build();
+
+  let workflow = Workflow::builder()
+    .config(workflow)
+    .build(&astro_run)
+    .await
+    .unwrap();
+
+  let ctx = astro_run.execution_context().build();
+
+  let res = workflow.run(ctx).await;
+
+  assert_eq!(res.state, WorkflowState::Succeeded);
+  let job_result = res.jobs.get("test").unwrap();
+  assert_eq!(
+    job_result.steps[0].summary,
+    Some("## Tests passed\n".to_string())
+  );
+  assert_eq!(job_result.steps[1].summary, None);
+  assert_eq!(
+    job_result.summary,
+    Some("## Tests passed\n\n| Benchmark | 1ms |".to_string())
+  );
+  assert_eq!(res.summary, job_result.summary);
+}
+
 #[astro_run_test::test]
 async fn test_before_run() {
   struct TestPlugin;
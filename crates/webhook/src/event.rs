@@ -0,0 +1,311 @@
+use astro_run::{Error, Result, TriggerEvent};
+use serde_json::Value;
+
+fn field(payload: &Value, pointer: &str) -> Result<String> {
+  match payload.pointer(pointer) {
+    Some(Value::String(value)) => Ok(value.clone()),
+    Some(Value::Number(value)) => Ok(value.to_string()),
+    _ => Err(Error::error(format!(
+      "Missing `{}` in webhook payload",
+      pointer
+    ))),
+  }
+}
+
+fn optional_field(payload: &Value, pointer: &str) -> Option<String> {
+  field(payload, pointer).ok()
+}
+
+/// Tag pushes become `tag` events, branch pushes `push` events
+fn push_event(ref_name: &str) -> (String, String) {
+  if let Some(tag) = ref_name.strip_prefix("refs/tags/") {
+    ("tag".to_string(), tag.to_string())
+  } else {
+    let branch = ref_name.strip_prefix("refs/heads/").unwrap_or(ref_name);
+
+    ("push".to_string(), branch.to_string())
+  }
+}
+
+/// Parses a GitHub webhook into a `TriggerEvent`.
+///
+/// `event` is the `X-GitHub-Event` header. Returns `None` for events which don't trigger runs,
+/// such as `ping`, deleted branches or closed pull requests.
+pub fn parse_github_event(event: &str, payload: Value) -> Result<Option<TriggerEvent>> {
+  if !matches!(event, "push" | "pull_request") {
+    return Ok(None);
+  }
+
+  let repo_owner = optional_field(&payload, "/repository/owner/login")
+    .map_or_else(|| field(&payload, "/repository/owner/name"), Ok)?;
+  let repo_name = field(&payload, "/repository/name")?;
+
+  let trigger_event = match event {
+    "push" => {
+      if payload.pointer("/deleted") == Some(&Value::Bool(true)) {
+        return Ok(None);
+      }
+
+      let ref_name = field(&payload, "/ref")?;
+      let (event, branch) = push_event(&ref_name);
+
+      TriggerEvent {
+        event,
+        repo_owner,
+        repo_name,
+        pr_number: None,
+        sha: field(&payload, "/after")?,
+        branch,
+        ref_name,
+        provider: Some("github".to_string()),
+        before: optional_field(&payload, "/before"),
+        base_ref: None,
+        payload: Some(payload),
+      }
+    }
+    "pull_request" => {
+      let action = field(&payload, "/action")?;
+      if !matches!(action.as_str(), "opened" | "synchronize" | "reopened") {
+        return Ok(None);
+      }
+
+      let pr_number = payload
+        .pointer("/number")
+        .and_then(|number| number.as_i64())
+        .ok_or(Error::error("Missing `/number` in webhook payload"))?;
+      let base_ref = field(&payload, "/pull_request/base/ref")?;
+
+      TriggerEvent {
+        event: "pull_request".to_string(),
+        repo_owner,
+        repo_name,
+        pr_number: Some(pr_number),
+        sha: field(&payload, "/pull_request/head/sha")?,
+        branch: base_ref.clone(),
+        ref_name: format!("refs/pull/{}/merge", pr_number),
+        provider: Some("github".to_string()),
+        before: None,
+        base_ref: Some(base_ref),
+        payload: Some(payload),
+      }
+    }
+    _ => return Ok(None),
+  };
+
+  Ok(Some(trigger_event))
+}
+
+/// Parses a GitLab webhook into a `TriggerEvent`.
+///
+/// `event` is the `X-Gitlab-Event` header. Merge requests become `pull_request` events.
+pub fn parse_gitlab_event(event: &str, payload: Value) -> Result<Option<TriggerEvent>> {
+  if !matches!(event, "Push Hook" | "Tag Push Hook" | "Merge Request Hook") {
+    return Ok(None);
+  }
+
+  let project = field(&payload, "/project/path_with_namespace")?;
+  let (repo_owner, repo_name) = project
+    .rsplit_once('/')
+    .map(|(owner, name)| (owner.to_string(), name.to_string()))
+    .ok_or(Error::error(format!(
+      "Invalid GitLab project `{}`",
+      project
+    )))?;
+
+  let trigger_event = match event {
+    "Push Hook" | "Tag Push Hook" => {
+      // `checkout_sha` is null when a branch or tag is deleted
+      let Some(sha) = optional_field(&payload, "/checkout_sha") else {
+        return Ok(None);
+      };
+
+      let ref_name = field(&payload, "/ref")?;
+      let (event, branch) = push_event(&ref_name);
+
+      TriggerEvent {
+        event,
+        repo_owner,
+        repo_name,
+        pr_number: None,
+        sha,
+        branch,
+        ref_name,
+        provider: Some("gitlab".to_string()),
+        before: optional_field(&payload, "/before"),
+        base_ref: None,
+        payload: Some(payload),
+      }
+    }
+    "Merge Request Hook" => {
+      let action = field(&payload, "/object_attributes/action")?;
+      if !matches!(action.as_str(), "open" | "reopen" | "update") {
+        return Ok(None);
+      }
+
+      let iid = payload
+        .pointer("/object_attributes/iid")
+        .and_then(|iid| iid.as_i64())
+        .ok_or(Error::error(
+          "Missing `/object_attributes/iid` in webhook payload",
+        ))?;
+      let target_branch = field(&payload, "/object_attributes/target_branch")?;
+
+      TriggerEvent {
+        event: "pull_request".to_string(),
+        repo_owner,
+        repo_name,
+        pr_number: Some(iid),
+        sha: field(&payload, "/object_attributes/last_commit/id")?,
+        branch: target_branch.clone(),
+        ref_name: format!("refs/merge-requests/{}/head", iid),
+        provider: Some("gitlab".to_string()),
+        before: None,
+        base_ref: Some(target_branch),
+        payload: Some(payload),
+      }
+    }
+    _ => return Ok(None),
+  };
+
+  Ok(Some(trigger_event))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_github_push() {
+    let payload = json!({
+      "ref": "refs/heads/main",
+      "before": "000000",
+      "after": "abcdef",
+      "deleted": false,
+      "repository": {
+        "name": "astro-run",
+        "owner": { "name": "panghu-huang", "login": "panghu-huang" }
+      }
+    });
+
+    let event = parse_github_event("push", payload.clone())
+      .unwrap()
+      .unwrap();
+    assert_eq!(event.event, "push");
+    assert_eq!(event.branch, "main");
+    assert_eq!(event.sha, "abcdef");
+    assert_eq!(event.repo_owner, "panghu-huang");
+    assert_eq!(event.provider, Some("github".to_string()));
+
+    let mut tag = payload.clone();
+    tag["ref"] = json!("refs/tags/v1.0.0");
+    let event = parse_github_event("push", tag).unwrap().unwrap();
+    assert_eq!(event.event, "tag");
+    assert_eq!(event.branch, "v1.0.0");
+
+    let mut deleted = payload;
+    deleted["deleted"] = json!(true);
+    assert!(parse_github_event("push", deleted).unwrap().is_none());
+  }
+
+  #[test]
+  fn test_github_pull_request() {
+    let payload = json!({
+      "action": "synchronize",
+      "number": 12,
+      "pull_request": {
+        "head": { "sha": "abcdef", "ref": "feature" },
+        "base": { "sha": "123456", "ref": "main" }
+      },
+      "repository": {
+        "name": "astro-run",
+        "owner": { "login": "panghu-huang" }
+      }
+    });
+
+    let event = parse_github_event("pull_request", payload.clone())
+      .unwrap()
+      .unwrap();
+    assert_eq!(event.event, "pull_request");
+    assert_eq!(event.pr_number, Some(12));
+    assert_eq!(event.sha, "abcdef");
+    assert_eq!(event.branch, "main");
+    assert_eq!(event.ref_name, "refs/pull/12/merge");
+
+    let mut closed = payload;
+    closed["action"] = json!("closed");
+    assert!(parse_github_event("pull_request", closed)
+      .unwrap()
+      .is_none());
+
+    let error = parse_github_event("push", json!({})).unwrap_err();
+    assert_eq!(
+      error,
+      Error::error("Missing `/repository/owner/name` in webhook payload")
+    );
+  }
+
+  #[test]
+  fn test_gitlab_events() {
+    let project = json!({ "path_with_namespace": "group/sub/astro-run" });
+
+    let event = parse_gitlab_event(
+      "Push Hook",
+      json!({
+        "ref": "refs/heads/main",
+        "before": "123456",
+        "checkout_sha": "abcdef",
+        "project": project,
+      }),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(event.event, "push");
+    assert_eq!(event.repo_owner, "group/sub");
+    assert_eq!(event.repo_name, "astro-run");
+    assert_eq!(event.before, Some("123456".to_string()));
+    assert_eq!(event.provider, Some("gitlab".to_string()));
+
+    let event = parse_gitlab_event(
+      "Tag Push Hook",
+      json!({
+        "ref": "refs/tags/v1.0.0",
+        "checkout_sha": "abcdef",
+        "project": project,
+      }),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(event.event, "tag");
+    assert_eq!(event.branch, "v1.0.0");
+
+    let event = parse_gitlab_event(
+      "Merge Request Hook",
+      json!({
+        "object_attributes": {
+          "action": "open",
+          "iid": 3,
+          "target_branch": "main",
+          "last_commit": { "id": "abcdef" }
+        },
+        "project": project,
+      }),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(event.event, "pull_request");
+    assert_eq!(event.pr_number, Some(3));
+    assert_eq!(event.base_ref, Some("main".to_string()));
+
+    let deleted = parse_gitlab_event(
+      "Push Hook",
+      json!({
+        "ref": "refs/heads/main",
+        "checkout_sha": null,
+        "project": project,
+      }),
+    )
+    .unwrap();
+    assert!(deleted.is_none());
+  }
+}
@@ -0,0 +1,9 @@
+mod event;
+mod server;
+mod signature;
+mod workflow_source;
+
+pub use event::*;
+pub use server::*;
+pub use signature::*;
+pub use workflow_source::*;
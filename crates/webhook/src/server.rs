@@ -0,0 +1,246 @@
+use crate::{
+  parse_github_event, parse_gitlab_event, verify_github_signature, verify_gitlab_token,
+  WorkflowSource,
+};
+use astro_run::{AstroRun, Error, Result, TriggerEvent, WorkflowRegistry};
+use axum::{
+  body::Bytes,
+  extract::State,
+  http::{HeaderMap, StatusCode},
+  routing::post,
+  Router,
+};
+use std::{net::TcpListener, sync::Arc};
+
+type Response = (StatusCode, String);
+
+struct WebhookState {
+  astro_run: AstroRun,
+  workflow_source: Box<dyn WorkflowSource>,
+  github_secret: Option<String>,
+  gitlab_token: Option<String>,
+  insecure_no_verification: bool,
+}
+
+/// # AstroRunWebhookServer
+/// Serves `POST /webhooks/github` and `POST /webhooks/gitlab`.
+///
+/// Requests are verified with the configured secret, parsed into a `TriggerEvent`,
+/// and every workflow of the repository whose `on` condition matches is started.
+/// Requests of a provider without a secret are rejected, unless verification is turned off
+/// with `AstroRunWebhookServerBuilder::insecure_no_verification`.
+/// The response body lists the ids of the started workflows.
+pub struct AstroRunWebhookServer {
+  state: Arc<WebhookState>,
+}
+
+impl AstroRunWebhookServer {
+  pub fn builder() -> AstroRunWebhookServerBuilder {
+    AstroRunWebhookServerBuilder::new()
+  }
+
+  pub fn router(&self) -> Router {
+    Router::new()
+      .route("/webhooks/github", post(github_webhook))
+      .route("/webhooks/gitlab", post(gitlab_webhook))
+      .with_state(Arc::clone(&self.state))
+  }
+
+  pub async fn serve(self, addr: &str) -> Result<()> {
+    let listener = TcpListener::bind(addr)?;
+
+    self.serve_with_listener(listener).await
+  }
+
+  pub async fn serve_with_listener(self, listener: TcpListener) -> Result<()> {
+    log::info!("Webhook server listening on {:?}", listener.local_addr());
+
+    axum::Server::from_tcp(listener)
+      .map_err(|err| Error::init_error(format!("Failed to start webhook server: {}", err)))?
+      .serve(self.router().into_make_service())
+      .await
+      .map_err(|err| Error::internal_runtime_error(format!("Webhook server error: {}", err)))
+  }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+  headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+async fn github_webhook(
+  State(state): State<Arc<WebhookState>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  if !state.insecure_no_verification {
+    let Some(secret) = &state.github_secret else {
+      return unconfigured("GitHub");
+    };
+    let signature = header(&headers, "x-hub-signature-256").unwrap_or_default();
+
+    if !verify_github_signature(secret, &body, signature) {
+      return (StatusCode::UNAUTHORIZED, "Invalid signature".to_string());
+    }
+  }
+
+  let event = header(&headers, "x-github-event").unwrap_or_default();
+
+  handle(&state, body, |payload| parse_github_event(event, payload)).await
+}
+
+async fn gitlab_webhook(
+  State(state): State<Arc<WebhookState>>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Response {
+  if !state.insecure_no_verification {
+    let Some(secret) = &state.gitlab_token else {
+      return unconfigured("GitLab");
+    };
+    let token = header(&headers, "x-gitlab-token").unwrap_or_default();
+
+    if !verify_gitlab_token(secret, token) {
+      return (StatusCode::UNAUTHORIZED, "Invalid token".to_string());
+    }
+  }
+
+  let event = header(&headers, "x-gitlab-event").unwrap_or_default();
+
+  handle(&state, body, |payload| parse_gitlab_event(event, payload)).await
+}
+
+/// Requests can not be verified without a secret
+fn unconfigured(provider: &str) -> Response {
+  log::warn!(
+    "Rejected {} webhook, the secret of {} webhooks is not configured",
+    provider,
+    provider
+  );
+
+  (
+    StatusCode::UNAUTHORIZED,
+    format!("{} webhooks are not configured", provider),
+  )
+}
+
+async fn handle<F>(state: &WebhookState, body: Bytes, parse: F) -> Response
+where
+  F: FnOnce(serde_json::Value) -> Result<Option<TriggerEvent>>,
+{
+  let event = serde_json::from_slice(&body)
+    .map_err(|err| Error::error(format!("Invalid webhook payload: {}", err)))
+    .and_then(parse);
+
+  let event = match event {
+    Ok(Some(event)) => event,
+    Ok(None) => return (StatusCode::ACCEPTED, "[]".to_string()),
+    Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()),
+  };
+
+  match start_workflows(state, event).await {
+    Ok(ids) => (
+      StatusCode::ACCEPTED,
+      serde_json::to_string(&ids).unwrap_or_default(),
+    ),
+    Err(err) => {
+      log::error!("Failed to start workflows: {}", err);
+      (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+    }
+  }
+}
+
+async fn start_workflows(state: &WebhookState, event: TriggerEvent) -> Result<Vec<String>> {
+  let mut registry = WorkflowRegistry::new(state.astro_run.clone());
+
+  for (name, config) in state.workflow_source.workflows(&event).await? {
+    registry.add(name, config);
+  }
+
+  let result = registry.trigger(event).await;
+
+  for skipped in &result.skipped {
+    log::trace!("Workflow `{}` skipped: {}", skipped.name, skipped.reason);
+  }
+
+  Ok(
+    result
+      .started
+      .iter()
+      .map(|workflow| workflow.id.to_string())
+      .collect(),
+  )
+}
+
+pub struct AstroRunWebhookServerBuilder {
+  astro_run: Option<AstroRun>,
+  workflow_source: Option<Box<dyn WorkflowSource>>,
+  github_secret: Option<String>,
+  gitlab_token: Option<String>,
+  insecure_no_verification: bool,
+}
+
+impl AstroRunWebhookServerBuilder {
+  pub fn new() -> Self {
+    Self {
+      astro_run: None,
+      workflow_source: None,
+      github_secret: None,
+      gitlab_token: None,
+      insecure_no_verification: false,
+    }
+  }
+
+  pub fn astro_run(mut self, astro_run: AstroRun) -> Self {
+    self.astro_run = Some(astro_run);
+    self
+  }
+
+  pub fn workflow_source(mut self, workflow_source: impl WorkflowSource + 'static) -> Self {
+    self.workflow_source = Some(Box::new(workflow_source));
+    self
+  }
+
+  /// Secret of GitHub webhooks, verified against `X-Hub-Signature-256`
+  pub fn github_secret(mut self, secret: impl Into<String>) -> Self {
+    self.github_secret = Some(secret.into());
+    self
+  }
+
+  /// Secret token of GitLab webhooks, verified against `X-Gitlab-Token`
+  pub fn gitlab_token(mut self, token: impl Into<String>) -> Self {
+    self.gitlab_token = Some(token.into());
+    self
+  }
+
+  /// Accepts every request without verifying it, even if a secret is configured.
+  /// Anyone who can reach the server is able to start workflows, only use it for local testing
+  pub fn insecure_no_verification(mut self) -> Self {
+    self.insecure_no_verification = true;
+    self
+  }
+
+  pub fn build(self) -> Result<AstroRunWebhookServer> {
+    let astro_run = self
+      .astro_run
+      .ok_or(Error::init_error("AstroRun is not set"))?;
+    let workflow_source = self
+      .workflow_source
+      .ok_or(Error::init_error("Workflow source is not set"))?;
+
+    Ok(AstroRunWebhookServer {
+      state: Arc::new(WebhookState {
+        astro_run,
+        workflow_source,
+        github_secret: self.github_secret,
+        gitlab_token: self.gitlab_token,
+        insecure_no_verification: self.insecure_no_verification,
+      }),
+    })
+  }
+}
+
+impl Default for AstroRunWebhookServerBuilder {
+  fn default() -> Self {
+    Self::new()
+  }
+}
@@ -0,0 +1,75 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Verifies the `X-Hub-Signature-256` header (`sha256=<hex digest>`) of a GitHub webhook
+pub fn verify_github_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+  let Some(digest) = signature.strip_prefix("sha256=") else {
+    return false;
+  };
+
+  let Ok(digest) = hex::decode(digest) else {
+    return false;
+  };
+
+  let Ok(mut mac) = HmacSha256::new_from_slice(secret.as_bytes()) else {
+    return false;
+  };
+
+  mac.update(body);
+  // Constant-time comparison
+  mac.verify_slice(&digest).is_ok()
+}
+
+/// Verifies the `X-Gitlab-Token` header of a GitLab webhook
+pub fn verify_gitlab_token(secret: &str, token: &str) -> bool {
+  if secret.len() != token.len() {
+    return false;
+  }
+
+  secret
+    .bytes()
+    .zip(token.bytes())
+    .fold(0, |diff, (a, b)| diff | (a ^ b))
+    == 0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_github_signature() {
+    // Example from the GitHub documentation
+    let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    assert!(verify_github_signature(
+      "It's a Secret to Everybody",
+      b"Hello, World!",
+      signature
+    ));
+    assert!(!verify_github_signature(
+      "It's a Secret to Everybody",
+      b"Hello, World",
+      signature
+    ));
+    assert!(!verify_github_signature(
+      "It's a Secret to Everybody",
+      b"Hello, World!",
+      "sha1=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+    ));
+    assert!(!verify_github_signature(
+      "It's a Secret to Everybody",
+      b"Hello, World!",
+      "sha256=invalid"
+    ));
+  }
+
+  #[test]
+  fn test_gitlab_token() {
+    assert!(verify_gitlab_token("secret-token", "secret-token"));
+    assert!(!verify_gitlab_token("secret-token", "secret-tokem"));
+    assert!(!verify_gitlab_token("secret-token", "secret"));
+  }
+}
@@ -0,0 +1,91 @@
+use astro_run::{Error, Result, TriggerEvent};
+use std::path::PathBuf;
+
+/// # WorkflowSource
+/// Locates the workflow files of the repository an event belongs to.
+#[async_trait::async_trait]
+pub trait WorkflowSource: Send + Sync {
+  /// Returns the name and config of every workflow of the repository
+  async fn workflows(&self, event: &TriggerEvent) -> Result<Vec<(String, String)>>;
+}
+
+/// Reads `*.yml` / `*.yaml` files from `<root>/<repo_owner>/<repo_name>/<workflows_dir>`.
+/// `workflows_dir` defaults to `.astro-run/workflows`.
+pub struct FileSystemWorkflowSource {
+  root: PathBuf,
+  workflows_dir: PathBuf,
+}
+
+impl FileSystemWorkflowSource {
+  pub fn new(root: impl Into<PathBuf>) -> Self {
+    Self {
+      root: root.into(),
+      workflows_dir: PathBuf::from(".astro-run").join("workflows"),
+    }
+  }
+
+  pub fn workflows_dir(mut self, workflows_dir: impl Into<PathBuf>) -> Self {
+    self.workflows_dir = workflows_dir.into();
+    self
+  }
+}
+
+#[async_trait::async_trait]
+impl WorkflowSource for FileSystemWorkflowSource {
+  async fn workflows(&self, event: &TriggerEvent) -> Result<Vec<(String, String)>> {
+    let is_invalid = |path: &str| {
+      path.is_empty()
+        || path
+          .split('/')
+          .any(|part| part.is_empty() || part == "." || part == "..")
+    };
+
+    // The owner may contain subgroups (`group/subgroup`), but must not escape the root
+    if is_invalid(&event.repo_owner) || is_invalid(&event.repo_name) {
+      return Err(Error::error(format!(
+        "Invalid repository `{}/{}`",
+        event.repo_owner, event.repo_name
+      )));
+    }
+
+    let dir = self
+      .root
+      .join(&event.repo_owner)
+      .join(&event.repo_name)
+      .join(&self.workflows_dir);
+
+    let mut entries = match tokio::fs::read_dir(&dir).await {
+      Ok(entries) => entries,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+        log::trace!("No workflows found in {}", dir.display());
+        return Ok(vec![]);
+      }
+      Err(err) => return Err(err.into()),
+    };
+
+    let mut workflows = vec![];
+
+    while let Some(entry) = entries.next_entry().await? {
+      let path = entry.path();
+      let is_yaml = matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("yml" | "yaml")
+      );
+
+      if !is_yaml {
+        continue;
+      }
+
+      let name = path
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+      workflows.push((name, tokio::fs::read_to_string(&path).await?));
+    }
+
+    workflows.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(workflows)
+  }
+}
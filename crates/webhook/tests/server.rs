@@ -0,0 +1,251 @@
+use astro_run::{stream, AstroRun, Context, RunResult, Runner};
+use astro_run_webhook::{
+  AstroRunWebhookServer, AstroRunWebhookServerBuilder, FileSystemWorkflowSource,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::path::PathBuf;
+
+struct TestRunner;
+
+#[astro_run::async_trait]
+impl Runner for TestRunner {
+  async fn run(&self, ctx: Context) -> astro_run::RunResponse {
+    let (tx, rx) = stream();
+    tx.log(ctx.command.run);
+    tx.end(RunResult::Succeeded);
+
+    Ok(rx)
+  }
+}
+
+struct Workspace(PathBuf);
+
+impl Workspace {
+  fn new() -> Self {
+    let root = std::env::temp_dir().join(uuid());
+    let dir = root.join("panghu-huang/astro-run/.astro-run/workflows");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    std::fs::write(
+      dir.join("release.yml"),
+      r#"
+on:
+  tag:
+    branches: [v*]
+jobs:
+  release:
+    steps:
+      - run: echo release
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+      dir.join("nightly.yaml"),
+      r#"
+on:
+  tag:
+    branches: [nightly-*]
+jobs:
+  nightly:
+    steps:
+      - run: echo nightly
+"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("README.md"), "Not a workflow").unwrap();
+
+    Self(root)
+  }
+}
+
+impl Drop for Workspace {
+  fn drop(&mut self) {
+    std::fs::remove_dir_all(&self.0).ok();
+  }
+}
+
+fn uuid() -> String {
+  format!(
+    "astro-run-webhook-{}",
+    std::time::SystemTime::now()
+      .duration_since(std::time::UNIX_EPOCH)
+      .unwrap()
+      .as_nanos()
+  )
+}
+
+fn sign(secret: &str, body: &str) -> String {
+  let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+  mac.update(body.as_bytes());
+
+  format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+async fn start_server(workspace: &Workspace) -> String {
+  serve(
+    AstroRunWebhookServer::builder()
+      .workflow_source(FileSystemWorkflowSource::new(&workspace.0))
+      .github_secret("github-secret")
+      .gitlab_token("gitlab-token"),
+  )
+}
+
+fn serve(builder: AstroRunWebhookServerBuilder) -> String {
+  let astro_run = AstroRun::builder().runner(TestRunner).build();
+  let server = builder.astro_run(astro_run).build().unwrap();
+
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+
+  tokio::spawn(server.serve_with_listener(listener));
+
+  format!("http://{}", addr)
+}
+
+#[astro_run_test::test]
+async fn test_github_webhook() {
+  let workspace = Workspace::new();
+  let url = format!("{}/webhooks/github", start_server(&workspace).await);
+  let client = reqwest::Client::new();
+
+  let body = serde_json::json!({
+    "ref": "refs/tags/v1.0.0",
+    "before": "0000000000000000000000000000000000000000",
+    "after": "abcdef",
+    "repository": {
+      "name": "astro-run",
+      "owner": { "login": "panghu-huang" }
+    }
+  })
+  .to_string();
+
+  let response = client
+    .post(&url)
+    .header("X-GitHub-Event", "push")
+    .header("X-Hub-Signature-256", sign("github-secret", &body))
+    .body(body.clone())
+    .send()
+    .await
+    .unwrap();
+
+  assert_eq!(response.status(), 202);
+  let ids: Vec<String> = response.json().await.unwrap();
+  // Only `release.yml` matches the tag
+  assert_eq!(ids.len(), 1);
+
+  let response = client
+    .post(&url)
+    .header("X-GitHub-Event", "push")
+    .header("X-Hub-Signature-256", sign("invalid-secret", &body))
+    .body(body)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), 401);
+
+  let body = r#"{"zen":"Keep it logically awesome.","hook_id":1}"#;
+  let response = client
+    .post(&url)
+    .header("X-GitHub-Event", "ping")
+    .header("X-Hub-Signature-256", sign("github-secret", body))
+    .body(body)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), 202);
+  assert_eq!(response.text().await.unwrap(), "[]");
+
+  let response = client
+    .post(&url)
+    .header("X-GitHub-Event", "push")
+    .header("X-Hub-Signature-256", sign("github-secret", "invalid"))
+    .body("invalid")
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), 400);
+}
+
+#[astro_run_test::test]
+async fn test_gitlab_webhook() {
+  let workspace = Workspace::new();
+  let url = format!("{}/webhooks/gitlab", start_server(&workspace).await);
+  let client = reqwest::Client::new();
+
+  let body = serde_json::json!({
+    "ref": "refs/tags/nightly-20240101",
+    "checkout_sha": "abcdef",
+    "project": { "path_with_namespace": "panghu-huang/astro-run" }
+  })
+  .to_string();
+
+  let response = client
+    .post(&url)
+    .header("X-Gitlab-Event", "Tag Push Hook")
+    .header("X-Gitlab-Token", "gitlab-token")
+    .body(body.clone())
+    .send()
+    .await
+    .unwrap();
+
+  assert_eq!(response.status(), 202);
+  let ids: Vec<String> = response.json().await.unwrap();
+  assert_eq!(ids.len(), 1);
+
+  let response = client
+    .post(&url)
+    .header("X-Gitlab-Event", "Tag Push Hook")
+    .header("X-Gitlab-Token", "invalid-token")
+    .body(body)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), 401);
+}
+
+#[astro_run_test::test]
+async fn test_webhook_without_secret() {
+  let workspace = Workspace::new();
+  let client = reqwest::Client::new();
+
+  let body = serde_json::json!({
+    "ref": "refs/tags/v1.0.0",
+    "after": "abcdef",
+    "repository": {
+      "name": "astro-run",
+      "owner": { "login": "panghu-huang" }
+    }
+  })
+  .to_string();
+
+  let url = serve(
+    AstroRunWebhookServer::builder().workflow_source(FileSystemWorkflowSource::new(&workspace.0)),
+  );
+  for provider in ["github", "gitlab"] {
+    let response = client
+      .post(format!("{}/webhooks/{}", url, provider))
+      .header("X-GitHub-Event", "push")
+      .body(body.clone())
+      .send()
+      .await
+      .unwrap();
+    assert_eq!(response.status(), 401);
+  }
+
+  let url = serve(
+    AstroRunWebhookServer::builder()
+      .workflow_source(FileSystemWorkflowSource::new(&workspace.0))
+      .insecure_no_verification(),
+  );
+  let response = client
+    .post(format!("{}/webhooks/github", url))
+    .header("X-GitHub-Event", "push")
+    .body(body)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(response.status(), 202);
+  let ids: Vec<String> = response.json().await.unwrap();
+  assert_eq!(ids.len(), 1);
+}